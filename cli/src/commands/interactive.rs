@@ -1,6 +1,7 @@
 //! Interactive mode command
 
 use crate::interactive::app::run_rich_interactive;
+use crate::output::interactive_handler::InteractiveOutputConfig;
 use anyhow::Result;
 use std::path::PathBuf;
 use tracing::debug;
@@ -9,6 +10,8 @@ use tracing::debug;
 pub async fn interactive_command(
     config_loader: crate::config::CliConfigLoader,
     trajectory_file: Option<PathBuf>,
+    session_file: Option<PathBuf>,
+    output_config: InteractiveOutputConfig,
     debug_output: bool,
 ) -> Result<()> {
     if debug_output {
@@ -19,6 +22,10 @@ pub async fn interactive_command(
         debug!("Trajectory file: {}", trajectory_file.display());
     }
 
+    if let Some(session_file) = &session_file {
+        debug!("Session file: {}", session_file.display());
+    }
+
     // Load LLM configuration
     let llm_config = config_loader.load().await?;
     if debug_output {
@@ -35,5 +42,12 @@ pub async fn interactive_command(
     }
 
     // Run the interactive mode (always use rich mode)
-    run_rich_interactive(llm_config, project_path, debug_output).await
+    run_rich_interactive(
+        llm_config,
+        project_path,
+        session_file,
+        output_config,
+        debug_output,
+    )
+    .await
 }
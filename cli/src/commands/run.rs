@@ -14,13 +14,14 @@ pub struct RunConfig {
     pub patch_path: PathBuf,
     pub working_dir: Option<PathBuf>,
     pub debug_output: bool,
+    pub output_config: crate::output::cli_handler::CliOutputConfig,
 }
 
 /// Execute a single task
 pub async fn run_command(config: RunConfig) -> Result<()> {
     info!("Executing task: {}", config.task);
 
-    use crate::output::cli_handler::{CliOutputConfig, CliOutputHandler};
+    use crate::output::cli_handler::CliOutputHandler;
     use coro_core::{trajectory::TrajectoryRecorder, AgentBuilder, AgentConfig, OutputMode};
 
     // Load LLM configuration
@@ -41,10 +42,7 @@ pub async fn run_command(config: RunConfig) -> Result<()> {
     }
 
     // Create CLI output handler
-    let cli_config = CliOutputConfig {
-        realtime_updates: true, // Always enable realtime updates for better UX
-    };
-    let cli_output = Box::new(CliOutputHandler::new(cli_config));
+    let cli_output = Box::new(CliOutputHandler::new(config.output_config));
 
     // Build agent with new configuration system and CLI tools
     let cli_tool_registry = crate::tools::create_cli_tool_registry();
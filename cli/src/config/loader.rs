@@ -8,6 +8,7 @@
 //! 5. Environment variables only (no files)
 
 use anyhow::{anyhow, Context, Result};
+use coro_core::config::{HttpClientConfig, MaxTokensField, RetryConfig};
 use coro_core::{ModelParams, Protocol, ResolvedLlmConfig};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -30,6 +31,16 @@ pub struct RawConfig {
     /// Additional headers (optional)
     #[serde(default)]
     pub headers: HashMap<String, String>,
+    /// HTTP connection pooling and keep-alive settings (optional)
+    #[serde(default)]
+    pub http: HttpClientConfig,
+    /// Token limit field for OpenAI-compatible APIs: "auto", "max_tokens" or
+    /// "max_completion_tokens" (optional)
+    #[serde(default)]
+    pub max_tokens_field: MaxTokensField,
+    /// Retry policy for transient provider errors (optional)
+    #[serde(default)]
+    pub retry: RetryConfig,
 }
 
 /// CLI configuration loader
@@ -284,6 +295,9 @@ impl CliConfigLoader {
             model,
             params: ModelParams::default(),
             headers: HashMap::new(),
+            http: HttpClientConfig::default(),
+            max_tokens_field: MaxTokensField::default(),
+            retry: RetryConfig::default(),
         })
     }
 
@@ -378,7 +392,10 @@ impl CliConfigLoader {
         // Create resolved config
         let resolved = ResolvedLlmConfig::new(protocol, base_url, api_key, config.model)
             .with_params(config.params)
-            .with_headers(config.headers);
+            .with_headers(config.headers)
+            .with_http_config(config.http)
+            .with_max_tokens_field(config.max_tokens_field)
+            .with_retry_config(config.retry);
 
         // Validate
         resolved
@@ -8,6 +8,7 @@ use crate::interactive::message_handler::{app_message_to_ui_message, AppMessage}
 use crate::interactive::pages::main_page::MainPage;
 use crate::interactive::pages::router_test::RouterTestPage;
 use crate::interactive::terminal_output::{output_content_block, overwrite_previous_lines};
+use crate::output::interactive_handler::InteractiveOutputConfig;
 use anyhow::Result;
 use coro_core::ResolvedLlmConfig;
 use coro_router as router;
@@ -274,6 +275,8 @@ pub fn submit_task_with_file_processing(
     project_path: PathBuf,
     ui_sender: broadcast::Sender<AppMessage>,
     agent: Arc<Mutex<Option<coro_core::agent::AgentCore>>>,
+    session_file: Option<PathBuf>,
+    output_config: InteractiveOutputConfig,
 ) {
     use crate::interactive::message_handler::get_random_status_word;
 
@@ -309,6 +312,8 @@ pub fn submit_task_with_file_processing(
                     project_path_clone,
                     ui_sender_clone,
                     agent_clone.clone(),
+                    session_file.clone(),
+                    output_config.clone(),
                 );
             }
             Err(e) => {
@@ -329,6 +334,8 @@ pub fn submit_task_with_file_processing(
                     project_path_clone,
                     ui_sender_clone,
                     agent_clone,
+                    session_file,
+                    output_config,
                 );
             }
         }
@@ -345,6 +352,10 @@ struct AppContext {
     debug_model: bool,
     // Persistent agent instance for conversation continuity
     agent: Arc<Mutex<Option<coro_core::agent::AgentCore>>>,
+    // Where the agent's context is saved on exit and resumed from, if anywhere
+    session_file: Option<PathBuf>,
+    // How tool activity is shown, including which tools are silenced
+    output_config: InteractiveOutputConfig,
 }
 
 impl AppContext {
@@ -352,6 +363,8 @@ impl AppContext {
         llm_config: ResolvedLlmConfig,
         project_path: PathBuf,
         ui_sender: broadcast::Sender<AppMessage>,
+        session_file: Option<PathBuf>,
+        output_config: InteractiveOutputConfig,
         debug_model: bool,
     ) -> Self {
         let ui_anim = UiAnimationConfig::from_env();
@@ -363,6 +376,8 @@ impl AppContext {
             ui_anim,
             debug_model,
             agent: Arc::new(Mutex::new(None)),
+            session_file,
+            output_config,
         }
     }
 }
@@ -371,11 +386,21 @@ impl AppContext {
 pub async fn run_rich_interactive(
     llm_config: ResolvedLlmConfig,
     project_path: PathBuf,
+    session_file: Option<PathBuf>,
+    output_config: InteractiveOutputConfig,
     debug_model: bool,
 ) -> Result<()> {
     // Create UI broadcast channel and app context
     let (ui_sender, _) = broadcast::channel::<AppMessage>(256);
-    let app_context = AppContext::new(llm_config, project_path, ui_sender, debug_model);
+    let app_context = AppContext::new(
+        llm_config,
+        project_path,
+        ui_sender,
+        session_file.clone(),
+        output_config,
+        debug_model,
+    );
+    let agent = app_context.agent.clone();
 
     // Run the iocraft-based UI with context provider in render loop mode
     tokio::task::spawn_blocking(move || {
@@ -391,6 +416,22 @@ pub async fn run_rich_interactive(
     })
     .await??;
 
+    // Save the conversation so the next launch can resume it
+    if let Some(session_file) = session_file {
+        // A task still holding the agent would block exit, so skip the save instead
+        match agent.try_lock() {
+            Ok(guard) => {
+                if let Some(agent) = guard.as_ref() {
+                    crate::interactive::task_executor::save_session(agent, &session_file)?;
+                }
+            }
+            Err(_) => tracing::warn!(
+                "A task was still running on exit; session not saved to {}",
+                session_file.display()
+            ),
+        }
+    }
+
     Ok(())
 }
 
@@ -553,6 +594,8 @@ fn CoroApp(mut hooks: Hooks) -> impl Into<AnyElement<'static>> {
         project_path: app_context.project_path.clone(),
         ui_sender: app_context.ui_sender.clone(),
         agent: app_context.agent.clone(),
+        session_file: app_context.session_file.clone(),
+        output_config: app_context.output_config.clone(),
     };
 
     // 使用 coro-router 构建路由并提供上下文
@@ -11,6 +11,7 @@ use crate::interactive::file_search::{
 };
 use crate::interactive::input_history::InputHistory;
 use crate::interactive::message_handler::AppMessage;
+use crate::output::interactive_handler::InteractiveOutputConfig;
 use coro_core::ResolvedLlmConfig;
 use coro_router::use_router;
 use iocraft::prelude::*;
@@ -119,6 +120,8 @@ impl Default for InputSectionProps {
                 project_path: PathBuf::new(),
                 ui_sender: tokio::sync::broadcast::channel(1).0,
                 agent: Arc::new(Mutex::new(None)),
+                session_file: None,
+                output_config: InteractiveOutputConfig::default(),
             },
         }
     }
@@ -131,6 +134,8 @@ pub struct InputSectionContext {
     pub project_path: PathBuf,
     pub ui_sender: broadcast::Sender<AppMessage>,
     pub agent: Arc<Mutex<Option<coro_core::agent::AgentCore>>>,
+    pub session_file: Option<PathBuf>,
+    pub output_config: InteractiveOutputConfig,
 }
 
 /// Enhanced text input component that wraps iocraft's TextInput with submit handling
@@ -933,6 +938,8 @@ pub fn spawn_ui_agent_task_with_context(
     project_path: PathBuf,
     ui_sender: broadcast::Sender<AppMessage>,
     agent: Arc<Mutex<Option<coro_core::agent::AgentCore>>>,
+    session_file: Option<PathBuf>,
+    output_config: InteractiveOutputConfig,
 ) {
     use crate::interactive::message_handler::get_random_status_word;
     use crate::interactive::task_executor::execute_agent_task_with_context;
@@ -968,6 +975,8 @@ pub fn spawn_ui_agent_task_with_context(
             project_path,
             ui_sender.clone(),
             agent,
+            session_file,
+            output_config,
         )
         .await
         {
@@ -1066,10 +1075,14 @@ pub fn InputSection(mut hooks: Hooks, props: &InputSectionProps) -> impl Into<An
 
     // Navigation is now handled directly in keyboard events - no async needed
 
+    // Id of the confirmation request currently waiting for a y/n answer
+    let pending_confirmation = hooks.use_state(|| None::<String>);
+
     // Subscribe to UI events to track task status
     let ui_sender_status = context.ui_sender.clone();
     let mut is_task_running_clone = is_task_running;
     let mut current_user_input_clone = current_user_input;
+    let mut pending_confirmation_clone = pending_confirmation;
     hooks.use_future(async move {
         let mut rx = ui_sender_status.subscribe();
         while let Ok(event) = rx.recv().await {
@@ -1081,10 +1094,17 @@ pub fn InputSection(mut hooks: Hooks, props: &InputSectionProps) -> impl Into<An
                 | AppMessage::AgentExecutionInterrupted { .. } => {
                     is_task_running_clone.set(false);
                     current_user_input_clone.set(String::new());
+                    pending_confirmation_clone.set(None);
                 }
                 AppMessage::UserMessage(input) => {
                     current_user_input_clone.set(input);
                 }
+                AppMessage::ConfirmationRequested { request } => {
+                    pending_confirmation_clone.set(Some(request.id));
+                }
+                AppMessage::ConfirmationResolved { .. } => {
+                    pending_confirmation_clone.set(None);
+                }
                 _ => {}
             }
         }
@@ -1109,13 +1129,36 @@ pub fn InputSection(mut hooks: Hooks, props: &InputSectionProps) -> impl Into<An
                 TerminalEvent::Key(KeyEvent { code, kind, .. })
                     if kind != KeyEventKind::Release =>
                 {
+                    // Answer a pending confirmation before anything else
+                    let pending_request_id = pending_confirmation.read().clone();
+                    if let Some(request_id) = pending_request_id {
+                        let approved = match code {
+                            KeyCode::Char('y') | KeyCode::Char('Y') => Some(true),
+                            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Enter => Some(false),
+                            _ => None,
+                        };
+                        if let Some(approved) = approved {
+                            let _ = ui_sender.send(AppMessage::ConfirmationResolved {
+                                request_id,
+                                decision: coro_core::output::ConfirmationDecision {
+                                    approved,
+                                    note: None,
+                                    approved_ids: None,
+                                },
+                            });
+                            return;
+                        }
+                    }
+
                     match code {
                         KeyCode::Esc => {
                             // Handle ESC key - interrupt current task if running
                             if *is_task_running.read() {
                                 let user_input = current_user_input.read().clone();
-                                let _ = ui_sender
-                                    .send(AppMessage::AgentExecutionInterrupted { user_input });
+                                let _ = ui_sender.send(AppMessage::AgentExecutionInterrupted {
+                                    user_input,
+                                    reason: Some("user pressed Esc".to_string()),
+                                });
                             }
                         }
                         KeyCode::Up => {
@@ -1192,6 +1235,8 @@ pub fn InputSection(mut hooks: Hooks, props: &InputSectionProps) -> impl Into<An
                     let llm_config = llm_config.clone();
                     let project_path = project_path.clone();
                     let agent = context.agent.clone();
+                    let session_file = context.session_file.clone();
+                    let output_config = context.output_config.clone();
                     move |input: String| {
                         if input.trim().is_empty() {
                             return;
@@ -1225,6 +1270,8 @@ pub fn InputSection(mut hooks: Hooks, props: &InputSectionProps) -> impl Into<An
                             project_path.clone(),
                             ui_sender.clone(),
                             agent.clone(),
+                            session_file.clone(),
+                            output_config.clone(),
                         );
                     }
                 },
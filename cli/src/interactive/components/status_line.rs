@@ -89,7 +89,9 @@ pub fn DynamicStatusLine(
                 }
                 AppMessage::SystemMessage(_)
                 | AppMessage::UserMessage(_)
-                | AppMessage::InteractiveUpdate(_) => {
+                | AppMessage::InteractiveUpdate(_)
+                | AppMessage::ConfirmationRequested { .. }
+                | AppMessage::ConfirmationResolved { .. } => {
                     // Ignored for status line
                 }
             }
@@ -4,6 +4,7 @@
 //! and message processing logic for the interactive UI.
 
 use crate::output::interactive_handler::InteractiveMessage;
+use coro_core::tools::utils::truncate_to_char_boundary;
 use rand::seq::SliceRandom;
 
 /// Random status words for initial display
@@ -71,10 +72,27 @@ pub enum AppMessage {
     SystemMessage(String),
     UserMessage(String),
     InteractiveUpdate(InteractiveMessage),
-    AgentTaskStarted { operation: String },
+    AgentTaskStarted {
+        operation: String,
+    },
     AgentExecutionCompleted,
-    AgentExecutionInterrupted { user_input: String },
-    TokenUpdate { tokens: u32 },
+    AgentExecutionInterrupted {
+        user_input: String,
+        /// Why the task was interrupted, e.g. "user pressed Esc"
+        reason: Option<String>,
+    },
+    TokenUpdate {
+        tokens: u32,
+    },
+    /// The agent is waiting for the user to approve or deny an action
+    ConfirmationRequested {
+        request: coro_core::output::ConfirmationRequest,
+    },
+    /// The user answered a pending confirmation request
+    ConfirmationResolved {
+        request_id: String,
+        decision: coro_core::output::ConfirmationDecision,
+    },
 }
 
 /// Get a random status word
@@ -216,14 +234,51 @@ pub fn app_message_to_ui_message(
         },
         AppMessage::AgentTaskStarted { .. } => None,
         AppMessage::AgentExecutionCompleted => None,
-        AppMessage::AgentExecutionInterrupted { user_input: _ } => Some((
+        AppMessage::AgentExecutionInterrupted { .. } => Some((
             "system".to_string(),
             "  \x1b[31m⏹ Interrupted by user\x1b[0m".to_string(),
             Some(generate_message_id()),
             false,
         )),
         AppMessage::TokenUpdate { .. } => None, // Token updates don't create UI messages, they update state directly
+        AppMessage::ConfirmationRequested { request } => Some((
+            "system".to_string(),
+            format_confirmation_prompt(&request),
+            Some(request.id.clone()),
+            false,
+        )),
+        AppMessage::ConfirmationResolved { .. } => None,
+    }
+}
+
+/// Format a confirmation request as a prompt the user can answer with y/n
+pub fn format_confirmation_prompt(request: &coro_core::output::ConfirmationRequest) -> String {
+    let mut prompt = request.title.clone();
+    if !request.message.is_empty() {
+        prompt.push('\n');
+        prompt.push_str(&request.message);
+    }
+
+    if let Some(tool) = &request.tool {
+        if !tool.summary.is_empty() {
+            prompt.push_str(&format!("\n  {}", tool.summary));
+        }
+    } else if let Some(params) = request.metadata.get("parameters") {
+        if let Ok(mut preview) = serde_json::to_string(params) {
+            if preview.len() > 200 {
+                preview.truncate(truncate_to_char_boundary(&preview, 200).len());
+                preview.push_str("...");
+            }
+            prompt.push_str(&format!("\nParameters: {}", preview));
+        }
+    }
+
+    if request.kind == coro_core::output::ConfirmationKind::ToolBatch {
+        prompt.push_str("\nConfirm all? [y/N]");
+    } else {
+        prompt.push_str("\nConfirm? [y/N]");
     }
+    prompt
 }
 
 #[cfg(test)]
@@ -261,6 +316,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_format_confirmation_prompt() {
+        let mut metadata = std::collections::HashMap::new();
+        metadata.insert(
+            "parameters".to_string(),
+            serde_json::json!({"command": "rm -rf build"}),
+        );
+        let request = coro_core::output::ConfirmationRequest {
+            id: "call_1".to_string(),
+            kind: coro_core::output::ConfirmationKind::ToolExecution,
+            title: "Execute tool: bash".to_string(),
+            message: "This tool requires confirmation before execution.".to_string(),
+            metadata,
+            tool: Some(coro_core::output::ToolCallPreview::new(
+                "bash",
+                coro_core::tools::ToolKind::Local,
+                serde_json::json!({"command": "rm -rf build"}),
+            )),
+        };
+
+        let prompt = format_confirmation_prompt(&request);
+        assert!(prompt.starts_with("Execute tool: bash"));
+        assert!(prompt.contains("\n  rm -rf build\n"));
+        assert!(!prompt.contains("Parameters:"));
+        assert!(prompt.ends_with("Confirm? [y/N]"));
+
+        let ui_message =
+            app_message_to_ui_message(AppMessage::ConfirmationRequested { request }).unwrap();
+        assert_eq!(ui_message.2, Some("call_1".to_string()));
+    }
+
     #[test]
     fn test_is_bash_output_content() {
         assert!(is_bash_output_content("total 10"));
@@ -7,13 +7,29 @@ use crate::interactive::message_handler::AppMessage;
 use crate::output::interactive_handler::{InteractiveMessage, InteractiveOutputConfig};
 use anyhow::Result;
 use coro_core::ResolvedLlmConfig;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use tokio::sync::{broadcast, mpsc};
 
+/// Default minimum interval between token and identical status updates
+const DEFAULT_UPDATE_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Rate limiting state for token and status updates sent to the UI
+#[derive(Default)]
+struct UpdateThrottle {
+    last_tokens_at: Option<Instant>,
+    /// Latest token count held back by the throttle
+    pending_tokens: Option<u32>,
+    last_status: Option<(String, Instant)>,
+}
+
 /// Custom output handler that forwards events and tracks tokens
 pub struct TokenTrackingOutputHandler {
     interactive_handler: crate::output::interactive_handler::InteractiveOutputHandler,
     ui_sender: broadcast::Sender<AppMessage>,
+    update_interval: Duration,
+    throttle: Mutex<UpdateThrottle>,
 }
 
 impl TokenTrackingOutputHandler {
@@ -28,6 +44,57 @@ impl TokenTrackingOutputHandler {
                 interactive_sender,
             ),
             ui_sender,
+            update_interval: DEFAULT_UPDATE_INTERVAL,
+            throttle: Mutex::new(UpdateThrottle::default()),
+        }
+    }
+
+    /// Set the minimum interval between token updates and between identical
+    /// status updates; the latest token count is still sent on flush
+    pub fn with_update_interval(mut self, interval: Duration) -> Self {
+        self.update_interval = interval;
+        self
+    }
+
+    fn send_token_update(&self, tokens: u32) {
+        let now = Instant::now();
+        {
+            let mut throttle = self.throttle.lock().unwrap();
+            let due = throttle
+                .last_tokens_at
+                .is_none_or(|last| now.duration_since(last) >= self.update_interval);
+            if !due {
+                throttle.pending_tokens = Some(tokens);
+                return;
+            }
+            throttle.last_tokens_at = Some(now);
+            throttle.pending_tokens = None;
+        }
+        let _ = self.ui_sender.send(AppMessage::TokenUpdate { tokens });
+    }
+
+    fn send_status_update(&self, status: &str) {
+        let now = Instant::now();
+        {
+            let mut throttle = self.throttle.lock().unwrap();
+            let repeated = throttle.last_status.as_ref().is_some_and(|(last, at)| {
+                last == status && now.duration_since(*at) < self.update_interval
+            });
+            if repeated {
+                return;
+            }
+            throttle.last_status = Some((status.to_string(), now));
+        }
+        let _ = self.ui_sender.send(AppMessage::AgentTaskStarted {
+            operation: status.to_string(),
+        });
+    }
+
+    /// Send the token count held back by the throttle, if any
+    fn flush_pending_tokens(&self) {
+        let pending = self.throttle.lock().unwrap().pending_tokens.take();
+        if let Some(tokens) = pending {
+            let _ = self.ui_sender.send(AppMessage::TokenUpdate { tokens });
         }
     }
 }
@@ -41,23 +108,24 @@ impl coro_core::output::AgentOutput for TokenTrackingOutputHandler {
         // Check for token updates and status updates in various events
         match &event {
             coro_core::output::AgentEvent::ExecutionCompleted { context, .. } => {
+                // The final count supersedes anything the throttle held back
+                self.throttle.lock().unwrap().pending_tokens = None;
                 if context.token_usage.total_tokens > 0 {
                     let _ = self.ui_sender.send(AppMessage::TokenUpdate {
                         tokens: context.token_usage.total_tokens,
                     });
                 }
             }
+            coro_core::output::AgentEvent::ExecutionInterrupted { .. } => {
+                // No final count follows an interruption
+                self.flush_pending_tokens();
+            }
             coro_core::output::AgentEvent::TokenUsageUpdated { token_usage } => {
-                // Send immediate token update for smooth animation
-                let _ = self.ui_sender.send(AppMessage::TokenUpdate {
-                    tokens: token_usage.total_tokens,
-                });
+                // Throttled so bursts of updates do not flood the UI channel
+                self.send_token_update(token_usage.total_tokens);
             }
             coro_core::output::AgentEvent::StatusUpdate { status, .. } => {
-                // Send status update to UI
-                let _ = self.ui_sender.send(AppMessage::AgentTaskStarted {
-                    operation: status.clone(),
-                });
+                self.send_status_update(status);
             }
             _ => {}
         }
@@ -66,15 +134,75 @@ impl coro_core::output::AgentOutput for TokenTrackingOutputHandler {
         self.interactive_handler.emit_event(event).await
     }
 
+    async fn request_confirmation(
+        &self,
+        request: &coro_core::output::ConfirmationRequest,
+    ) -> Result<coro_core::output::ConfirmationDecision, Box<dyn std::error::Error + Send + Sync>>
+    {
+        // Subscribe before publishing so the answer cannot be missed
+        let mut rx = self.ui_sender.subscribe();
+        let _ = self.ui_sender.send(AppMessage::ConfirmationRequested {
+            request: request.clone(),
+        });
+
+        loop {
+            match rx.recv().await {
+                Ok(AppMessage::ConfirmationResolved {
+                    request_id,
+                    decision,
+                }) if request_id == request.id => return Ok(decision),
+                Ok(AppMessage::AgentExecutionInterrupted { .. }) => {
+                    return Ok(coro_core::output::ConfirmationDecision {
+                        approved: false,
+                        note: Some("Task interrupted before confirmation".to_string()),
+                        approved_ids: None,
+                    });
+                }
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => {
+                    return Ok(coro_core::output::ConfirmationDecision {
+                        approved: false,
+                        note: Some("UI channel closed; default deny".to_string()),
+                        approved_ids: None,
+                    });
+                }
+            }
+        }
+    }
+
     fn supports_realtime_updates(&self) -> bool {
         self.interactive_handler.supports_realtime_updates()
     }
 
     async fn flush(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.flush_pending_tokens();
         self.interactive_handler.flush().await
     }
 }
 
+/// Save the agent's context to `path` so a later session can resume it
+pub fn save_session(agent: &coro_core::agent::AgentCore, path: &Path) -> Result<()> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(parent)?;
+    }
+    agent.export_context_to_file(path)?;
+    Ok(())
+}
+
+/// Resume the session saved at `path` into `agent`
+///
+/// Returns `false` without touching the agent if no session was saved yet.
+pub fn restore_session(agent: &mut coro_core::agent::AgentCore, path: &Path) -> Result<bool> {
+    if !path.exists() {
+        return Ok(false);
+    }
+    agent.restore_context_from_file(path)?;
+    Ok(true)
+}
+
 /// Execute agent task with persistent agent to maintain conversation context
 pub async fn execute_agent_task_with_context(
     task: String,
@@ -82,6 +210,8 @@ pub async fn execute_agent_task_with_context(
     project_path: PathBuf,
     ui_sender: broadcast::Sender<AppMessage>,
     agent: std::sync::Arc<tokio::sync::Mutex<Option<coro_core::agent::AgentCore>>>,
+    session_file: Option<PathBuf>,
+    output_config: InteractiveOutputConfig,
 ) -> Result<()> {
     // Create a receiver to listen for interruption signals
     let mut interrupt_receiver = ui_sender.subscribe();
@@ -116,12 +246,8 @@ pub async fn execute_agent_task_with_context(
         }
 
         // Create TokenTrackingOutputHandler with UI integration
-        let interactive_config = InteractiveOutputConfig {
-            realtime_updates: true,
-            show_tool_details: true,
-        };
         let token_tracking_output = Box::new(TokenTrackingOutputHandler::new(
-            interactive_config,
+            output_config,
             interactive_sender,
             ui_sender.clone(),
         ));
@@ -133,7 +259,7 @@ pub async fn execute_agent_task_with_context(
         )));
 
         // Create new agent with abort controller
-        let new_agent = coro_core::agent::AgentCore::new_with_output_and_registry(
+        let mut new_agent = coro_core::agent::AgentCore::new_with_output_and_registry(
             agent_config,
             llm_config,
             token_tracking_output,
@@ -142,6 +268,27 @@ pub async fn execute_agent_task_with_context(
         )
         .await?;
 
+        // Continue the conversation from the previous launch, if saved
+        if let Some(session_file) = &session_file {
+            match restore_session(&mut new_agent, session_file) {
+                Ok(true) => {
+                    let _ = ui_sender.send(AppMessage::SystemMessage(format!(
+                        "Resumed session from {}",
+                        session_file.display()
+                    )));
+                }
+                Ok(false) => {}
+                // An unreadable session must not block every task; start fresh instead
+                Err(e) => {
+                    let _ = ui_sender.send(AppMessage::SystemMessage(format!(
+                        "Could not resume session from {}: {}. Starting a new session.",
+                        session_file.display(),
+                        e
+                    )));
+                }
+            }
+        }
+
         *agent_guard = Some(new_agent);
     } else {
         // Agent exists, update its abort controller for this task
@@ -161,15 +308,17 @@ pub async fn execute_agent_task_with_context(
     let interrupt_future = async move {
         loop {
             match interrupt_receiver.recv().await {
-                Ok(AppMessage::AgentExecutionInterrupted { .. }) => {
-                    abort_controller_for_cancel.cancel();
-                    return Err(anyhow::anyhow!("Task interrupted by user"));
+                Ok(AppMessage::AgentExecutionInterrupted { reason, .. }) => {
+                    abort_controller_for_cancel.cancel_with_reason(
+                        reason.unwrap_or_else(|| "Task interrupted by user".to_string()),
+                    );
+                    // Let the task wind down so it reports the interruption
+                    std::future::pending::<()>().await;
                 }
                 Ok(_) => continue, // Ignore other messages
                 Err(_) => break,   // Channel closed
             }
         }
-        Ok(())
     };
 
     // Race between task execution and interruption
@@ -177,9 +326,7 @@ pub async fn execute_agent_task_with_context(
         result = task_future => {
             result?;
         }
-        interrupt_result = interrupt_future => {
-            interrupt_result?;
-        }
+        _ = interrupt_future => {}
     }
 
     Ok(())
@@ -220,6 +367,7 @@ pub async fn execute_agent_task(
     let interactive_config = InteractiveOutputConfig {
         realtime_updates: true,
         show_tool_details: true,
+        ..Default::default()
     };
     let token_tracking_output = Box::new(TokenTrackingOutputHandler::new(
         interactive_config,
@@ -254,16 +402,17 @@ pub async fn execute_agent_task(
     let interrupt_future = async move {
         loop {
             match interrupt_receiver.recv().await {
-                Ok(AppMessage::AgentExecutionInterrupted { .. }) => {
-                    abort_controller_for_cancel.cancel();
-                    tracing::warn!("Task interrupted by user");
-                    return Err(anyhow::anyhow!("Task interrupted by user"));
+                Ok(AppMessage::AgentExecutionInterrupted { reason, .. }) => {
+                    let reason = reason.unwrap_or_else(|| "Task interrupted by user".to_string());
+                    tracing::warn!("{}", reason);
+                    abort_controller_for_cancel.cancel_with_reason(reason);
+                    // Let the task wind down so it reports the interruption
+                    std::future::pending::<()>().await;
                 }
                 Ok(_) => continue, // Ignore other messages
                 Err(_) => break,   // Channel closed
             }
         }
-        Ok(())
     };
 
     // Race between task execution and interruption
@@ -271,9 +420,7 @@ pub async fn execute_agent_task(
         result = task_future => {
             result?;
         }
-        interrupt_result = interrupt_future => {
-            interrupt_result?;
-        }
+        _ = interrupt_future => {}
     }
 
     Ok(())
@@ -292,9 +439,353 @@ mod tests {
         let config = InteractiveOutputConfig {
             realtime_updates: true,
             show_tool_details: true,
+            ..Default::default()
         };
 
         let handler = TokenTrackingOutputHandler::new(config, interactive_sender, ui_sender);
         assert!(handler.supports_realtime_updates());
     }
+
+    fn confirmation_request(id: &str) -> coro_core::output::ConfirmationRequest {
+        coro_core::output::ConfirmationRequest {
+            id: id.to_string(),
+            kind: coro_core::output::ConfirmationKind::ToolExecution,
+            title: "Execute tool: bash".to_string(),
+            message: "This tool requires confirmation before execution.".to_string(),
+            metadata: std::collections::HashMap::new(),
+            tool: None,
+        }
+    }
+
+    fn test_handler(ui_sender: broadcast::Sender<AppMessage>) -> TokenTrackingOutputHandler {
+        let (interactive_sender, _) = mpsc::unbounded_channel();
+        let config = InteractiveOutputConfig {
+            realtime_updates: true,
+            show_tool_details: true,
+            ..Default::default()
+        };
+        TokenTrackingOutputHandler::new(config, interactive_sender, ui_sender)
+    }
+
+    /// Tool that needs confirmation and counts how often it actually ran
+    struct GuardedTool {
+        runs: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl coro_core::tools::Tool for GuardedTool {
+        fn name(&self) -> &str {
+            "guarded"
+        }
+
+        fn description(&self) -> &str {
+            "Needs confirmation before it runs"
+        }
+
+        fn parameters_schema(&self) -> serde_json::Value {
+            serde_json::json!({"type": "object", "properties": {}})
+        }
+
+        async fn execute(
+            &self,
+            call: coro_core::tools::ToolCall,
+        ) -> coro_core::error::Result<coro_core::tools::ToolResult> {
+            self.runs.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(coro_core::tools::ToolResult::success(
+                call.id.as_str(),
+                "ran",
+            ))
+        }
+
+        fn requires_confirmation(&self) -> bool {
+            true
+        }
+    }
+
+    struct GuardedToolFactory {
+        runs: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    impl coro_core::tools::ToolFactory for GuardedToolFactory {
+        fn create(&self) -> Box<dyn coro_core::tools::Tool> {
+            Box::new(GuardedTool {
+                runs: self.runs.clone(),
+            })
+        }
+
+        fn tool_name(&self) -> &str {
+            "guarded"
+        }
+
+        fn tool_description(&self) -> &str {
+            "Needs confirmation before it runs"
+        }
+    }
+
+    /// Run one `guarded` call, answering its confirmation over the UI channel
+    ///
+    /// Returns how often the tool ran and the tool result the model saw.
+    async fn run_confirmed_tool_call(approved: bool) -> (usize, String) {
+        use coro_core::llm::{ContentBlock, MessageContent};
+
+        let (ui_sender, _) = broadcast::channel::<AppMessage>(64);
+        let (interactive_sender, _interactive_receiver) = mpsc::unbounded_channel();
+        let handler = TokenTrackingOutputHandler::new(
+            InteractiveOutputConfig {
+                realtime_updates: true,
+                show_tool_details: true,
+                ..Default::default()
+            },
+            interactive_sender,
+            ui_sender.clone(),
+        );
+
+        // Simulate the TUI answering the prompt
+        let mut ui_rx = ui_sender.subscribe();
+        let responder_sender = ui_sender.clone();
+        let responder = tokio::spawn(async move {
+            while let Ok(message) = ui_rx.recv().await {
+                if let AppMessage::ConfirmationRequested { request } = message {
+                    let _ = responder_sender.send(AppMessage::ConfirmationResolved {
+                        request_id: request.id,
+                        decision: coro_core::output::ConfirmationDecision {
+                            approved,
+                            note: None,
+                            approved_ids: None,
+                        },
+                    });
+                    break;
+                }
+            }
+        });
+
+        let runs = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let mut registry = coro_core::tools::ToolRegistry::default();
+        registry.register_factory(Box::new(GuardedToolFactory { runs: runs.clone() }));
+        let client = std::sync::Arc::new(
+            coro_core::llm::ScriptedLlmClient::new()
+                .with_tool_call("guarded", serde_json::json!({}))
+                .with_tool_call("task_done", serde_json::json!({"summary": "Done"})),
+        );
+        let mut agent = coro_core::agent::AgentCore::new_with_client(
+            coro_core::AgentConfig {
+                max_steps: 3,
+                tools: vec!["guarded".to_string(), "task_done".to_string()],
+                ..Default::default()
+            },
+            client.clone(),
+            Box::new(handler),
+            &registry,
+        );
+
+        agent
+            .execute_task_with_context("Run the guarded tool", Path::new("."))
+            .await
+            .unwrap();
+        responder.await.unwrap();
+
+        // The second request carries the result of the guarded call
+        let requests = client.requests();
+        let result = requests[1]
+            .messages
+            .iter()
+            .filter_map(|message| match &message.content {
+                MessageContent::MultiModal(blocks) => Some(blocks),
+                _ => None,
+            })
+            .flatten()
+            .find_map(|block| match block {
+                ContentBlock::ToolResult { content, .. } => Some(content.clone()),
+                _ => None,
+            })
+            .unwrap();
+        (runs.load(std::sync::atomic::Ordering::SeqCst), result)
+    }
+
+    #[tokio::test]
+    async fn test_confirmed_tool_call_runs() {
+        let (runs, result) = run_confirmed_tool_call(true).await;
+        assert_eq!(runs, 1);
+        assert_eq!(result, "ran");
+    }
+
+    #[tokio::test]
+    async fn test_rejected_tool_call_does_not_run() {
+        let (runs, result) = run_confirmed_tool_call(false).await;
+        assert_eq!(runs, 0);
+        assert_eq!(result, "Error: Execution cancelled by user");
+    }
+
+    fn token_event(total_tokens: u32) -> coro_core::output::AgentEvent {
+        coro_core::output::AgentEvent::TokenUsageUpdated {
+            token_usage: coro_core::output::TokenUsage {
+                total_tokens,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn received_tokens(rx: &mut broadcast::Receiver<AppMessage>) -> Vec<u32> {
+        let mut tokens = Vec::new();
+        while let Ok(message) = rx.try_recv() {
+            if let AppMessage::TokenUpdate { tokens: count } = message {
+                tokens.push(count);
+            }
+        }
+        tokens
+    }
+
+    #[tokio::test]
+    async fn test_token_updates_throttled_with_final_flush() {
+        let interval = Duration::from_millis(200);
+        let (ui_sender, mut ui_rx) = broadcast::channel::<AppMessage>(64);
+        let handler = test_handler(ui_sender).with_update_interval(interval);
+
+        for tokens in 1..=10 {
+            handler.emit_event(token_event(tokens)).await.unwrap();
+        }
+        assert_eq!(received_tokens(&mut ui_rx), vec![1]);
+
+        tokio::time::sleep(interval + Duration::from_millis(20)).await;
+        for tokens in 11..=15 {
+            handler.emit_event(token_event(tokens)).await.unwrap();
+        }
+        assert_eq!(received_tokens(&mut ui_rx), vec![11]);
+
+        handler.flush().await.unwrap();
+        assert_eq!(received_tokens(&mut ui_rx), vec![15]);
+    }
+
+    #[tokio::test]
+    async fn test_held_back_tokens_sent_on_interrupt() {
+        let (ui_sender, mut ui_rx) = broadcast::channel::<AppMessage>(64);
+        let handler = test_handler(ui_sender).with_update_interval(Duration::from_secs(60));
+
+        for tokens in [100, 250] {
+            handler.emit_event(token_event(tokens)).await.unwrap();
+        }
+        assert_eq!(received_tokens(&mut ui_rx), vec![100]);
+
+        handler
+            .emit_event(coro_core::output::AgentEvent::ExecutionInterrupted {
+                context: coro_core::output::AgentExecutionContext {
+                    agent_id: "coro".to_string(),
+                    original_goal: "Long task".to_string(),
+                    current_task: "Long task".to_string(),
+                    project_path: ".".to_string(),
+                    max_steps: 10,
+                    current_step: 2,
+                    execution_time: Duration::from_secs(1),
+                    token_usage: Default::default(),
+                    progress: 0.2,
+                    compression_stats: Default::default(),
+                },
+                reason: "Task interrupted by user".to_string(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(received_tokens(&mut ui_rx), vec![250]);
+    }
+
+    #[tokio::test]
+    async fn test_identical_status_updates_throttled() {
+        let (ui_sender, mut ui_rx) = broadcast::channel::<AppMessage>(64);
+        let handler = test_handler(ui_sender).with_update_interval(Duration::from_secs(60));
+
+        for status in ["Reading", "Reading", "Editing", "Editing", "Reading"] {
+            handler
+                .emit_event(coro_core::output::AgentEvent::StatusUpdate {
+                    status: status.to_string(),
+                    metadata: std::collections::HashMap::new(),
+                })
+                .await
+                .unwrap();
+        }
+
+        let mut operations = Vec::new();
+        while let Ok(message) = ui_rx.try_recv() {
+            if let AppMessage::AgentTaskStarted { operation } = message {
+                operations.push(operation);
+            }
+        }
+        assert_eq!(operations, vec!["Reading", "Editing", "Reading"]);
+    }
+
+    #[tokio::test]
+    async fn test_confirmation_denied_on_interrupt() {
+        let (ui_sender, _) = broadcast::channel::<AppMessage>(10);
+        let handler = test_handler(ui_sender.clone());
+
+        let mut ui_rx = ui_sender.subscribe();
+        let responder_sender = ui_sender.clone();
+        let responder = tokio::spawn(async move {
+            while let Ok(message) = ui_rx.recv().await {
+                if let AppMessage::ConfirmationRequested { .. } = message {
+                    let _ = responder_sender.send(AppMessage::AgentExecutionInterrupted {
+                        user_input: String::new(),
+                        reason: None,
+                    });
+                    break;
+                }
+            }
+        });
+
+        let decision = handler
+            .request_confirmation(&confirmation_request("call_2"))
+            .await
+            .unwrap();
+        responder.await.unwrap();
+
+        assert!(!decision.approved);
+    }
+
+    async fn session_agent() -> coro_core::agent::AgentCore {
+        let llm_config = ResolvedLlmConfig::new(
+            coro_core::Protocol::OpenAICompat,
+            "https://api.openai.com/v1".to_string(),
+            "test-key".to_string(),
+            "gpt-4o".to_string(),
+        );
+        coro_core::agent::AgentCore::new_with_output_and_registry(
+            coro_core::AgentConfig::default(),
+            llm_config,
+            Box::new(coro_core::output::events::NullOutput),
+            crate::tools::create_cli_tool_registry(),
+            None,
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_session_saved_on_exit_resumes_conversation() {
+        use coro_core::llm::LlmMessage;
+
+        let dir = tempfile::tempdir().unwrap();
+        let session_file = dir.path().join("sessions").join("last.json");
+
+        // Nothing saved yet: a fresh agent starts a new conversation
+        let mut first = session_agent().await;
+        assert!(!restore_session(&mut first, &session_file).unwrap());
+
+        let history = vec![
+            LlmMessage::user("What does main.rs do?"),
+            LlmMessage::assistant("It parses the CLI arguments and dispatches commands."),
+        ];
+        first.restore_from_history(history.clone()).unwrap();
+        save_session(&first, &session_file).unwrap();
+        drop(first);
+
+        // Next launch picks the conversation up where it stopped
+        let mut second = session_agent().await;
+        assert!(restore_session(&mut second, &session_file).unwrap());
+        let restored = second
+            .export_context_snapshot()
+            .unwrap()
+            .conversation_history;
+        assert_eq!(
+            serde_json::to_value(&restored).unwrap(),
+            serde_json::to_value(&history).unwrap()
+        );
+    }
 }
@@ -25,6 +25,8 @@ mod ui;
 
 use commands::{interactive_command, run_command, test_command, tools_command};
 use config::CliConfigLoader;
+use output::cli_handler::CliOutputConfig;
+use output::interactive_handler::InteractiveOutputConfig;
 
 /// coro - A high-performance AI coding agent
 #[derive(Parser)]
@@ -81,6 +83,14 @@ struct Cli {
     #[arg(long, default_value = "changes.patch")]
     patch_path: PathBuf,
 
+    /// Session file for interactive mode: saved on exit and resumed on the next launch
+    #[arg(long)]
+    session_file: Option<PathBuf>,
+
+    /// Hide the status lines and results of a tool (repeatable)
+    #[arg(long = "silent-tool", value_name = "TOOL")]
+    silent_tools: Vec<String>,
+
     /// The task to execute (if provided, runs in single-task mode)
     task: Option<String>,
 
@@ -124,6 +134,23 @@ fn build_config_loader(cli: &Cli) -> CliConfigLoader {
     loader
 }
 
+/// Build the single-task output configuration from CLI arguments
+fn build_cli_output_config(cli: &Cli) -> CliOutputConfig {
+    CliOutputConfig {
+        realtime_updates: true, // Always enable realtime updates for better UX
+        ..Default::default()
+    }
+    .with_silent_tools(cli.silent_tools.iter().cloned())
+}
+
+/// Build the interactive output configuration from CLI arguments
+fn build_interactive_output_config(cli: &Cli) -> InteractiveOutputConfig {
+    InteractiveOutputConfig {
+        silent_tools: build_cli_output_config(cli).silent_tools,
+        ..Default::default()
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
@@ -146,6 +173,8 @@ async fn main() -> Result<()> {
 
     // Build configuration loader
     let config_loader = build_config_loader(&cli);
+    let cli_output_config = build_cli_output_config(&cli);
+    let interactive_output_config = build_interactive_output_config(&cli);
 
     match (cli.task, cli.command) {
         // If task is provided, run in single-task mode
@@ -159,6 +188,7 @@ async fn main() -> Result<()> {
                 patch_path: cli.patch_path,
                 working_dir: cli.working_dir,
                 debug_output: cli.debug_output,
+                output_config: cli_output_config,
             })
             .await
         }
@@ -172,7 +202,46 @@ async fn main() -> Result<()> {
         (None, Some(Commands::Test)) => test_command().await,
         // Default to interactive mode
         (None, None) => {
-            interactive_command(config_loader, cli.trajectory_file, cli.debug_output).await
+            interactive_command(
+                config_loader,
+                cli.trajectory_file,
+                cli.session_file,
+                interactive_output_config,
+                cli.debug_output,
+            )
+            .await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_silent_tool_flag_reaches_both_output_configs() {
+        let cli = Cli::try_parse_from([
+            "coro",
+            "--silent-tool",
+            "status_report",
+            "--silent-tool",
+            "bash",
+        ])
+        .unwrap();
+
+        let run_renderers = build_cli_output_config(&cli).renderers();
+        let interactive_renderers = build_interactive_output_config(&cli)
+            .cli_config()
+            .renderers();
+        for renderers in [run_renderers, interactive_renderers] {
+            assert!(renderers.is_silent("status_report"));
+            assert!(renderers.is_silent("bash"));
+            // Built-in silent tools stay silent
+            assert!(renderers.is_silent("sequentialthinking"));
+            assert!(!renderers.is_silent("str_replace_based_edit_tool"));
         }
+
+        let cli = Cli::try_parse_from(["coro"]).unwrap();
+        assert!(!build_cli_output_config(&cli).renderers().is_silent("bash"));
     }
 }
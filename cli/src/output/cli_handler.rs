@@ -1,41 +1,60 @@
 //! CLI output handler implementation
 
-use super::formatters::{DiffFormatter, ToolFormatter};
+use super::formatters::ToolFormatter;
+use super::renderers::{SilentRenderer, ToolOutputRenderer, ToolRendererRegistry};
 use async_trait::async_trait;
 use coro_core::output::{AgentEvent, AgentOutput, MessageLevel};
+use coro_core::tools::utils::truncate_to_char_boundary;
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tracing::{debug, error, info, warn};
 
-/// Tools that should not display status indicators
-static SILENT_TOOLS: &[&str] = &["sequentialthinking"];
-
-/// Check if a tool should be silent (no status display)
-fn is_silent_tool(tool_name: &str) -> bool {
-    SILENT_TOOLS.contains(&tool_name)
-}
-
 /// CLI output configuration
 #[derive(Debug, Clone)]
 pub struct CliOutputConfig {
     /// Whether to support real-time updates
     pub realtime_updates: bool,
+    /// Tools whose status lines and results are not shown
+    pub silent_tools: Vec<String>,
 }
 
 impl Default for CliOutputConfig {
     fn default() -> Self {
         Self {
             realtime_updates: true,
+            silent_tools: vec!["sequentialthinking".to_string()],
         }
     }
 }
 
+impl CliOutputConfig {
+    /// Also hide `tools`, on top of the ones already silent
+    pub fn with_silent_tools(mut self, tools: impl IntoIterator<Item = String>) -> Self {
+        for tool_name in tools {
+            if !self.silent_tools.contains(&tool_name) {
+                self.silent_tools.push(tool_name);
+            }
+        }
+        self
+    }
+
+    /// Built-in renderers, with the silent tools hidden
+    pub fn renderers(&self) -> ToolRendererRegistry {
+        let mut renderers = ToolRendererRegistry::new();
+        for tool_name in &self.silent_tools {
+            renderers.register(tool_name.as_str(), Box::new(SilentRenderer));
+        }
+        renderers
+    }
+}
+
 /// CLI output handler that formats events for terminal display
 pub struct CliOutputHandler {
     config: CliOutputConfig,
     tool_formatter: ToolFormatter,
-    diff_formatter: DiffFormatter,
+    /// Per-tool rendering of tool results
+    renderers: ToolRendererRegistry,
     /// Track active tool executions for real-time updates
     active_tools: Arc<Mutex<HashMap<String, coro_core::output::ToolExecutionInfo>>>,
 }
@@ -44,9 +63,9 @@ impl CliOutputHandler {
     /// Create a new CLI output handler
     pub fn new(config: CliOutputConfig) -> Self {
         Self {
+            renderers: config.renderers(),
             config,
             tool_formatter: ToolFormatter::new(),
-            diff_formatter: DiffFormatter::new(),
             active_tools: Arc::new(Mutex::new(HashMap::new())),
         }
     }
@@ -55,6 +74,61 @@ impl CliOutputHandler {
     pub fn default() -> Self {
         Self::new(CliOutputConfig::default())
     }
+
+    /// Render results of `tool_name` with `renderer` instead of the default
+    pub fn with_tool_renderer(
+        mut self,
+        tool_name: impl Into<String>,
+        renderer: Box<dyn ToolOutputRenderer>,
+    ) -> Self {
+        self.renderers.register(tool_name, renderer);
+        self
+    }
+
+    /// Prompt for a batched confirmation: all, none, or a list of call numbers
+    fn read_batch_decision(
+        request: &coro_core::output::ConfirmationRequest,
+    ) -> Result<coro_core::output::ConfirmationDecision, Box<dyn std::error::Error + Send + Sync>>
+    {
+        use std::io::{stdin, stdout, Write};
+
+        let ids: Vec<String> = request
+            .metadata
+            .get("tool_calls")
+            .and_then(|calls| calls.as_array())
+            .map(|calls| {
+                calls
+                    .iter()
+                    .filter_map(|call| call["execution_id"].as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        print!("Confirm? [y]es to all / [N]o / numbers to run (e.g. 1,3): ");
+        stdout().flush()?;
+        let mut line = String::new();
+        stdin().read_line(&mut line)?;
+        let answer = line.trim();
+
+        if matches!(answer, "y" | "Y") {
+            return Ok(coro_core::output::ConfirmationDecision {
+                approved: true,
+                note: None,
+                approved_ids: None,
+            });
+        }
+
+        let approved_ids: Vec<String> = answer
+            .split(',')
+            .filter_map(|part| part.trim().parse::<usize>().ok())
+            .filter_map(|number| ids.get(number.checked_sub(1)?).cloned())
+            .collect();
+        Ok(coro_core::output::ConfirmationDecision {
+            approved: false,
+            note: None,
+            approved_ids: Some(approved_ids),
+        })
+    }
 }
 
 #[async_trait]
@@ -95,10 +169,15 @@ impl AgentOutput for CliOutputHandler {
                 let token_usage = &context.token_usage;
                 if token_usage.total_tokens > 0 {
                     debug!(
-                        "Tokens: {} input + {} output = {} total",
+                        "Tokens: {} input + {} output = {} total{}",
                         token_usage.input_tokens,
                         token_usage.output_tokens,
-                        token_usage.total_tokens
+                        token_usage.total_tokens,
+                        if token_usage.estimated {
+                            " (estimated)"
+                        } else {
+                            ""
+                        }
                     );
                 }
             }
@@ -120,9 +199,13 @@ impl AgentOutput for CliOutputHandler {
                 // Usually handled by individual tool completions
             }
 
+            AgentEvent::ToolCallDelta { .. } => {
+                // Printed lines can't be revised; the complete call is shown once it starts
+            }
+
             AgentEvent::ToolExecutionStarted { tool_info } => {
                 // Skip status display for silent tools
-                if !is_silent_tool(&tool_info.tool_name) {
+                if !self.renderers.is_silent(&tool_info.tool_name) {
                     // Show executing status (white dot)
                     let status = self.tool_formatter.format_tool_status(&tool_info);
                     if !status.is_empty() {
@@ -140,7 +223,7 @@ impl AgentOutput for CliOutputHandler {
 
             AgentEvent::ToolExecutionCompleted { tool_info } => {
                 // Skip all output for silent tools - their content is handled separately
-                if is_silent_tool(&tool_info.tool_name) {
+                if self.renderers.is_silent(&tool_info.tool_name) {
                     return Ok(());
                 }
 
@@ -166,17 +249,10 @@ impl AgentOutput for CliOutputHandler {
                     println!("{}", final_status);
                 }
 
-                // Show result content if available
-                if let Some(result_display) = self.tool_formatter.format_tool_result(&tool_info) {
+                // Show result content, rendered for the tool
+                if let Some(result_display) = self.renderers.render(&tool_info) {
                     println!("{}", result_display);
                 }
-
-                // Show diff for edit tools
-                if tool_info.tool_name == "str_replace_based_edit_tool" {
-                    if let Some(diff_display) = self.diff_formatter.format_edit_result(&tool_info) {
-                        println!("{}", diff_display);
-                    }
-                }
             }
 
             AgentEvent::AgentThinking {
@@ -192,6 +268,18 @@ impl AgentOutput for CliOutputHandler {
                 // This is mainly for interactive mode
             }
 
+            AgentEvent::UnknownToolRequested { name } => {
+                debug!("Model requested unknown tool: {}", name);
+            }
+
+            AgentEvent::UserInputRequested { question } => {
+                debug!("Agent asked the user: {}", question);
+            }
+
+            AgentEvent::ProgressUpdate { fraction } => {
+                debug!("Estimated progress: {:.0}%", fraction * 100.0);
+            }
+
             AgentEvent::StatusUpdate {
                 status: _,
                 metadata: _,
@@ -250,6 +338,16 @@ impl AgentOutput for CliOutputHandler {
                 debug!("Compression summary: {}", summary);
             }
 
+            AgentEvent::CompressionSkipped {
+                estimated_tokens,
+                threshold,
+            } => {
+                debug!(
+                    "Compression skipped: ~{} tokens is under the {} token threshold",
+                    estimated_tokens, threshold
+                );
+            }
+
             AgentEvent::CompressionFailed {
                 error,
                 fallback_action,
@@ -257,6 +355,42 @@ impl AgentOutput for CliOutputHandler {
                 warn!("⚠️  Compression failed: {}", error);
                 info!("Fallback: {}", fallback_action);
             }
+
+            AgentEvent::PromptTrimmed {
+                tokens_before,
+                tokens_after,
+                max_tokens,
+                truncated_results,
+            } => {
+                debug!(
+                    "Prompt trimmed to fit the context window: {} → {} tokens (budget {}, {} tool results truncated)",
+                    tokens_before, tokens_after, max_tokens, truncated_results
+                );
+            }
+
+            AgentEvent::ModelRefused { reason, message } => {
+                let reason = match reason {
+                    coro_core::llm::FinishReason::ContentFilter => "blocked by a content filter",
+                    _ => "refused to respond",
+                };
+                warn!("🚫 The model {}", reason);
+                if let Some(message) = message {
+                    info!("{}", message);
+                }
+            }
+
+            AgentEvent::CommitCreated { hash } => {
+                info!("📝 Committed changes as {}", &hash[..hash.len().min(12)]);
+            }
+
+            AgentEvent::ConversationReset {
+                tasks, tokens_used, ..
+            } => {
+                info!(
+                    "🔄 Conversation reset after {} tasks ({} tokens); continuing from a summary",
+                    tasks, tokens_used
+                );
+            }
         }
 
         Ok(())
@@ -279,16 +413,24 @@ impl AgentOutput for CliOutputHandler {
             println!("{}", request.message);
         }
 
-        if let Some(params) = request.metadata.get("parameters") {
+        if let Some(tool) = &request.tool {
+            if !tool.summary.is_empty() {
+                println!("  {}", tool.summary);
+            }
+        } else if let Some(params) = request.metadata.get("parameters") {
             if let Ok(mut preview) = serde_json::to_string(params) {
                 if preview.len() > 200 {
-                    preview.truncate(200);
+                    preview.truncate(truncate_to_char_boundary(&preview, 200).len());
                     preview.push_str("...");
                 }
                 println!("Parameters: {}", preview);
             }
         }
 
+        if request.kind == coro_core::output::ConfirmationKind::ToolBatch {
+            return Self::read_batch_decision(request);
+        }
+
         print!("Confirm? [y/N]: ");
         stdout().flush()?;
         let mut line = String::new();
@@ -297,11 +439,55 @@ impl AgentOutput for CliOutputHandler {
         Ok(coro_core::output::ConfirmationDecision {
             approved,
             note: None,
+            approved_ids: None,
         })
     }
 
+    async fn request_user_input(
+        &self,
+        request: &coro_core::output::UserInputRequest,
+    ) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+        use std::io::{stdin, stdout, IsTerminal, Write};
+
+        // Nobody to answer when input is piped or redirected
+        if !stdin().is_terminal() {
+            return Ok(None);
+        }
+
+        println!("{}", request.question);
+        print!("> ");
+        stdout().flush()?;
+        let mut line = String::new();
+        stdin().read_line(&mut line)?;
+        Ok(Some(line.trim().to_string()))
+    }
+
     async fn flush(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         use std::io::Write;
         std::io::stdout().flush().map_err(|e| e.into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_configured_silent_tools_hide_status() {
+        let default_handler = CliOutputHandler::new(CliOutputConfig::default());
+        assert!(default_handler.renderers.is_silent("sequentialthinking"));
+        assert!(!default_handler.renderers.is_silent("status_report"));
+
+        let handler = CliOutputHandler::new(CliOutputConfig {
+            silent_tools: vec![
+                "sequentialthinking".to_string(),
+                "status_report".to_string(),
+            ],
+            ..Default::default()
+        });
+        assert!(handler.renderers.is_silent("status_report"));
+        assert!(handler.renderers.is_silent("sequentialthinking"));
+        assert!(!handler.renderers.is_silent("bash"));
+        assert!(!handler.renderers.is_silent("str_replace_based_edit_tool"));
+    }
+}
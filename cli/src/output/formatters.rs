@@ -1,6 +1,8 @@
 //! Formatting utilities for CLI output
 
 use coro_core::output::{ToolExecutionInfo, ToolExecutionStatus};
+use coro_core::tools::utils::truncate_to_char_boundary;
+use coro_core::tools::ToolContentType;
 use std::path::Path;
 use tracing::debug;
 
@@ -12,9 +14,11 @@ const WHITE: &str = "\x1b[97m"; // White text for executing status
 const GREEN: &str = "\x1b[92m"; // Green text for success status
 const RED: &str = "\x1b[91m"; // Red text for error status
 const BLACK: &str = "\x1b[30m"; // Black text for better contrast on colored backgrounds
+const CYAN: &str = "\x1b[96m"; // Cyan text for JSON keys
 const RESET: &str = "\x1b[0m";
 
 /// Tool execution formatter
+#[derive(Default)]
 pub struct ToolFormatter;
 
 impl ToolFormatter {
@@ -144,7 +148,7 @@ impl ToolFormatter {
                 // For bash commands, show the output directly without prefix
                 if !result.content.trim().is_empty() {
                     let display_content = if result.content.len() > 200 {
-                        format!("{}...", &result.content[..197])
+                        format!("{}...", truncate_to_char_boundary(&result.content, 197))
                     } else {
                         result.content.clone()
                     };
@@ -198,17 +202,64 @@ impl ToolFormatter {
                 None
             }
             _ => {
-                if !result.content.trim().is_empty() {
-                    Some(format!("  ⎿  {}", result.content))
-                } else {
-                    None
+                if result.content.trim().is_empty() {
+                    return None;
                 }
+                let content = match result.content_type {
+                    ToolContentType::Json => self.highlight_json(&result.content),
+                    ToolContentType::Diff => self.highlight_diff(&result.content),
+                    ToolContentType::PlainText | ToolContentType::Markdown => {
+                        result.content.clone()
+                    }
+                };
+                Some(format!("  ⎿  {}", content))
             }
         }
     }
+
+    /// Color object keys in JSON content, leaving any other lines untouched
+    fn highlight_json(&self, content: &str) -> String {
+        content
+            .lines()
+            .map(|line| {
+                let trimmed = line.trim_start();
+                let indent = &line[..line.len() - trimmed.len()];
+                match trimmed.find("\": ") {
+                    Some(end) if trimmed.starts_with('"') => format!(
+                        "{}{}{}{}{}",
+                        indent,
+                        CYAN,
+                        &trimmed[..=end],
+                        RESET,
+                        &trimmed[end + 1..]
+                    ),
+                    _ => line.to_string(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Color added and removed lines of a unified diff
+    fn highlight_diff(&self, content: &str) -> String {
+        content
+            .lines()
+            .map(|line| {
+                if line.starts_with('+') && !line.starts_with("+++") {
+                    format!("{}{}{}", GREEN, line, RESET)
+                } else if line.starts_with('-') && !line.starts_with("---") {
+                    format!("{}{}{}", RED, line, RESET)
+                } else {
+                    line.to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
 }
 
 /// Diff formatter for file editing operations
+#[derive(Default)]
 pub struct DiffFormatter;
 
 impl DiffFormatter {
@@ -319,9 +370,57 @@ impl DiffFormatter {
     /// Truncate line if too long
     fn truncate_line(&self, line: &str) -> String {
         if line.len() > 100 {
-            format!("{}...", &line[..97])
+            format!("{}...", truncate_to_char_boundary(line, 97))
         } else {
             line.to_string()
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use coro_core::tools::ToolResult;
+    use std::collections::HashMap;
+
+    fn tool_info(tool_name: &str, result: ToolResult) -> ToolExecutionInfo {
+        ToolExecutionInfo {
+            execution_id: "test_call".to_string(),
+            tool_name: tool_name.to_string(),
+            parameters: HashMap::new(),
+            status: ToolExecutionStatus::Success,
+            result: Some(result),
+            timestamp: chrono::Utc::now(),
+            metadata: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_tool_result_branches_on_content_type() {
+        let formatter = ToolFormatter::new();
+        let content = "[\n  {\n    \"name\": \"coro\"\n  }\n]";
+
+        let plain = tool_info("json_edit_tool", ToolResult::success("test_call", content));
+        assert_eq!(
+            formatter.format_tool_result(&plain).unwrap(),
+            format!("  ⎿  {}", content)
+        );
+
+        let json = tool_info(
+            "json_edit_tool",
+            ToolResult::success("test_call", content).with_content_type(ToolContentType::Json),
+        );
+        let rendered = formatter.format_tool_result(&json).unwrap();
+        assert!(rendered.contains(&format!("{}\"name\":{} \"coro\"", CYAN, RESET)));
+
+        let diff = tool_info(
+            "patch_tool",
+            ToolResult::success("test_call", "--- a/x\n+++ b/x\n-old\n+new")
+                .with_content_type(ToolContentType::Diff),
+        );
+        let rendered = formatter.format_tool_result(&diff).unwrap();
+        assert!(rendered.contains(&format!("{}-old{}", RED, RESET)));
+        assert!(rendered.contains(&format!("{}+new{}", GREEN, RESET)));
+        assert!(rendered.contains("\n+++ b/x\n"));
+    }
+}
@@ -2,7 +2,8 @@
 //! Delegates all output behavior to CliOutputHandler while maintaining UI integration
 
 use super::cli_handler::{CliOutputConfig, CliOutputHandler};
-use super::formatters::{DiffFormatter, ToolFormatter};
+use super::formatters::ToolFormatter;
+use super::renderers::{SilentRenderer, ToolOutputRenderer, ToolRendererRegistry};
 use async_trait::async_trait;
 use coro_core::output::{AgentEvent, AgentOutput, MessageLevel};
 use coro_core::tools::output_formatter::{GRAY, RESET};
@@ -11,14 +12,6 @@ use std::sync::Arc;
 use tokio::sync::mpsc;
 use tokio::sync::Mutex;
 
-/// Tools that should not display status indicators
-static SILENT_TOOLS: &[&str] = &["sequentialthinking", "status_report"];
-
-/// Check if a tool should be silent (no status display)
-fn is_silent_tool(tool_name: &str) -> bool {
-    SILENT_TOOLS.contains(&tool_name)
-}
-
 /// Message types for interactive UI updates
 #[derive(Debug, Clone)]
 pub enum InteractiveMessage {
@@ -52,6 +45,8 @@ pub struct InteractiveOutputConfig {
     pub realtime_updates: bool,
     /// Whether to show detailed tool output
     pub show_tool_details: bool,
+    /// Tools whose status lines and results are not shown
+    pub silent_tools: Vec<String>,
 }
 
 impl Default for InteractiveOutputConfig {
@@ -59,6 +54,17 @@ impl Default for InteractiveOutputConfig {
         Self {
             realtime_updates: true,
             show_tool_details: true,
+            silent_tools: CliOutputConfig::default().silent_tools,
+        }
+    }
+}
+
+impl InteractiveOutputConfig {
+    /// Configuration for the underlying CLI handler
+    pub fn cli_config(&self) -> CliOutputConfig {
+        CliOutputConfig {
+            realtime_updates: self.realtime_updates,
+            silent_tools: self.silent_tools.clone(),
         }
     }
 }
@@ -72,8 +78,8 @@ pub struct InteractiveOutputHandler {
     _ui_sender: Option<mpsc::UnboundedSender<InteractiveMessage>>,
     /// Tool formatter for consistent formatting
     tool_formatter: ToolFormatter,
-    /// Diff formatter for edit results
-    diff_formatter: DiffFormatter,
+    /// Per-tool rendering of tool results
+    renderers: ToolRendererRegistry,
     /// Track active tool executions
     active_tools: Arc<Mutex<HashMap<String, coro_core::output::ToolExecutionInfo>>>,
 }
@@ -84,21 +90,33 @@ impl InteractiveOutputHandler {
         config: InteractiveOutputConfig,
         ui_sender: mpsc::UnboundedSender<InteractiveMessage>,
     ) -> Self {
-        // Create CLI output handler with the same realtime_updates setting
-        let cli_config = CliOutputConfig {
-            realtime_updates: config.realtime_updates,
-        };
+        // Create CLI output handler with the same realtime and silent tool settings
+        let cli_config = config.cli_config();
+
+        // Status reports are shown in the status line instead
+        let mut renderers = cli_config.renderers();
         let cli_handler = CliOutputHandler::new(cli_config);
+        renderers.register("status_report", Box::new(SilentRenderer));
 
         Self {
             cli_handler,
             _ui_sender: Some(ui_sender),
             tool_formatter: ToolFormatter::new(),
-            diff_formatter: DiffFormatter::new(),
+            renderers,
             active_tools: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
+    /// Render results of `tool_name` with `renderer` instead of the default
+    pub fn with_tool_renderer(
+        mut self,
+        tool_name: impl Into<String>,
+        renderer: Box<dyn ToolOutputRenderer>,
+    ) -> Self {
+        self.renderers.register(tool_name, renderer);
+        self
+    }
+
     /// Create with default configuration
     pub fn with_sender(ui_sender: mpsc::UnboundedSender<InteractiveMessage>) -> Self {
         Self::new(InteractiveOutputConfig::default(), ui_sender)
@@ -130,7 +148,7 @@ impl AgentOutput for InteractiveOutputHandler {
 
                 AgentEvent::ToolExecutionStarted { tool_info } => {
                     // Skip status display for silent tools like sequentialthinking
-                    if !is_silent_tool(&tool_info.tool_name) {
+                    if !self.renderers.is_silent(&tool_info.tool_name) {
                         // Use same format as CLI mode
                         let status_msg = self.tool_formatter.format_tool_status(&tool_info);
                         if !status_msg.is_empty() {
@@ -151,7 +169,7 @@ impl AgentOutput for InteractiveOutputHandler {
                     // Here, it’s actually a status display that starts with a dot.
 
                     // Skip all output for silent tools
-                    if is_silent_tool(&tool_info.tool_name) {
+                    if self.renderers.is_silent(&tool_info.tool_name) {
                         return Ok(());
                     }
 
@@ -168,20 +186,10 @@ impl AgentOutput for InteractiveOutputHandler {
                         });
                     }
 
-                    // Show result content if available
-                    if let Some(result_display) = self.tool_formatter.format_tool_result(&tool_info)
-                    {
+                    // Show result content, rendered for the tool
+                    if let Some(result_display) = self.renderers.render(&tool_info) {
                         let _ = ui_sender.send(InteractiveMessage::ToolResult(result_display));
                     }
-
-                    // Show diff for edit tools
-                    if tool_info.tool_name == "str_replace_based_edit_tool" {
-                        if let Some(diff_display) =
-                            self.diff_formatter.format_edit_result(&tool_info)
-                        {
-                            let _ = ui_sender.send(InteractiveMessage::ToolResult(diff_display));
-                        }
-                    }
                 }
 
                 AgentEvent::AgentThinking { thinking, .. } => {
@@ -277,6 +285,14 @@ impl AgentOutput for InteractiveOutputHandler {
         coro_core::output::AgentOutput::request_confirmation(&self.cli_handler, request).await
     }
 
+    async fn request_user_input(
+        &self,
+        request: &coro_core::output::UserInputRequest,
+    ) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+        // Delegate to CLI output handler (interactive UI can customize later)
+        coro_core::output::AgentOutput::request_user_input(&self.cli_handler, request).await
+    }
+
     async fn flush(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         // Delegate to CLI output handler
         self.cli_handler.flush().await
@@ -0,0 +1,185 @@
+//! Per-tool rendering of tool results
+//!
+//! Tools that need more than the default plain-content display register a
+//! [`ToolOutputRenderer`] under their name instead of being special-cased in
+//! the output handler.
+
+use super::formatters::{DiffFormatter, ToolFormatter};
+use coro_core::output::ToolExecutionInfo;
+use std::collections::HashMap;
+
+/// Renders the result of one tool for terminal display
+pub trait ToolOutputRenderer: Send + Sync {
+    /// Text shown once the tool has completed, or `None` to show nothing
+    fn render(&self, tool_info: &ToolExecutionInfo) -> Option<String>;
+
+    /// Whether the tool's status lines are hidden as well
+    fn is_silent(&self) -> bool {
+        false
+    }
+}
+
+/// Shows the result content according to its content type
+#[derive(Default)]
+pub struct DefaultRenderer {
+    formatter: ToolFormatter,
+}
+
+impl ToolOutputRenderer for DefaultRenderer {
+    fn render(&self, tool_info: &ToolExecutionInfo) -> Option<String> {
+        self.formatter.format_tool_result(tool_info)
+    }
+}
+
+/// Shows the result of a file edit followed by its diff
+#[derive(Default)]
+pub struct EditRenderer {
+    formatter: ToolFormatter,
+    diff_formatter: DiffFormatter,
+}
+
+impl ToolOutputRenderer for EditRenderer {
+    fn render(&self, tool_info: &ToolExecutionInfo) -> Option<String> {
+        let parts: Vec<String> = [
+            self.formatter.format_tool_result(tool_info),
+            self.diff_formatter.format_edit_result(tool_info),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join("\n"))
+        }
+    }
+}
+
+/// Shows nothing; for tools whose content is displayed elsewhere
+pub struct SilentRenderer;
+
+impl ToolOutputRenderer for SilentRenderer {
+    fn render(&self, _tool_info: &ToolExecutionInfo) -> Option<String> {
+        None
+    }
+
+    fn is_silent(&self) -> bool {
+        true
+    }
+}
+
+/// Maps tool names to their renderers, falling back to [`DefaultRenderer`]
+pub struct ToolRendererRegistry {
+    renderers: HashMap<String, Box<dyn ToolOutputRenderer>>,
+    default: DefaultRenderer,
+}
+
+impl ToolRendererRegistry {
+    /// Create a registry with the built-in renderers
+    pub fn new() -> Self {
+        let mut registry = Self::empty();
+        registry.register(
+            "str_replace_based_edit_tool",
+            Box::new(EditRenderer::default()),
+        );
+        registry
+    }
+
+    /// Create a registry where every tool uses the default renderer
+    pub fn empty() -> Self {
+        Self {
+            renderers: HashMap::new(),
+            default: DefaultRenderer::default(),
+        }
+    }
+
+    /// Use `renderer` for `tool_name`, replacing any earlier registration
+    pub fn register(
+        &mut self,
+        tool_name: impl Into<String>,
+        renderer: Box<dyn ToolOutputRenderer>,
+    ) {
+        self.renderers.insert(tool_name.into(), renderer);
+    }
+
+    /// Renderer used for `tool_name`
+    pub fn get(&self, tool_name: &str) -> &dyn ToolOutputRenderer {
+        match self.renderers.get(tool_name) {
+            Some(renderer) => renderer.as_ref(),
+            None => &self.default,
+        }
+    }
+
+    /// Render a completed tool execution with the renderer for its tool
+    pub fn render(&self, tool_info: &ToolExecutionInfo) -> Option<String> {
+        self.get(&tool_info.tool_name).render(tool_info)
+    }
+
+    /// Whether status lines for `tool_name` are hidden
+    pub fn is_silent(&self, tool_name: &str) -> bool {
+        self.get(tool_name).is_silent()
+    }
+}
+
+impl Default for ToolRendererRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use coro_core::output::ToolExecutionStatus;
+    use coro_core::tools::ToolResult;
+
+    /// Renders `passed`/`failed` counts from a test run as a table
+    struct TestTableRenderer;
+
+    impl ToolOutputRenderer for TestTableRenderer {
+        fn render(&self, tool_info: &ToolExecutionInfo) -> Option<String> {
+            let data = tool_info.result.as_ref()?.data.as_ref()?;
+            let passed = data["passed"].as_u64()?;
+            let failed = data["failed"].as_u64()?;
+            Some(format!(
+                "  passed | failed\n  {:>6} | {:>6}",
+                passed, failed
+            ))
+        }
+    }
+
+    fn tool_info(tool_name: &str, result: ToolResult) -> ToolExecutionInfo {
+        ToolExecutionInfo {
+            execution_id: "test_call".to_string(),
+            tool_name: tool_name.to_string(),
+            parameters: HashMap::new(),
+            status: ToolExecutionStatus::Success,
+            result: Some(result),
+            timestamp: chrono::Utc::now(),
+            metadata: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_registered_renderer_used_only_for_its_tool() {
+        let mut registry = ToolRendererRegistry::new();
+        registry.register("run_tests", Box::new(TestTableRenderer));
+
+        let tests = tool_info(
+            "run_tests",
+            ToolResult::success("test_call", "12 passed; 1 failed")
+                .with_data(serde_json::json!({ "passed": 12, "failed": 1 })),
+        );
+        assert_eq!(
+            registry.render(&tests).unwrap(),
+            "  passed | failed\n      12 |      1"
+        );
+
+        let other = tool_info("json_edit_tool", ToolResult::success("test_call", "done"));
+        assert_eq!(registry.render(&other).unwrap(), "  ⎿  done");
+        assert!(!registry.is_silent("run_tests"));
+        registry.register("sequentialthinking", Box::new(SilentRenderer));
+        assert!(registry.is_silent("sequentialthinking"));
+    }
+}
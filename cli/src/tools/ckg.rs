@@ -3,7 +3,7 @@
 use async_trait::async_trait;
 use coro_core::error::Result;
 use coro_core::impl_tool_factory;
-use coro_core::tools::utils::validate_absolute_path;
+use coro_core::tools::utils::{resolve_tool_path, validate_absolute_path, IgnoreMatcher};
 use coro_core::tools::{Tool, ToolCall, ToolExample, ToolResult};
 use rusqlite::{params, Connection};
 use serde_json::json;
@@ -448,11 +448,15 @@ impl Tool for CkgTool {
         match operation.as_str() {
             "build" => {
                 let path: String = call.get_parameter("path")?;
+                let path = resolve_tool_path(&call, &path)
+                    .to_string_lossy()
+                    .to_string();
                 let recursive: bool = call.get_parameter_or("recursive", true);
                 let file_extensions: Option<Vec<String>> =
                     call.get_parameter("file_extensions").ok();
 
-                self.build_knowledge_graph(&call.id, &path, recursive, file_extensions)
+                let ignore = IgnoreMatcher::for_tool_call(&call, Path::new(&path), true);
+                self.build_knowledge_graph(&call.id, &path, recursive, file_extensions, &ignore)
                     .await
             }
             "query" => {
@@ -461,6 +465,9 @@ impl Tool for CkgTool {
             }
             "analyze" => {
                 let path: String = call.get_parameter("path")?;
+                let path = resolve_tool_path(&call, &path)
+                    .to_string_lossy()
+                    .to_string();
                 self.analyze_file(&call.id, &path).await
             }
             "stats" => self.get_statistics(&call.id).await,
@@ -520,6 +527,7 @@ impl CkgTool {
         path: &str,
         recursive: bool,
         file_extensions: Option<Vec<String>>,
+        ignore: &IgnoreMatcher,
     ) -> Result<ToolResult> {
         let path = Path::new(path);
         validate_absolute_path(path)?;
@@ -558,6 +566,11 @@ impl CkgTool {
             WalkDir::new(path).max_depth(1)
         };
 
+        // Skip ignored directories entirely instead of filtering their contents
+        let walker = walker
+            .into_iter()
+            .filter_entry(|entry| !ignore.is_ignored(entry.path(), entry.file_type().is_dir()));
+
         for entry in walker {
             match entry {
                 Ok(entry) => {
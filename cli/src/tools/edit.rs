@@ -4,8 +4,9 @@ use async_trait::async_trait;
 use coro_core::error::Result;
 use coro_core::impl_tool_factory;
 use coro_core::tools::utils::{
-    check_file_exists, create_edit_snippet, expand_tabs, format_with_line_numbers, maybe_truncate,
-    run_command, validate_absolute_path, validate_directory_operation,
+    check_file_exists, create_edit_snippet, expand_tabs, format_with_line_numbers,
+    max_edit_fraction, maybe_truncate, resolve_tool_path, run_command, validate_absolute_path,
+    validate_directory_operation, IgnoreMatcher,
 };
 use coro_core::tools::{Tool, ToolCall, ToolExample, ToolResult};
 use serde_json::json;
@@ -14,6 +15,9 @@ use std::path::Path;
 /// Number of lines to show in snippets
 const SNIPPET_LINES: usize = 4;
 
+/// Files with fewer lines may be replaced whole despite `max_edit_fraction`
+const MIN_GUARDED_FILE_LINES: usize = 10;
+
 /// Available edit tool commands
 const EDIT_TOOL_COMMANDS: &[&str] = &["view", "create", "str_replace", "insert"];
 
@@ -92,7 +96,8 @@ impl Tool for EditTool {
     async fn execute(&self, call: ToolCall) -> Result<ToolResult> {
         let command: String = call.get_parameter("command")?;
         let path_str: String = call.get_parameter("path")?;
-        let path = Path::new(&path_str);
+        let path_buf = resolve_tool_path(&call, &path_str);
+        let path = path_buf.as_path();
 
         // Validate path and command
         if let Err(e) = self.validate_path(&command, path) {
@@ -102,7 +107,13 @@ impl Tool for EditTool {
         match command.as_str() {
             "view" => {
                 let view_range: Option<Vec<i32>> = call.get_parameter("view_range").ok();
-                self.view_handler(&call.id, path, view_range).await
+                let default_root = if path.is_dir() {
+                    path
+                } else {
+                    path.parent().unwrap_or(path)
+                };
+                let ignore = IgnoreMatcher::for_tool_call(&call, default_root, true);
+                self.view_handler(&call.id, path, view_range, &ignore).await
             }
             "create" => {
                 let file_text: String = call.get_parameter("file_text").map_err(|_| {
@@ -114,8 +125,14 @@ impl Tool for EditTool {
                 let old_str: String = call.get_parameter("old_str")
                     .map_err(|_| "Parameter `old_str` is required and should be a string for command: str_replace")?;
                 let new_str: Option<String> = call.get_parameter("new_str").ok();
-                self.str_replace_handler(&call.id, path, &old_str, new_str.as_deref())
-                    .await
+                self.str_replace_handler(
+                    &call.id,
+                    path,
+                    &old_str,
+                    new_str.as_deref(),
+                    max_edit_fraction(&call),
+                )
+                .await
             }
             "insert" => {
                 let insert_line: i32 = call.get_parameter("insert_line").map_err(|_| {
@@ -139,6 +156,27 @@ impl Tool for EditTool {
         }
     }
 
+    fn estimated_output_size(&self, call: &ToolCall) -> Option<usize> {
+        // Only a whole-file view returns the file's contents
+        let command: String = call.get_parameter("command").ok()?;
+        if command != "view" || call.parameters.get("view_range").is_some() {
+            return None;
+        }
+        let path: String = call.get_parameter("path").ok()?;
+        let metadata = std::fs::metadata(resolve_tool_path(call, &path)).ok()?;
+        metadata.is_file().then_some(metadata.len() as usize)
+    }
+
+    fn is_read_only(&self, call: &ToolCall) -> bool {
+        call.get_parameter::<String>("command")
+            .is_ok_and(|command| command == "view")
+    }
+
+    fn edits_file(&self, call: &ToolCall) -> bool {
+        call.get_parameter::<String>("command")
+            .is_ok_and(|command| matches!(command.as_str(), "create" | "str_replace" | "insert"))
+    }
+
     fn examples(&self) -> Vec<ToolExample> {
         vec![
             ToolExample {
@@ -199,7 +237,18 @@ impl EditTool {
         call_id: &str,
         path: &Path,
         view_range: Option<Vec<i32>>,
+        ignore: &IgnoreMatcher,
     ) -> Result<ToolResult> {
+        if ignore.is_path_ignored(path) {
+            return Ok(ToolResult::error(
+                call_id,
+                &format!(
+                    "The path {} is excluded by the project's ignore rules (.gitignore, .coroignore or configured ignore globs).",
+                    path.display()
+                ),
+            ));
+        }
+
         if path.is_dir() {
             if view_range.is_some() {
                 return Ok(ToolResult::error(
@@ -207,18 +256,29 @@ impl EditTool {
                     "The `view_range` parameter is not allowed when `path` points to a directory.",
                 ));
             }
-            return self.view_directory(call_id, path).await;
+            return self.view_directory(call_id, path, ignore).await;
         }
 
         self.view_file(call_id, path, view_range).await
     }
 
     /// View directory contents
-    async fn view_directory(&self, call_id: &str, path: &Path) -> Result<ToolResult> {
+    async fn view_directory(
+        &self,
+        call_id: &str,
+        path: &Path,
+        ignore: &IgnoreMatcher,
+    ) -> Result<ToolResult> {
         let find_cmd = format!("find {} -maxdepth 2 -not -path '*/\\.*'", path.display());
         let (return_code, stdout, stderr) = run_command(&find_cmd, Some(30), None).await?;
 
         if return_code == 0 && stderr.is_empty() {
+            // Drop entries hidden by .gitignore, .coroignore or configured globs
+            let stdout = stdout
+                .lines()
+                .filter(|line| !ignore.is_path_ignored(Path::new(line)))
+                .collect::<Vec<_>>()
+                .join("\n");
             let output = format!(
                 "Here's the files and directories up to 2 levels deep in {}, excluding hidden items:\n{}\n",
                 path.display(),
@@ -314,6 +374,7 @@ impl EditTool {
         path: &Path,
         old_str: &str,
         new_str: Option<&str>,
+        max_fraction: Option<f64>,
     ) -> Result<ToolResult> {
         let file_content = expand_tabs(&self.read_file(path)?);
         let old_str_expanded = expand_tabs(old_str);
@@ -349,6 +410,22 @@ impl EditTool {
             )));
         }
 
+        // Steer near-total rewrites toward targeted edits
+        if let Some(max_fraction) = max_fraction {
+            let fraction = old_str_expanded.len() as f64 / file_content.len() as f64;
+            if fraction > max_fraction && file_content.lines().count() >= MIN_GUARDED_FILE_LINES {
+                return Ok(ToolResult::error(
+                    call_id,
+                    &format!(
+                        "No replacement was performed. old_str covers {:.0}% of {}, more than the allowed {:.0}% for a single edit. Make targeted edits that replace only the lines that need to change.",
+                        fraction * 100.0,
+                        path.display(),
+                        max_fraction * 100.0
+                    ),
+                ));
+            }
+        }
+
         // Replace old_str with new_str
         let new_file_content = file_content.replace(&old_str_expanded, &new_str_expanded);
         self.write_file(path, &new_file_content)?;
@@ -458,3 +535,89 @@ impl_tool_factory!(
     "str_replace_based_edit_tool",
     "Edit files by viewing, creating, or replacing text content"
 );
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(parameters: serde_json::Value) -> ToolCall {
+        ToolCall {
+            id: "test_call".to_string(),
+            name: "str_replace_based_edit_tool".to_string(),
+            parameters,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_only_changing_commands_edit_the_file() {
+        let tool = EditTool::new();
+        for command in ["create", "str_replace", "insert"] {
+            assert!(tool.edits_file(&call(json!({"command": command, "path": "a.rs"}))));
+        }
+        assert!(!tool.edits_file(&call(json!({"command": "view", "path": "a.rs"}))));
+    }
+
+    #[tokio::test]
+    async fn test_near_total_rewrite_rejected_by_edit_guard() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lib.rs");
+        let original: String = (1..=20).map(|n| format!("line {}\n", n)).collect();
+        std::fs::write(&path, &original).unwrap();
+        let tool = EditTool::new();
+        let guarded = |old_str: &str, new_str: &str| {
+            let mut call = call(json!({
+                "command": "str_replace",
+                "path": path.to_str().unwrap(),
+                "old_str": old_str,
+                "new_str": new_str
+            }));
+            call.metadata = Some(std::collections::HashMap::from([(
+                coro_core::tools::utils::MAX_EDIT_FRACTION_METADATA_KEY.to_string(),
+                json!(0.5),
+            )]));
+            call
+        };
+
+        let rewrite = tool
+            .execute(guarded(&original[..original.len() - 1], "rewritten"))
+            .await
+            .unwrap();
+        assert!(!rewrite.success);
+        assert!(rewrite.content.contains("targeted edits"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), original);
+
+        let small = tool
+            .execute(guarded("line 7\n", "line seven\n"))
+            .await
+            .unwrap();
+        assert!(small.success);
+        assert!(std::fs::read_to_string(&path)
+            .unwrap()
+            .contains("line seven"));
+    }
+
+    #[test]
+    fn test_whole_file_view_estimates_file_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, "x".repeat(1000)).unwrap();
+        let tool = EditTool::new();
+        let path = path.to_str().unwrap();
+
+        assert_eq!(
+            tool.estimated_output_size(&call(json!({"command": "view", "path": path}))),
+            Some(1000)
+        );
+        assert_eq!(
+            tool.estimated_output_size(&call(
+                json!({"command": "view", "path": path, "view_range": [1, 1]})
+            )),
+            None
+        );
+        assert_eq!(
+            tool.estimated_output_size(&call(json!({"command": "create", "path": path}))),
+            None
+        );
+    }
+}
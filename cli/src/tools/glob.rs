@@ -3,11 +3,8 @@
 use async_trait::async_trait;
 use coro_core::error::Result;
 use coro_core::impl_tool_factory;
+use coro_core::tools::utils::IgnoreMatcher;
 use coro_core::tools::{Tool, ToolCall, ToolExample, ToolResult};
-use ignore::{
-    gitignore::{Gitignore, GitignoreBuilder},
-    Match,
-};
 use serde_json::json;
 use std::collections::HashSet;
 use std::path::{Path, PathBuf};
@@ -22,7 +19,7 @@ struct MatchConfig {
     pattern: String,
     base_path: PathBuf,
     include_hidden: bool,
-    respect_gitignore: bool,
+    ignore: IgnoreMatcher,
     case_sensitive: Option<bool>,
     max_depth: Option<u32>,
     files_only: bool,
@@ -49,6 +46,7 @@ impl Tool for GlobTool {
          * Supports standard glob patterns: *, ?, [abc], {foo,bar}, **\n\
          * Works on all platforms (Windows, macOS, Linux)\n\
          * Respects .gitignore files by default (can be disabled)\n\
+         * Always respects the project's .coroignore and configured ignore globs\n\
          * Returns absolute paths for found files and directories\n\
          * Limited to first 1000 matches to prevent overwhelming output\n\
          \n\
@@ -142,11 +140,13 @@ impl Tool for GlobTool {
             base_path.to_path_buf()
         };
 
+        let ignore = IgnoreMatcher::for_tool_call(&call, &base_path, respect_gitignore);
+
         let config = MatchConfig {
             pattern: pattern.clone(),
             base_path: base_path.clone(),
             include_hidden,
-            respect_gitignore,
+            ignore,
             case_sensitive,
             max_depth,
             files_only,
@@ -203,6 +203,10 @@ impl Tool for GlobTool {
         }
     }
 
+    fn is_read_only(&self, _call: &ToolCall) -> bool {
+        true
+    }
+
     fn examples(&self) -> Vec<ToolExample> {
         vec![
             ToolExample {
@@ -256,13 +260,6 @@ impl Tool for GlobTool {
 
 impl GlobTool {
     async fn find_matching_files(&self, config: MatchConfig) -> Result<Vec<PathBuf>> {
-        // Build gitignore matcher if needed
-        let gitignore = if config.respect_gitignore {
-            self.build_gitignore(&config.base_path)?
-        } else {
-            None
-        };
-
         // Determine case sensitivity (default based on platform)
         let case_sensitive = config.case_sensitive.unwrap_or({
             // Default: case-insensitive on Windows, case-sensitive elsewhere
@@ -320,13 +317,9 @@ impl GlobTool {
                 }
             }
 
-            // Check gitignore
-            if let Some(ref gitignore) = gitignore {
-                let relative_path = path.strip_prefix(&config.base_path).unwrap_or(path);
-                match gitignore.matched(relative_path, is_dir) {
-                    Match::Ignore(_) => continue,
-                    Match::None | Match::Whitelist(_) => {}
-                }
+            // Check .gitignore, .coroignore and configured globs
+            if config.ignore.is_ignored(path, is_dir) {
+                continue;
             }
 
             // Check if path matches the glob pattern
@@ -339,38 +332,6 @@ impl GlobTool {
         matches.sort();
         Ok(matches)
     }
-
-    fn build_gitignore(&self, base_path: &Path) -> Result<Option<Gitignore>> {
-        let mut builder = GitignoreBuilder::new(base_path);
-
-        // Try to add .gitignore files walking up the directory tree
-        let mut current_path = base_path;
-        loop {
-            let gitignore_path = current_path.join(".gitignore");
-            if gitignore_path.exists() {
-                if let Some(e) = builder.add(&gitignore_path) {
-                    tracing::warn!(
-                        "Failed to parse .gitignore at {}: {}",
-                        gitignore_path.display(),
-                        e
-                    );
-                }
-            }
-
-            match current_path.parent() {
-                Some(parent) => current_path = parent,
-                None => break,
-            }
-        }
-
-        match builder.build() {
-            Ok(gitignore) => Ok(Some(gitignore)),
-            Err(e) => {
-                tracing::warn!("Failed to build gitignore matcher: {}", e);
-                Ok(None)
-            }
-        }
-    }
 }
 
 impl Default for GlobTool {
@@ -3,8 +3,8 @@
 use async_trait::async_trait;
 use coro_core::error::Result;
 use coro_core::impl_tool_factory;
-use coro_core::tools::utils::validate_absolute_path;
-use coro_core::tools::{Tool, ToolCall, ToolExample, ToolResult};
+use coro_core::tools::utils::{resolve_tool_path, validate_absolute_path};
+use coro_core::tools::{Tool, ToolCall, ToolContentType, ToolExample, ToolResult};
 use jsonpath_rust::JsonPathQuery;
 use serde_json::{json, Value};
 use std::path::Path;
@@ -85,7 +85,8 @@ impl Tool for JsonEditTool {
         let value: Option<Value> = call.get_parameter("value").ok();
         let pretty_print: bool = call.get_parameter_or("pretty_print", true);
 
-        let file_path = Path::new(&file_path_str);
+        let file_path_buf = resolve_tool_path(&call, &file_path_str);
+        let file_path = file_path_buf.as_path();
         validate_absolute_path(file_path)?;
 
         match operation.as_str() {
@@ -228,10 +229,8 @@ impl JsonEditTool {
                         serde_json::to_string(&results)?
                     };
 
-                    Ok(ToolResult::success(
-                        call_id,
-                        &format!("JSONPath '{}' matches:\n{}", path, output),
-                    ))
+                    Ok(ToolResult::success(call_id, &output)
+                        .with_content_type(ToolContentType::Json))
                 }
                 Err(e) => Ok(ToolResult::error(
                     call_id,
@@ -245,10 +244,7 @@ impl JsonEditTool {
                 serde_json::to_string(&data)?
             };
 
-            Ok(ToolResult::success(
-                call_id,
-                &format!("JSON content of {}:\n{}", file_path.display(), output),
-            ))
+            Ok(ToolResult::success(call_id, &output).with_content_type(ToolContentType::Json))
         }
     }
 
@@ -428,3 +424,34 @@ impl_tool_factory!(
     "json_edit_tool",
     "Tool for editing JSON files with JSONPath expressions"
 );
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_view_reports_json_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        std::fs::write(&path, r#"{"name": "coro", "debug": true}"#).unwrap();
+
+        let result = JsonEditTool::new()
+            .execute(ToolCall {
+                id: "test_call".to_string(),
+                name: "json_edit_tool".to_string(),
+                parameters: json!({
+                    "operation": "view",
+                    "file_path": path.to_str().unwrap(),
+                    "json_path": "$.name"
+                }),
+                metadata: None,
+            })
+            .await
+            .unwrap();
+
+        assert!(result.success);
+        assert_eq!(result.content_type, ToolContentType::Json);
+        let matches: Value = serde_json::from_str(&result.content).unwrap();
+        assert!(matches.to_string().contains("\"coro\""));
+    }
+}
@@ -1,26 +1,38 @@
 //! Abort (cancellation) controller for AgentCore
 
-#[derive(Clone)]
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone)]
 pub struct AbortController {
     tx: tokio::sync::watch::Sender<bool>,
+    reason: Arc<Mutex<Option<String>>>,
 }
 
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub struct AbortRegistration {
     rx: tokio::sync::watch::Receiver<bool>,
+    reason: Arc<Mutex<Option<String>>>,
 }
 
 impl AbortController {
     /// Create a new controller and its registration
     pub fn new() -> (Self, AbortRegistration) {
         let (tx, rx) = tokio::sync::watch::channel(false);
-        (Self { tx: tx.clone() }, AbortRegistration { rx })
+        let reason = Arc::new(Mutex::new(None));
+        (
+            Self {
+                tx: tx.clone(),
+                reason: reason.clone(),
+            },
+            AbortRegistration { rx, reason },
+        )
     }
 
     /// Subscribe to this controller to obtain a registration
     pub fn subscribe(&self) -> AbortRegistration {
         AbortRegistration {
             rx: self.tx.subscribe(),
+            reason: self.reason.clone(),
         }
     }
 
@@ -31,6 +43,24 @@ impl AbortController {
         // This is fine as this is the lowest level and should work silently
         let _ = result; // Suppress unused variable warning
     }
+
+    /// Trigger cancellation, recording why (e.g. "user pressed Esc", "timeout")
+    ///
+    /// Only the first reason is kept.
+    pub fn cancel_with_reason(&self, reason: impl Into<String>) {
+        self.reason.lock().unwrap().get_or_insert(reason.into());
+        self.cancel();
+    }
+
+    /// Whether cancellation has been triggered
+    pub fn is_cancelled(&self) -> bool {
+        *self.tx.borrow()
+    }
+
+    /// Whether any registration is still listening to this controller
+    pub fn has_subscribers(&self) -> bool {
+        self.tx.receiver_count() > 0
+    }
 }
 
 impl AbortRegistration {
@@ -39,6 +69,11 @@ impl AbortRegistration {
         *self.rx.borrow()
     }
 
+    /// Why cancellation was triggered, if a reason was given
+    pub fn reason(&self) -> Option<String> {
+        self.reason.lock().unwrap().clone()
+    }
+
     /// Wait until cancellation is triggered (returns immediately if already cancelled)
     pub async fn cancelled(&mut self) {
         if !*self.rx.borrow() {
@@ -134,6 +169,19 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_abort_controller_keeps_first_reason() {
+        let (controller, registration) = AbortController::new();
+        assert_eq!(registration.reason(), None);
+
+        controller.cancel_with_reason("timeout");
+        controller.cancel_with_reason("user pressed Esc");
+
+        assert!(registration.is_cancelled());
+        assert_eq!(registration.reason().as_deref(), Some("timeout"));
+        assert_eq!(controller.subscribe().reason().as_deref(), Some("timeout"));
+    }
+
     #[tokio::test]
     async fn test_abort_controller_clone() {
         let (controller, mut registration) = AbortController::new();
@@ -0,0 +1,81 @@
+//! Committing the files an agent changed once its task succeeds
+
+use crate::agent::config::CommitConfig;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// Stage `files` and commit them in the git repository containing `project_path`
+///
+/// Returns the new commit's hash, or `None` when `project_path` is not in a
+/// git repository or none of `files` differ from `HEAD`. Anything already
+/// staged for other paths is left out of the commit. The repository's commit
+/// hooks run unless `config.skip_hooks` is set, and a hook that rejects the
+/// commit is reported as an error.
+pub async fn commit_files(
+    project_path: &Path,
+    files: &[PathBuf],
+    config: &CommitConfig,
+    task: &str,
+) -> Result<Option<String>, String> {
+    // Not being in a repository, or git being missing, is not an error
+    if git(project_path, &["rev-parse", "--is-inside-work-tree"])
+        .await
+        .is_err()
+    {
+        return Ok(None);
+    }
+
+    let paths: Vec<String> = files
+        .iter()
+        .filter(|file| file.exists())
+        .map(|file| file.display().to_string())
+        .collect();
+    if paths.is_empty() {
+        return Ok(None);
+    }
+
+    let mut add = vec!["add", "--"];
+    add.extend(paths.iter().map(String::as_str));
+    git(project_path, &add).await?;
+
+    let mut diff = vec!["diff", "--cached", "--quiet", "--"];
+    diff.extend(paths.iter().map(String::as_str));
+    if git(project_path, &diff).await.is_ok() {
+        return Ok(None);
+    }
+
+    let message = config.message_for(task);
+    let author = config
+        .author
+        .as_ref()
+        .map(|author| format!("--author={}", author));
+    let mut commit = vec!["commit", "-m", message.as_str()];
+    if config.skip_hooks {
+        commit.push("--no-verify");
+    }
+    commit.extend(author.as_deref());
+    commit.push("--");
+    commit.extend(paths.iter().map(String::as_str));
+    git(project_path, &commit).await?;
+
+    git(project_path, &["rev-parse", "HEAD"]).await.map(Some)
+}
+
+/// Run git in `dir`, returning its trimmed stdout or a description of the failure
+async fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| format!("failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "git {} failed: {}",
+            args.first().copied().unwrap_or_default(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
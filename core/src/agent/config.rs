@@ -1,6 +1,9 @@
 //! Agent configuration structures
 
+use crate::agent::execution::FailureKind;
+use crate::agent::tokens::TokenCalculator;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 /// Output mode for the agent
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -9,6 +12,8 @@ pub enum OutputMode {
     Debug,
     /// Normal mode with clean, user-friendly output
     Normal,
+    /// Like normal mode, but thinking events are never emitted
+    Quiet,
 }
 
 impl Default for OutputMode {
@@ -17,6 +22,23 @@ impl Default for OutputMode {
     }
 }
 
+/// Which `sequentialthinking` thoughts are emitted as `AgentThinking` events
+///
+/// Every thought is still recorded in the trajectory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThinkingEmission {
+    /// Emit every thought
+    #[default]
+    All,
+    /// Emit only the last thought of each step
+    Latest,
+    /// Emit one thought in every N, starting with the first
+    EveryNth(usize),
+    /// Never emit thoughts
+    None,
+}
+
 /// Configuration for an agent
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentConfig {
@@ -37,6 +59,379 @@ pub struct AgentConfig {
     /// If not provided, the default system prompt will be used
     #[serde(default)]
     pub system_prompt: Option<String>,
+
+    /// System prompts for particular models, keyed by model name; these take
+    /// precedence over `system_prompt` when the active model has an entry
+    #[serde(default)]
+    pub system_prompt_by_model: HashMap<String, String>,
+
+    /// Fail instead of injecting synthetic tool results when the history ends
+    /// with tool calls that have no matching results
+    #[serde(default)]
+    pub strict_history: bool,
+
+    /// Extra gitignore-style globs hidden from context-gathering tools,
+    /// applied on top of `.gitignore` and `.coroignore`
+    #[serde(default)]
+    pub ignore_globs: Vec<String>,
+
+    /// Keep full tool output in the trajectory but send a truncated copy to
+    /// the model (only applies when a trajectory recorder is attached)
+    #[serde(default)]
+    pub full_trajectory_tool_output: bool,
+
+    /// Which thoughts are emitted as `AgentThinking` events
+    #[serde(default)]
+    pub thinking_emission: ThinkingEmission,
+
+    /// Emit reasoning text as `AgentThinking` events and only the remaining
+    /// text as normal messages
+    ///
+    /// Reasoning is text inside `<thinking>`/`<think>` tags, or any text that
+    /// accompanies tool calls.
+    #[serde(default)]
+    pub separate_reasoning: bool,
+
+    /// How many levels of sub-agents the `sub_agent` tool may spawn
+    /// (0 disables delegation)
+    #[serde(default = "AgentConfig::default_max_delegation_depth")]
+    pub max_delegation_depth: usize,
+
+    /// Whether tools that need network access may be used; when false they
+    /// are refused and not advertised to the model
+    #[serde(default = "AgentConfig::default_allow_network")]
+    pub allow_network: bool,
+
+    /// Estimate token usage locally when the provider omits it, so token
+    /// accounting keeps advancing
+    #[serde(default = "AgentConfig::default_estimate_missing_usage")]
+    pub estimate_missing_usage: bool,
+
+    /// Model context window in tokens; when set, prompts that would not leave
+    /// room for `reserved_output_tokens` are compressed or trimmed before sending
+    #[serde(default)]
+    pub context_window: Option<u32>,
+
+    /// Tokens of the context window kept free for the model's reply
+    #[serde(default = "AgentConfig::default_reserved_output_tokens")]
+    pub reserved_output_tokens: u32,
+
+    /// Ask for confirmation of all confirmation-requiring tool calls in a step
+    /// with a single request instead of one prompt per call
+    #[serde(default)]
+    pub batch_confirmations: bool,
+
+    /// Name the enabled tools in an "Available tools" line of the system
+    /// prompt; models with native tool calling already receive the schemas
+    #[serde(default = "AgentConfig::default_list_tools_in_prompt")]
+    pub list_tools_in_prompt: bool,
+
+    /// Emit text the model sends alongside tool calls as a normal message
+    /// before the tools run; ignored when `separate_reasoning` treats that
+    /// text as reasoning
+    #[serde(default)]
+    pub emit_tool_call_text: bool,
+
+    /// Compress older history before running a tool that expects a large
+    /// output (see `Tool::estimated_output_size`)
+    #[serde(default = "AgentConfig::default_speculative_compression")]
+    pub speculative_compression: bool,
+
+    /// Largest single message, in estimated tokens; longer task messages are
+    /// cut in the middle with a marker. Defaults to half the prompt budget
+    /// when `context_window` is set
+    #[serde(default)]
+    pub max_message_tokens: Option<u32>,
+
+    /// Most tokens the model may generate per response, also used as the
+    /// conversation manager's compression budget. Falls back to the LLM
+    /// config's `max_tokens`, then to the model's known output limit
+    #[serde(default)]
+    pub max_output_tokens: Option<u32>,
+
+    /// Trajectory entries that may wait to be written by a background task
+    /// before the agent waits for it; 0 writes each entry before continuing
+    #[serde(default = "AgentConfig::default_trajectory_write_queue")]
+    pub trajectory_write_queue: usize,
+
+    /// Most bytes of a pinned file kept in context; the rest is cut off
+    #[serde(default = "AgentConfig::default_pinned_file_max_bytes")]
+    pub pinned_file_max_bytes: usize,
+
+    /// Longest a task may run before it is cancelled; `None` means no limit
+    #[serde(default)]
+    pub max_duration: Option<std::time::Duration>,
+
+    /// Send tasks after the first in a conversation as the user typed them,
+    /// without the `[Task]:` framing
+    #[serde(default)]
+    pub raw_continuation_tasks: bool,
+
+    /// Convert path separators in file tool inputs to the host's and resolve
+    /// relative paths against the project root
+    #[serde(default = "AgentConfig::default_normalize_tool_paths")]
+    pub normalize_tool_paths: bool,
+
+    /// After `reflection_threshold` tool calls fail in a row, ask the model to
+    /// analyze the failures and change approach before continuing
+    #[serde(default)]
+    pub reflect_on_failure: bool,
+
+    /// Consecutive tool failures that trigger a reflection prompt
+    #[serde(default = "AgentConfig::default_reflection_threshold")]
+    pub reflection_threshold: usize,
+
+    /// Sent to the model in place of empty tool output, which some providers
+    /// reject; `None` sends empty output as is
+    #[serde(default = "AgentConfig::default_empty_tool_result_placeholder")]
+    pub empty_tool_result_placeholder: Option<String>,
+
+    /// Run a tool call repeated in the same assistant message (same name and
+    /// input) only once, answering the repeats with the first result
+    #[serde(default)]
+    pub dedupe_tool_calls: bool,
+
+    /// Largest fraction of a file (0.0 to 1.0) a single file edit may replace;
+    /// larger edits are rejected with a request for a targeted edit
+    #[serde(default)]
+    pub max_edit_fraction: Option<f64>,
+
+    /// Commit the files changed by edit tools when a task succeeds
+    #[serde(default)]
+    pub auto_commit: Option<CommitConfig>,
+
+    /// Wrap the output of network tools, and of the tools named in
+    /// `untrusted_output_tools`, in framing that marks it as data rather than
+    /// instructions
+    #[serde(default)]
+    pub frame_untrusted_tool_output: bool,
+
+    /// Tools besides network tools whose output is framed as untrusted, such
+    /// as file readers
+    #[serde(default)]
+    pub untrusted_output_tools: Vec<String>,
+
+    /// Tasks a conversation may hold before it is summarized and replaced by
+    /// a fresh conversation seeded with the summary
+    #[serde(default)]
+    pub max_tasks_per_conversation: Option<usize>,
+
+    /// Tokens a conversation may consume before it is summarized and reset
+    #[serde(default)]
+    pub max_conversation_tokens: Option<u32>,
+
+    /// Remove ANSI escape sequences (colors, cursor movement) from tool output
+    /// before the model sees it; the trajectory keeps the original output
+    #[serde(default = "AgentConfig::default_strip_ansi_tool_output")]
+    pub strip_ansi_tool_output: bool,
+
+    /// Largest serialized size in bytes of the messages and tool definitions
+    /// sent in one request; larger requests are compressed and trimmed first
+    #[serde(default)]
+    pub max_request_bytes: Option<usize>,
+
+    /// Directory levels of the project tree listed in the default system
+    /// prompt (0 leaves the listing out, 1 lists only the top level); deeper
+    /// trees may list up to 200 entries instead of 50
+    #[serde(default = "AgentConfig::default_project_tree_depth")]
+    pub project_tree_depth: usize,
+
+    /// Lowest confidence a `task_done` call may report; below it the task is
+    /// not completed and the model is asked to verify its work and go on
+    ///
+    /// Calls that report no confidence always complete the task.
+    #[serde(default)]
+    pub min_confidence: Option<f32>,
+
+    /// Answer a read-only tool call that repeats an earlier one and returns
+    /// the same output with a short note instead of the full output again
+    #[serde(default)]
+    pub note_unchanged_tool_results: bool,
+
+    /// When set, an interrupted task asks the model to summarize its progress
+    /// so far, giving up after this long; the request itself is not cancelled
+    #[serde(default)]
+    pub interrupt_summary_timeout: Option<std::time::Duration>,
+
+    /// Run a failed task again from the conversation it started with
+    #[serde(default)]
+    pub task_retry: Option<TaskRetryPolicy>,
+}
+
+/// How changes are committed after a successful task
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitConfig {
+    /// Commit message; `{task}` is replaced with the first line of the task
+    #[serde(default = "CommitConfig::default_message_template")]
+    pub message_template: String,
+
+    /// Author in `Name <email>` form; git's configured identity when `None`
+    #[serde(default)]
+    pub author: Option<String>,
+
+    /// Pass `--no-verify`, skipping the repository's pre-commit and
+    /// commit-msg hooks
+    #[serde(default)]
+    pub skip_hooks: bool,
+}
+
+impl CommitConfig {
+    fn default_message_template() -> String {
+        "coro: {task}".to_string()
+    }
+
+    /// Commit message for `task`
+    pub fn message_for(&self, task: &str) -> String {
+        let summary = task.lines().next().unwrap_or_default().trim();
+        self.message_template.replace("{task}", summary)
+    }
+}
+
+impl Default for CommitConfig {
+    fn default() -> Self {
+        Self {
+            message_template: Self::default_message_template(),
+            author: None,
+            skip_hooks: false,
+        }
+    }
+}
+
+/// When and how often a failed task is run again
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskRetryPolicy {
+    /// Runs after the first one; 0 disables retrying
+    #[serde(default = "TaskRetryPolicy::default_max_retries")]
+    pub max_retries: u32,
+
+    /// Failure kinds that are retried
+    #[serde(default = "TaskRetryPolicy::default_retry_on")]
+    pub retry_on: Vec<FailureKind>,
+
+    /// Delay before the first retry in milliseconds, doubled for each later one
+    #[serde(default = "TaskRetryPolicy::default_base_delay_ms")]
+    pub base_delay_ms: u64,
+
+    /// Largest delay between runs in milliseconds
+    #[serde(default = "TaskRetryPolicy::default_max_delay_ms")]
+    pub max_delay_ms: u64,
+}
+
+impl TaskRetryPolicy {
+    fn default_max_retries() -> u32 {
+        2
+    }
+
+    fn default_retry_on() -> Vec<FailureKind> {
+        vec![FailureKind::Transient, FailureKind::Deadline]
+    }
+
+    fn default_base_delay_ms() -> u64 {
+        1_000
+    }
+
+    fn default_max_delay_ms() -> u64 {
+        30_000
+    }
+
+    /// Whether a run that failed with `kind` is retried
+    pub fn retries(&self, kind: Option<FailureKind>) -> bool {
+        kind.is_some_and(|kind| self.retry_on.contains(&kind))
+    }
+
+    /// Delay before retry number `retry`, counting from 1
+    pub fn delay_for(&self, retry: u32) -> std::time::Duration {
+        let factor = 1u64 << retry.saturating_sub(1).min(20);
+        std::time::Duration::from_millis(
+            self.base_delay_ms
+                .saturating_mul(factor)
+                .min(self.max_delay_ms),
+        )
+    }
+}
+
+impl Default for TaskRetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: Self::default_max_retries(),
+            retry_on: Self::default_retry_on(),
+            base_delay_ms: Self::default_base_delay_ms(),
+            max_delay_ms: Self::default_max_delay_ms(),
+        }
+    }
+}
+
+impl AgentConfig {
+    fn default_max_delegation_depth() -> usize {
+        1
+    }
+
+    fn default_allow_network() -> bool {
+        true
+    }
+
+    fn default_estimate_missing_usage() -> bool {
+        true
+    }
+
+    fn default_reserved_output_tokens() -> u32 {
+        8192
+    }
+
+    fn default_list_tools_in_prompt() -> bool {
+        true
+    }
+
+    fn default_speculative_compression() -> bool {
+        true
+    }
+
+    fn default_normalize_tool_paths() -> bool {
+        true
+    }
+
+    fn default_strip_ansi_tool_output() -> bool {
+        true
+    }
+
+    fn default_project_tree_depth() -> usize {
+        crate::agent::prompt::DEFAULT_PROJECT_TREE_DEPTH
+    }
+
+    fn default_reflection_threshold() -> usize {
+        3
+    }
+
+    fn default_empty_tool_result_placeholder() -> Option<String> {
+        Some("(no output)".to_string())
+    }
+
+    fn default_trajectory_write_queue() -> usize {
+        64
+    }
+
+    fn default_pinned_file_max_bytes() -> usize {
+        32 * 1024
+    }
+
+    /// Largest estimated prompt that may be sent, if a context window is configured
+    pub fn max_prompt_tokens(&self) -> Option<u32> {
+        self.context_window
+            .map(|window| window.saturating_sub(self.reserved_output_tokens))
+    }
+
+    /// Output token limit for `model`, given the LLM config's `max_tokens`
+    pub fn output_token_limit(&self, configured: Option<u32>, model: &str) -> u32 {
+        self.max_output_tokens
+            .or(configured)
+            .unwrap_or_else(|| TokenCalculator::max_output_tokens_for_model(model))
+    }
+
+    /// Largest estimated size of a single message, if any limit applies
+    pub fn message_token_limit(&self) -> Option<u32> {
+        self.max_message_tokens
+            .or_else(|| self.max_prompt_tokens().map(|budget| budget / 2))
+    }
 }
 
 impl Default for AgentConfig {
@@ -52,6 +447,45 @@ impl Default for AgentConfig {
             ],
             output_mode: OutputMode::default(),
             system_prompt: None,
+            system_prompt_by_model: HashMap::new(),
+            strict_history: false,
+            ignore_globs: Vec::new(),
+            full_trajectory_tool_output: false,
+            thinking_emission: ThinkingEmission::default(),
+            separate_reasoning: false,
+            max_delegation_depth: Self::default_max_delegation_depth(),
+            allow_network: Self::default_allow_network(),
+            estimate_missing_usage: Self::default_estimate_missing_usage(),
+            context_window: None,
+            reserved_output_tokens: Self::default_reserved_output_tokens(),
+            batch_confirmations: false,
+            list_tools_in_prompt: Self::default_list_tools_in_prompt(),
+            emit_tool_call_text: false,
+            speculative_compression: Self::default_speculative_compression(),
+            max_message_tokens: None,
+            max_output_tokens: None,
+            trajectory_write_queue: Self::default_trajectory_write_queue(),
+            pinned_file_max_bytes: Self::default_pinned_file_max_bytes(),
+            max_duration: None,
+            raw_continuation_tasks: false,
+            normalize_tool_paths: Self::default_normalize_tool_paths(),
+            reflect_on_failure: false,
+            reflection_threshold: Self::default_reflection_threshold(),
+            empty_tool_result_placeholder: Self::default_empty_tool_result_placeholder(),
+            dedupe_tool_calls: false,
+            max_edit_fraction: None,
+            auto_commit: None,
+            frame_untrusted_tool_output: false,
+            untrusted_output_tools: Vec::new(),
+            max_tasks_per_conversation: None,
+            max_conversation_tokens: None,
+            strip_ansi_tool_output: Self::default_strip_ansi_tool_output(),
+            max_request_bytes: None,
+            project_tree_depth: Self::default_project_tree_depth(),
+            min_confidence: None,
+            note_unchanged_tool_results: false,
+            interrupt_summary_timeout: None,
+            task_retry: None,
         }
     }
 }
@@ -61,6 +495,14 @@ pub struct AgentBuilder {
     llm_config: crate::config::ResolvedLlmConfig,
     agent_config: AgentConfig,
     abort_controller: Option<super::AbortController>,
+    metrics: Option<std::sync::Arc<dyn crate::metrics::Metrics>>,
+    text_transformer: Option<std::sync::Arc<dyn crate::output::AssistantTextTransformer>>,
+    rate_limiter: Option<std::sync::Arc<crate::llm::RateLimiter>>,
+    stop_condition: Option<std::sync::Arc<dyn super::StopCondition>>,
+    tool_gate: Option<std::sync::Arc<dyn super::ToolGate>>,
+    tool_replay: Option<std::sync::Arc<crate::trajectory::ReplayToolExecutor>>,
+    outputs: Vec<Box<dyn crate::output::AgentOutput>>,
+    logs: Vec<(std::path::PathBuf, crate::output::LogFormat)>,
 }
 
 impl AgentBuilder {
@@ -70,6 +512,14 @@ impl AgentBuilder {
             llm_config,
             agent_config: AgentConfig::default(),
             abort_controller: None,
+            metrics: None,
+            text_transformer: None,
+            rate_limiter: None,
+            stop_condition: None,
+            tool_gate: None,
+            tool_replay: None,
+            outputs: Vec::new(),
+            logs: Vec::new(),
         }
     }
 
@@ -103,45 +553,408 @@ impl AgentBuilder {
         self
     }
 
+    /// Use `system_prompt` whenever the active model is `model`
+    pub fn with_model_system_prompt(
+        mut self,
+        model: impl Into<String>,
+        system_prompt: impl Into<String>,
+    ) -> Self {
+        self.agent_config
+            .system_prompt_by_model
+            .insert(model.into(), system_prompt.into());
+        self
+    }
+
+    /// Enable or disable strict history validation
+    pub fn with_strict_history(mut self, strict_history: bool) -> Self {
+        self.agent_config.strict_history = strict_history;
+        self
+    }
+
+    /// Set extra globs hidden from context-gathering tools
+    pub fn with_ignore_globs(mut self, ignore_globs: Vec<String>) -> Self {
+        self.agent_config.ignore_globs = ignore_globs;
+        self
+    }
+
+    /// Record full tool output in the trajectory while truncating it for the model
+    pub fn with_full_trajectory_tool_output(mut self, enabled: bool) -> Self {
+        self.agent_config.full_trajectory_tool_output = enabled;
+        self
+    }
+
+    /// Limit which thoughts are emitted as `AgentThinking` events
+    pub fn with_thinking_emission(mut self, thinking_emission: ThinkingEmission) -> Self {
+        self.agent_config.thinking_emission = thinking_emission;
+        self
+    }
+
+    /// Separate reasoning text from actionable output
+    pub fn with_separate_reasoning(mut self, enabled: bool) -> Self {
+        self.agent_config.separate_reasoning = enabled;
+        self
+    }
+
+    /// Bound how deeply sub-agents may be nested
+    pub fn with_max_delegation_depth(mut self, depth: usize) -> Self {
+        self.agent_config.max_delegation_depth = depth;
+        self
+    }
+
+    /// Allow or refuse tools that need network access
+    pub fn with_allow_network(mut self, allow_network: bool) -> Self {
+        self.agent_config.allow_network = allow_network;
+        self
+    }
+
+    /// Estimate token usage when the provider omits it
+    pub fn with_estimate_missing_usage(mut self, enabled: bool) -> Self {
+        self.agent_config.estimate_missing_usage = enabled;
+        self
+    }
+
+    /// Cap prompts at `context_window - reserved_output_tokens` estimated tokens
+    pub fn with_context_window(mut self, context_window: u32, reserved_output_tokens: u32) -> Self {
+        self.agent_config.context_window = Some(context_window);
+        self.agent_config.reserved_output_tokens = reserved_output_tokens;
+        self
+    }
+
+    /// Confirm a step's tool calls with one batched request
+    pub fn with_batch_confirmations(mut self, enabled: bool) -> Self {
+        self.agent_config.batch_confirmations = enabled;
+        self
+    }
+
+    /// Control whether the system prompt lists the enabled tools by name
+    pub fn with_list_tools_in_prompt(mut self, enabled: bool) -> Self {
+        self.agent_config.list_tools_in_prompt = enabled;
+        self
+    }
+
+    /// Show the model's narration that accompanies tool calls
+    pub fn with_emit_tool_call_text(mut self, enabled: bool) -> Self {
+        self.agent_config.emit_tool_call_text = enabled;
+        self
+    }
+
+    /// Control compression ahead of tool results expected to be large
+    pub fn with_speculative_compression(mut self, enabled: bool) -> Self {
+        self.agent_config.speculative_compression = enabled;
+        self
+    }
+
+    /// Truncate task messages larger than `max_tokens` estimated tokens
+    pub fn with_max_message_tokens(mut self, max_tokens: u32) -> Self {
+        self.agent_config.max_message_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Bound the background trajectory write queue (0 writes synchronously)
+    pub fn with_trajectory_write_queue(mut self, capacity: usize) -> Self {
+        self.agent_config.trajectory_write_queue = capacity;
+        self
+    }
+
+    /// Cap how much of each pinned file is kept in context
+    pub fn with_pinned_file_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.agent_config.pinned_file_max_bytes = max_bytes;
+        self
+    }
+
+    /// Cancel tasks that run longer than `max_duration`
+    pub fn with_max_duration(mut self, max_duration: std::time::Duration) -> Self {
+        self.agent_config.max_duration = Some(max_duration);
+        self
+    }
+
+    /// Only wrap the first task of a conversation in task framing
+    pub fn with_raw_continuation_tasks(mut self, enabled: bool) -> Self {
+        self.agent_config.raw_continuation_tasks = enabled;
+        self
+    }
+
+    /// Normalize separators and resolve relative paths in file tool inputs
+    pub fn with_normalize_tool_paths(mut self, enabled: bool) -> Self {
+        self.agent_config.normalize_tool_paths = enabled;
+        self
+    }
+
+    /// Prompt the model to reflect after repeated tool failures
+    pub fn with_reflect_on_failure(mut self, enabled: bool) -> Self {
+        self.agent_config.reflect_on_failure = enabled;
+        self
+    }
+
+    /// Set how many consecutive tool failures trigger a reflection prompt
+    pub fn with_reflection_threshold(mut self, threshold: usize) -> Self {
+        self.agent_config.reflection_threshold = threshold;
+        self
+    }
+
+    /// Set the text sent in place of empty tool output, or `None` to send it as is
+    pub fn with_empty_tool_result_placeholder(mut self, placeholder: Option<String>) -> Self {
+        self.agent_config.empty_tool_result_placeholder = placeholder;
+        self
+    }
+
+    /// Run identical tool calls within one assistant message only once
+    pub fn with_dedupe_tool_calls(mut self, enabled: bool) -> Self {
+        self.agent_config.dedupe_tool_calls = enabled;
+        self
+    }
+
+    /// Reject file edits that replace more than `fraction` of a file
+    pub fn with_max_edit_fraction(mut self, fraction: f64) -> Self {
+        self.agent_config.max_edit_fraction = Some(fraction);
+        self
+    }
+
+    /// Commit the files edited during a task once it succeeds
+    pub fn with_auto_commit(mut self, commit: CommitConfig) -> Self {
+        self.agent_config.auto_commit = Some(commit);
+        self
+    }
+
+    /// Mark the output of network tools as untrusted data in the model context
+    pub fn with_untrusted_output_framing(mut self, enabled: bool) -> Self {
+        self.agent_config.frame_untrusted_tool_output = enabled;
+        self
+    }
+
+    /// Treat the output of `tool` as untrusted when framing is enabled
+    pub fn with_untrusted_output_tool(mut self, tool: impl Into<String>) -> Self {
+        self.agent_config.untrusted_output_tools.push(tool.into());
+        self
+    }
+
+    /// Start a fresh, summary-seeded conversation after `tasks` tasks
+    pub fn with_max_tasks_per_conversation(mut self, tasks: usize) -> Self {
+        self.agent_config.max_tasks_per_conversation = Some(tasks);
+        self
+    }
+
+    /// Start a fresh, summary-seeded conversation once `tokens` have been used
+    pub fn with_max_conversation_tokens(mut self, tokens: u32) -> Self {
+        self.agent_config.max_conversation_tokens = Some(tokens);
+        self
+    }
+
+    /// Keep or remove ANSI escape sequences in tool output sent to the model
+    pub fn with_strip_ansi_tool_output(mut self, enabled: bool) -> Self {
+        self.agent_config.strip_ansi_tool_output = enabled;
+        self
+    }
+
+    /// Shrink requests whose serialized size exceeds `bytes` before sending
+    pub fn with_max_request_bytes(mut self, bytes: usize) -> Self {
+        self.agent_config.max_request_bytes = Some(bytes);
+        self
+    }
+
+    /// List the project tree `depth` levels deep in the system prompt
+    pub fn with_project_tree_depth(mut self, depth: usize) -> Self {
+        self.agent_config.project_tree_depth = depth;
+        self
+    }
+
+    /// Keep working when `task_done` reports a confidence below `min_confidence`
+    pub fn with_min_confidence(mut self, min_confidence: f32) -> Self {
+        self.agent_config.min_confidence = Some(min_confidence);
+        self
+    }
+
+    /// Replace repeated, unchanged read-only tool output with a short note
+    pub fn with_note_unchanged_tool_results(mut self, enabled: bool) -> Self {
+        self.agent_config.note_unchanged_tool_results = enabled;
+        self
+    }
+
+    /// Summarize the progress of interrupted tasks, waiting at most `timeout`
+    pub fn with_interrupt_summary(mut self, timeout: std::time::Duration) -> Self {
+        self.agent_config.interrupt_summary_timeout = Some(timeout);
+        self
+    }
+
+    /// Run failed tasks again according to `policy`
+    pub fn with_task_retry(mut self, policy: TaskRetryPolicy) -> Self {
+        self.agent_config.task_retry = Some(policy);
+        self
+    }
+
+    /// Limit how many tokens the model may generate per response
+    pub fn with_max_output_tokens(mut self, max_tokens: u32) -> Self {
+        self.agent_config.max_output_tokens = Some(max_tokens);
+        self
+    }
+
     /// Inject a global AbortController for cancellation support
     pub fn with_cancellation(mut self, controller: super::AbortController) -> Self {
         self.abort_controller = Some(controller);
         self
     }
 
+    /// Report counters and histograms to the given metrics sink
+    pub fn with_metrics(mut self, metrics: std::sync::Arc<dyn crate::metrics::Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Rewrite assistant text responses before they are shown to the user
+    pub fn with_text_transformer(
+        mut self,
+        transformer: std::sync::Arc<dyn crate::output::AssistantTextTransformer>,
+    ) -> Self {
+        self.text_transformer = Some(transformer);
+        self
+    }
+
+    /// Throttle LLM requests through a limiter that may be shared with other agents
+    pub fn with_rate_limiter(mut self, limiter: std::sync::Arc<crate::llm::RateLimiter>) -> Self {
+        self.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// End tasks successfully once `condition` holds after a step
+    pub fn with_stop_condition(
+        mut self,
+        condition: std::sync::Arc<dyn super::StopCondition>,
+    ) -> Self {
+        self.stop_condition = Some(condition);
+        self
+    }
+
+    /// Offer tools only at the steps `gate` allows them
+    pub fn with_tool_gate(mut self, gate: std::sync::Arc<dyn super::ToolGate>) -> Self {
+        self.tool_gate = Some(gate);
+        self
+    }
+
+    /// Answer tool calls from a recorded trajectory instead of running tools
+    pub fn with_tool_replay(
+        mut self,
+        replay: std::sync::Arc<crate::trajectory::ReplayToolExecutor>,
+    ) -> Self {
+        self.tool_replay = Some(replay);
+        self
+    }
+
+    /// Send events to `handler` as well; may be called repeatedly
+    ///
+    /// A handler passed to `build_with_output*` comes first, then these in the
+    /// order added. Only the first handler answers confirmations and questions.
+    pub fn with_output(mut self, handler: Box<dyn crate::output::AgentOutput>) -> Self {
+        self.outputs.push(handler);
+        self
+    }
+
+    /// Append events to `path` as JSON lines
+    pub fn with_json_log(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.logs
+            .push((path.into(), crate::output::LogFormat::Json));
+        self
+    }
+
+    /// Append events to `path` as human-readable lines
+    pub fn with_file_log(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.logs
+            .push((path.into(), crate::output::LogFormat::Text));
+        self
+    }
+
+    /// Combine `primary` with the handlers and logs added to the builder
+    async fn compose_output(
+        &mut self,
+        primary: Option<Box<dyn crate::output::AgentOutput>>,
+    ) -> crate::error::Result<Box<dyn crate::output::AgentOutput>> {
+        let mut handlers: Vec<Box<dyn crate::output::AgentOutput>> = primary.into_iter().collect();
+        handlers.append(&mut self.outputs);
+        for (path, format) in self.logs.drain(..) {
+            handlers.push(Box::new(
+                crate::output::FileLogOutput::open(path, format).await?,
+            ));
+        }
+        Ok(match handlers.len() {
+            0 => Box::new(crate::output::NullOutput),
+            1 => handlers.remove(0),
+            _ => Box::new(crate::output::TeeOutput::new(handlers)),
+        })
+    }
+
     /// Build the agent with the given output handler
     pub async fn build_with_output(
-        self,
+        mut self,
         output: Box<dyn crate::output::AgentOutput>,
     ) -> crate::error::Result<super::AgentCore> {
-        super::AgentCore::new_with_llm_config(
+        let output = self.compose_output(Some(output)).await?;
+        let mut agent = super::AgentCore::new_with_llm_config(
             self.agent_config,
             self.llm_config,
             output,
             self.abort_controller,
         )
-        .await
+        .await?;
+        if let Some(metrics) = self.metrics {
+            agent.set_metrics(metrics);
+        }
+        if let Some(transformer) = self.text_transformer {
+            agent.set_text_transformer(transformer);
+        }
+        if let Some(limiter) = self.rate_limiter {
+            agent.set_rate_limiter(limiter);
+        }
+        if let Some(condition) = self.stop_condition {
+            agent.set_stop_condition(condition);
+        }
+        if let Some(gate) = self.tool_gate {
+            agent.set_tool_gate(gate);
+        }
+        if let Some(replay) = self.tool_replay {
+            agent.set_tool_replay(replay);
+        }
+        Ok(agent)
     }
 
     /// Build the agent with custom output handler and tool registry
     pub async fn build_with_output_and_registry(
-        self,
+        mut self,
         output: Box<dyn crate::output::AgentOutput>,
         tool_registry: crate::tools::ToolRegistry,
     ) -> crate::error::Result<super::AgentCore> {
-        super::AgentCore::new_with_output_and_registry(
+        let output = self.compose_output(Some(output)).await?;
+        let mut agent = super::AgentCore::new_with_output_and_registry(
             self.agent_config,
             self.llm_config,
             output,
             tool_registry,
             self.abort_controller,
         )
-        .await
+        .await?;
+        if let Some(metrics) = self.metrics {
+            agent.set_metrics(metrics);
+        }
+        if let Some(transformer) = self.text_transformer {
+            agent.set_text_transformer(transformer);
+        }
+        if let Some(limiter) = self.rate_limiter {
+            agent.set_rate_limiter(limiter);
+        }
+        if let Some(condition) = self.stop_condition {
+            agent.set_stop_condition(condition);
+        }
+        if let Some(gate) = self.tool_gate {
+            agent.set_tool_gate(gate);
+        }
+        if let Some(replay) = self.tool_replay {
+            agent.set_tool_replay(replay);
+        }
+        Ok(agent)
     }
 
-    /// Build the agent with null output (for testing)
-    pub async fn build(self) -> crate::error::Result<super::AgentCore> {
-        use crate::output::events::NullOutput;
-        self.build_with_output(Box::new(NullOutput)).await
+    /// Build the agent with the handlers added through `with_output` and the
+    /// logs, or with null output when there are none
+    pub async fn build(mut self) -> crate::error::Result<super::AgentCore> {
+        let output = self.compose_output(None).await?;
+        self.build_with_output(output).await
     }
 }
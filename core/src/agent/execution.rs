@@ -1,7 +1,37 @@
 //! Agent execution result structures
 
+use crate::agent::CompressionLevel;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Why an execution did not succeed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    /// A step failed with an error
+    Error,
+    /// A step failed with an error that may pass on retry, such as a
+    /// network failure, rate limit or server error
+    Transient,
+    /// The task was cancelled through the abort controller
+    Interrupted,
+    /// The task ran longer than `max_duration`
+    Deadline,
+    /// The step limit was reached before the task completed
+    StepLimit,
+    /// The model refused or its response was blocked by a content filter
+    Refused,
+}
+
+/// A file the agent created during a task
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Artifact {
+    /// Where the file was created
+    pub path: PathBuf,
+    /// Size in bytes when the task ended
+    pub size: u64,
+}
 
 /// Result of agent execution
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -12,6 +42,11 @@ pub struct AgentExecution {
     /// Final result message
     pub final_result: String,
 
+    /// The agent's conclusion: the `task_done` summary, or the last
+    /// substantive assistant text when the task ended without it
+    #[serde(default)]
+    pub final_message: Option<String>,
+
     /// Number of steps executed
     pub steps_executed: usize,
 
@@ -23,6 +58,32 @@ pub struct AgentExecution {
 
     /// Optional metadata
     pub metadata: Option<HashMap<String, serde_json::Value>>,
+
+    /// Why the execution failed, when known
+    #[serde(default)]
+    pub failure_kind: Option<FailureKind>,
+
+    /// How often each compression level was applied during the task
+    #[serde(default)]
+    pub compression_stats: HashMap<CompressionLevel, usize>,
+
+    /// Files created during the task that still exist, in creation order;
+    /// edits to files that already existed are not included
+    #[serde(default)]
+    pub artifacts: Vec<Artifact>,
+
+    /// URLs fetched by tools during the task, in the order first consulted
+    #[serde(default)]
+    pub sources: Vec<String>,
+
+    /// Confidence from 0 to 1 the model reported with its final `task_done`
+    #[serde(default)]
+    pub confidence: Option<f32>,
+
+    /// The model's account of the progress made before the task was
+    /// interrupted, when `interrupt_summary_timeout` is set
+    #[serde(default)]
+    pub progress_summary: Option<String>,
 }
 
 impl AgentExecution {
@@ -31,10 +92,17 @@ impl AgentExecution {
         Self {
             success: true,
             final_result,
+            final_message: None,
             steps_executed,
             duration_ms,
             data: None,
             metadata: None,
+            failure_kind: None,
+            compression_stats: HashMap::new(),
+            artifacts: Vec::new(),
+            sources: Vec::new(),
+            confidence: None,
+            progress_summary: None,
         }
     }
 
@@ -43,19 +111,71 @@ impl AgentExecution {
         Self {
             success: false,
             final_result: format!("Execution failed: {}", error),
+            final_message: None,
             steps_executed,
             duration_ms,
             data: None,
             metadata: None,
+            failure_kind: None,
+            compression_stats: HashMap::new(),
+            artifacts: Vec::new(),
+            sources: Vec::new(),
+            confidence: None,
+            progress_summary: None,
         }
     }
 
+    /// Set the agent's final answer
+    pub fn with_final_message(mut self, final_message: Option<String>) -> Self {
+        self.final_message = final_message;
+        self
+    }
+
     /// Add structured data to the result
     pub fn with_data(mut self, data: serde_json::Value) -> Self {
         self.data = Some(data);
         self
     }
 
+    /// Record why the execution failed
+    pub fn with_failure_kind(mut self, failure_kind: FailureKind) -> Self {
+        self.failure_kind = Some(failure_kind);
+        self
+    }
+
+    /// Record how often each compression level was applied
+    pub fn with_compression_stats(
+        mut self,
+        compression_stats: HashMap<CompressionLevel, usize>,
+    ) -> Self {
+        self.compression_stats = compression_stats;
+        self
+    }
+
+    /// Record the files created during the task
+    pub fn with_artifacts(mut self, artifacts: Vec<Artifact>) -> Self {
+        self.artifacts = artifacts;
+        self
+    }
+
+    /// Record the URLs consulted during the task
+    pub fn with_sources(mut self, sources: Vec<String>) -> Self {
+        self.sources = sources;
+        self
+    }
+
+    /// Record the confidence the model reported when finishing
+    pub fn with_confidence(mut self, confidence: Option<f32>) -> Self {
+        self.confidence = confidence;
+        self
+    }
+
+    /// Record the summary of the progress made before an interruption
+    pub fn with_progress_summary(mut self, progress_summary: Option<String>) -> Self {
+        self.progress_summary = progress_summary;
+        self
+    }
+
     /// Add metadata to the result
     pub fn with_metadata(mut self, metadata: HashMap<String, serde_json::Value>) -> Self {
         self.metadata = Some(metadata);
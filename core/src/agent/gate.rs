@@ -0,0 +1,73 @@
+//! Step-dependent tool availability
+
+use crate::llm::{ContentBlock, LlmMessage, MessageContent};
+
+/// Decides which tools are offered to the model at each step
+///
+/// Tools the gate rejects are left out of the tool definitions and refused
+/// if the model calls them anyway.
+pub trait ToolGate: Send + Sync {
+    /// Whether `tool_name` may be used at `step`, given the conversation so far
+    fn is_available(&self, tool_name: &str, step: usize, messages: &[LlmMessage]) -> bool;
+}
+
+impl<F> ToolGate for F
+where
+    F: Fn(&str, usize, &[LlmMessage]) -> bool + Send + Sync,
+{
+    fn is_available(&self, tool_name: &str, step: usize, messages: &[LlmMessage]) -> bool {
+        self(tool_name, step, messages)
+    }
+}
+
+/// Offers a tool only after another tool has run successfully
+#[derive(Debug, Clone)]
+pub struct RequiresToolRun {
+    tool: String,
+    prerequisite: String,
+}
+
+impl RequiresToolRun {
+    /// Gate `tool` until `prerequisite` has succeeded, e.g. "apply_patch" after "read_file"
+    pub fn new(tool: impl Into<String>, prerequisite: impl Into<String>) -> Self {
+        Self {
+            tool: tool.into(),
+            prerequisite: prerequisite.into(),
+        }
+    }
+}
+
+impl ToolGate for RequiresToolRun {
+    fn is_available(&self, tool_name: &str, _step: usize, messages: &[LlmMessage]) -> bool {
+        if tool_name != self.tool {
+            return true;
+        }
+
+        let blocks: Vec<&ContentBlock> = messages
+            .iter()
+            .filter_map(|message| match &message.content {
+                MessageContent::MultiModal(blocks) => Some(blocks),
+                MessageContent::Text(_) => None,
+            })
+            .flatten()
+            .collect();
+
+        let prerequisite_ids: Vec<&str> = blocks
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::ToolUse { id, name, .. } if *name == self.prerequisite => {
+                    Some(id.as_str())
+                }
+                _ => None,
+            })
+            .collect();
+
+        blocks.iter().any(|block| {
+            matches!(
+                block,
+                ContentBlock::ToolResult { tool_use_id, is_error, .. }
+                    if *is_error != Some(true) && prerequisite_ids.contains(&tool_use_id.as_str())
+            )
+        })
+    }
+}
@@ -1,22 +1,40 @@
 //! Agent core logic and execution engine
 
 pub mod base;
+pub mod commit;
 pub mod config;
 pub mod core;
 pub mod execution;
+pub mod gate;
+pub mod pool;
 pub mod prompt;
+pub mod schema;
 pub mod state;
+pub mod stop;
 pub mod tokens;
 
 pub use base::{Agent, AgentResult};
-pub use config::{AgentBuilder, AgentConfig, OutputMode};
+pub use commit::commit_files;
+pub use config::{
+    AgentBuilder, AgentConfig, CommitConfig, OutputMode, TaskRetryPolicy, ThinkingEmission,
+};
 pub use core::AgentCore;
-pub use execution::AgentExecution;
-pub use prompt::{build_system_prompt_with_context, build_user_message, CORO_CODE_SYSTEM_PROMPT};
-pub use state::PersistedAgentContext;
+pub use execution::{AgentExecution, Artifact, FailureKind};
+pub use gate::{RequiresToolRun, ToolGate};
+pub use pool::AgentPool;
+pub use prompt::{
+    build_continuation_message_with_attachments, build_low_confidence_message,
+    build_output_schema_message, build_reflection_message, build_schema_retry_message,
+    build_system_prompt_with_context, build_system_prompt_with_ignore, build_user_message,
+    build_user_message_with_attachments, frame_untrusted_output, FileAttachment,
+    CORO_CODE_SYSTEM_PROMPT, DEFAULT_PROJECT_TREE_DEPTH,
+};
+pub use schema::parse_structured_answer;
+pub use state::{PersistedAgentContext, SystemPromptRedaction};
+pub use stop::{StopCondition, ToolResultContains};
 pub use tokens::{
-    CompressionLevel, CompressionSummary, ConversationManager, ConversationTokenStats,
-    MaybeCompressedResult, TokenCalculator,
+    CompressionDecision, CompressionLevel, CompressionSummary, ConversationManager,
+    ConversationTokenStats, MaybeCompressedResult, TokenCalculator,
 };
 
 pub mod abort;
@@ -0,0 +1,59 @@
+//! Cancelling every agent of a service at once
+
+use super::{AbortController, AgentCore};
+use std::sync::{Arc, Mutex};
+
+/// Tracks the abort controllers of live agents so they can be cancelled together
+///
+/// The pool holds only controllers, not the agents. An agent drops out of the
+/// pool once it is dropped or cancelled. Agents given a new controller with
+/// [`AgentCore::set_abort_controller`] must be registered again.
+#[derive(Debug, Clone, Default)]
+pub struct AgentPool {
+    controllers: Arc<Mutex<Vec<AbortController>>>,
+}
+
+impl AgentPool {
+    /// Create an empty pool
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Track `agent` until it is dropped or cancelled
+    pub fn register(&self, agent: &AgentCore) {
+        self.register_controller(agent.abort_controller().clone());
+    }
+
+    /// Track a controller handed to an agent that is built elsewhere
+    pub fn register_controller(&self, controller: AbortController) {
+        let mut controllers = self.controllers.lock().unwrap();
+        Self::prune(&mut controllers);
+        controllers.push(controller);
+    }
+
+    /// Number of tracked agents that are alive and not cancelled
+    pub fn active_count(&self) -> usize {
+        let mut controllers = self.controllers.lock().unwrap();
+        Self::prune(&mut controllers);
+        controllers.len()
+    }
+
+    /// Cancel every tracked agent, returning how many were cancelled
+    pub fn cancel_all(&self) -> usize {
+        self.cancel_all_with_reason("Agent pool shut down")
+    }
+
+    /// Cancel every tracked agent with `reason`, returning how many were cancelled
+    pub fn cancel_all_with_reason(&self, reason: &str) -> usize {
+        let mut controllers = self.controllers.lock().unwrap();
+        Self::prune(&mut controllers);
+        for controller in controllers.iter() {
+            controller.cancel_with_reason(reason);
+        }
+        std::mem::take(&mut *controllers).len()
+    }
+
+    fn prune(controllers: &mut Vec<AbortController>) {
+        controllers.retain(|controller| controller.has_subscribers() && !controller.is_cancelled());
+    }
+}
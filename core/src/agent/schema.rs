@@ -0,0 +1,174 @@
+//! Structured final answers checked against a caller-supplied JSON schema
+//!
+//! Only the subset of JSON Schema needed to describe an answer is checked:
+//! `type`, `enum`, `const`, `properties`, `required`, `additionalProperties`
+//! and `items`. Other keywords are ignored.
+
+use serde_json::Value;
+
+/// Parse `text` as JSON and check it against `schema`
+///
+/// A surrounding Markdown code fence is tolerated. Returns the parsed value,
+/// or a description of the first mismatch.
+pub fn parse_structured_answer(text: &str, schema: &Value) -> Result<Value, String> {
+    let json = strip_code_fence(text.trim());
+    let value: Value =
+        serde_json::from_str(json).map_err(|e| format!("the answer is not valid JSON: {}", e))?;
+    validate(&value, schema, "$")?;
+    Ok(value)
+}
+
+/// The contents of a ```` ```json ```` fence, or `text` unchanged
+fn strip_code_fence(text: &str) -> &str {
+    let Some(body) = text.strip_prefix("```") else {
+        return text;
+    };
+    let Some(body) = body.strip_suffix("```") else {
+        return text;
+    };
+    // Drop the info string, e.g. `json`
+    match body.split_once('\n') {
+        Some((_, json)) => json.trim(),
+        None => body.trim(),
+    }
+}
+
+fn validate(value: &Value, schema: &Value, path: &str) -> Result<(), String> {
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|name| has_type(value, name)) {
+            return Err(format!(
+                "{} should be of type {}, found {}",
+                path,
+                allowed.join(" or "),
+                type_name(value)
+            ));
+        }
+    }
+
+    if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+        if !options.contains(value) {
+            return Err(format!(
+                "{} should be one of {}",
+                path,
+                Value::from(options.clone())
+            ));
+        }
+    }
+
+    if let Some(constant) = schema.get("const") {
+        if constant != value {
+            return Err(format!("{} should be {}", path, constant));
+        }
+    }
+
+    if let Value::Object(object) = value {
+        let properties = schema.get("properties").and_then(Value::as_object);
+
+        if let Some(required) = schema.get("required").and_then(Value::as_array) {
+            if let Some(missing) = required
+                .iter()
+                .filter_map(Value::as_str)
+                .find(|key| !object.contains_key(*key))
+            {
+                return Err(format!(
+                    "{} is missing required property `{}`",
+                    path, missing
+                ));
+            }
+        }
+
+        for (key, item) in object {
+            let item_path = format!("{}.{}", path, key);
+            match properties.and_then(|properties| properties.get(key)) {
+                Some(item_schema) => validate(item, item_schema, &item_path)?,
+                None => match schema.get("additionalProperties") {
+                    Some(Value::Bool(false)) => {
+                        return Err(format!("{} is not an allowed property", item_path));
+                    }
+                    Some(extra @ Value::Object(_)) => validate(item, extra, &item_path)?,
+                    _ => {}
+                },
+            }
+        }
+    }
+
+    if let (Value::Array(items), Some(item_schema)) = (value, schema.get("items")) {
+        for (index, item) in items.iter().enumerate() {
+            validate(item, item_schema, &format!("{}[{}]", path, index))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "integer" => value.as_i64().is_some() || value.as_u64().is_some(),
+        "number" => value.is_number(),
+        other => type_name(value) == other,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_answer_checked_against_schema() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "status": {"enum": ["ok", "failed"]},
+                "files": {"type": "array", "items": {"type": "string"}},
+                "count": {"type": "integer"}
+            },
+            "required": ["status"],
+            "additionalProperties": false
+        });
+
+        let answer = "```json\n{\"status\": \"ok\", \"files\": [\"a.rs\"], \"count\": 2}\n```";
+        assert_eq!(
+            parse_structured_answer(answer, &schema).unwrap(),
+            json!({"status": "ok", "files": ["a.rs"], "count": 2})
+        );
+
+        let cases = [
+            ("Done!", "not valid JSON"),
+            ("{}", "$ is missing required property `status`"),
+            ("{\"status\": \"maybe\"}", "$.status should be one of"),
+            (
+                "{\"status\": \"ok\", \"files\": [1]}",
+                "$.files[0] should be of type string",
+            ),
+            (
+                "{\"status\": \"ok\", \"count\": 1.5}",
+                "$.count should be of type integer",
+            ),
+            (
+                "{\"status\": \"ok\", \"extra\": 1}",
+                "$.extra is not an allowed property",
+            ),
+        ];
+        for (answer, expected) in cases {
+            let error = parse_structured_answer(answer, &schema).unwrap_err();
+            assert!(error.contains(expected), "{}: {}", answer, error);
+        }
+    }
+}
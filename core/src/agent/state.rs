@@ -6,12 +6,28 @@
 //! independent of any live resources (LLM client, tool registry, etc.).
 
 use crate::agent::config::AgentConfig;
-use crate::llm::LlmMessage;
+use crate::llm::{LlmMessage, MessageRole};
 use crate::output::AgentExecutionContext;
+use crate::tools::builtin::Scratchpad;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::path::Path;
 
+/// How the system prompt is written into an exported snapshot
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SystemPromptRedaction {
+    /// Export the system prompt verbatim
+    #[default]
+    Keep,
+    /// Leave the system prompt out
+    Omit,
+    /// Replace the system prompt with its SHA-256 digest, so snapshots can be
+    /// compared without revealing it
+    Hash,
+}
+
 /// A versioned, serializable snapshot of an agent's context
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PersistedAgentContext {
@@ -27,6 +43,13 @@ pub struct PersistedAgentContext {
     pub conversation_history: Vec<LlmMessage>,
     /// Execution context: goal, current task, token usage, etc.
     pub execution_context: Option<AgentExecutionContext>,
+    /// How the system prompt was redacted on export; a redacted prompt is
+    /// regenerated on restore
+    #[serde(default)]
+    pub system_prompt_redaction: SystemPromptRedaction,
+    /// Notes the model kept with the `scratchpad` tool
+    #[serde(default)]
+    pub scratchpad: Scratchpad,
 }
 
 impl PersistedAgentContext {
@@ -44,7 +67,50 @@ impl PersistedAgentContext {
             config,
             conversation_history,
             execution_context,
+            system_prompt_redaction: SystemPromptRedaction::Keep,
+            scratchpad: Scratchpad::default(),
+        }
+    }
+
+    /// Include the model's scratchpad notes
+    pub fn with_scratchpad(mut self, scratchpad: Scratchpad) -> Self {
+        self.scratchpad = scratchpad;
+        self
+    }
+
+    /// Redact the system prompt, both the leading system message and any
+    /// custom prompt in the captured configuration
+    pub fn redact_system_prompt(mut self, redaction: SystemPromptRedaction) -> Self {
+        if redaction == SystemPromptRedaction::Keep {
+            return self;
         }
+
+        if let Some(config) = &mut self.config {
+            config.system_prompt = None;
+        }
+        if matches!(
+            self.conversation_history.first(),
+            Some(message) if message.role == MessageRole::System
+        ) {
+            let prompt = self.conversation_history.remove(0);
+            if redaction == SystemPromptRedaction::Hash {
+                let text = prompt.get_text().unwrap_or_default();
+                let mut placeholder = LlmMessage::system(Self::redacted_prompt_placeholder(&text));
+                if let Some(created_at) = prompt.created_at() {
+                    placeholder = placeholder.with_created_at(created_at);
+                }
+                self.conversation_history.insert(0, placeholder);
+            }
+        }
+        self.system_prompt_redaction = redaction;
+        self
+    }
+
+    /// Stand-in for a hashed system prompt
+    pub fn redacted_prompt_placeholder(prompt: &str) -> String {
+        let digest = Sha256::digest(prompt.as_bytes());
+        let hex: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+        format!("[redacted system prompt sha256:{}]", hex)
     }
 
     /// Serialize the snapshot to a JSON string
@@ -95,6 +161,8 @@ mod tests {
                 current_step: 1,
                 execution_time: std::time::Duration::from_secs(0),
                 token_usage: Default::default(),
+                progress: 0.0,
+                compression_stats: Default::default(),
             }),
         );
 
@@ -0,0 +1,59 @@
+//! Custom completion criteria for the agent loop
+
+use crate::llm::{ContentBlock, LlmMessage, MessageContent, MessageRole};
+
+/// Completion criterion checked after every step, in addition to `task_done`
+///
+/// When it returns true the task ends successfully.
+pub trait StopCondition: Send + Sync {
+    /// Whether the task is done after `step`, given the conversation so far
+    ///
+    /// The conversation ends with the step's assistant response followed by
+    /// the results of any tools it called.
+    fn should_stop(&self, step: usize, messages: &[LlmMessage]) -> bool;
+}
+
+impl<F> StopCondition for F
+where
+    F: Fn(usize, &[LlmMessage]) -> bool + Send + Sync,
+{
+    fn should_stop(&self, step: usize, messages: &[LlmMessage]) -> bool {
+        self(step, messages)
+    }
+}
+
+/// Stops once a tool result from the latest step contains a marker text
+#[derive(Debug, Clone)]
+pub struct ToolResultContains {
+    marker: String,
+}
+
+impl ToolResultContains {
+    /// Stop when a tool result contains `marker`, e.g. "ALL TESTS PASSED"
+    pub fn new(marker: impl Into<String>) -> Self {
+        Self {
+            marker: marker.into(),
+        }
+    }
+}
+
+impl StopCondition for ToolResultContains {
+    fn should_stop(&self, _step: usize, messages: &[LlmMessage]) -> bool {
+        messages
+            .iter()
+            .rev()
+            .take_while(|message| message.role == MessageRole::Tool)
+            .filter_map(|message| match &message.content {
+                MessageContent::MultiModal(blocks) => Some(blocks),
+                MessageContent::Text(_) => None,
+            })
+            .flatten()
+            .any(|block| {
+                matches!(
+                    block,
+                    ContentBlock::ToolResult { content, is_error, .. }
+                        if *is_error != Some(true) && content.contains(&self.marker)
+                )
+            })
+    }
+}
@@ -3,7 +3,7 @@
 //! Provides estimation algorithms for calculating token usage in LLM conversations,
 //! supporting multi-language content and different message types.
 
-use crate::llm::{ContentBlock, LlmMessage, MessageContent, MessageRole};
+use crate::llm::{ContentBlock, LlmMessage, MessageContent, MessageRole, ToolDefinition};
 use serde_json::Value;
 use std::collections::HashMap;
 
@@ -156,6 +156,37 @@ impl TokenCalculator {
         messages.iter().map(Self::estimate_message_tokens).sum()
     }
 
+    /// Count the estimated tokens of a single message, including role overhead
+    /// and every content block (text, images, tool calls and tool results)
+    pub fn count_message(message: &LlmMessage) -> usize {
+        Self::estimate_message_tokens(message) as usize
+    }
+
+    /// Count the estimated tokens of a message list as it would be sent to a provider
+    pub fn count_messages(messages: &[LlmMessage]) -> usize {
+        messages.iter().map(Self::count_message).sum()
+    }
+
+    /// Count the estimated tokens consumed by tool definitions (name, description and schema)
+    pub fn count_tool_definitions(tools: &[ToolDefinition]) -> usize {
+        tools
+            .iter()
+            .map(|tool| {
+                // Per-tool structural overhead ("type": "function", wrapper object)
+                let tool_overhead = 8;
+                tool_overhead
+                    + Self::estimate_text_tokens(&tool.function.name)
+                    + Self::estimate_text_tokens(&tool.function.description)
+                    + Self::estimate_json_tokens(&tool.function.parameters)
+            })
+            .sum::<u32>() as usize
+    }
+
+    /// Count the estimated prompt size of a request: messages plus tool definitions
+    pub fn count_request(messages: &[LlmMessage], tools: Option<&[ToolDefinition]>) -> usize {
+        Self::count_messages(messages) + tools.map(Self::count_tool_definitions).unwrap_or(0)
+    }
+
     /// Calculate token usage statistics for a conversation
     pub fn calculate_conversation_stats(messages: &[LlmMessage]) -> ConversationTokenStats {
         let mut stats = ConversationTokenStats::default();
@@ -215,6 +246,38 @@ impl TokenCalculator {
         limits
     }
 
+    /// Output tokens requested for models with no known output limit
+    pub const DEFAULT_MAX_OUTPUT_TOKENS: u32 = 8192;
+
+    /// Get the most tokens common models generate in a single response
+    pub fn get_model_output_limits() -> HashMap<&'static str, u32> {
+        let mut limits = HashMap::new();
+
+        limits.insert("gpt-4o", 16_384);
+        limits.insert("gpt-4o-mini", 16_384);
+        limits.insert("gpt-4-turbo", 4_096);
+        limits.insert("gpt-4", 8_192);
+        limits.insert("gpt-3.5-turbo", 4_096);
+        limits.insert("claude-3-5-sonnet", 8_192);
+        limits.insert("claude-3-5-haiku", 8_192);
+        limits.insert("claude-3-opus", 4_096);
+        limits.insert("gemini-1.5-pro", 8_192);
+        limits.insert("gemini-1.5-flash", 8_192);
+
+        limits
+    }
+
+    /// Output token limit for `model`, by the longest matching model name prefix
+    pub fn max_output_tokens_for_model(model: &str) -> u32 {
+        let model = model.to_lowercase();
+        Self::get_model_output_limits()
+            .into_iter()
+            .filter(|(prefix, _)| model.starts_with(prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, limit)| limit)
+            .unwrap_or(Self::DEFAULT_MAX_OUTPUT_TOKENS)
+    }
+
     /// Estimate if adding content would exceed a token budget
     pub fn would_exceed_budget(current_tokens: u32, additional_content: &str, budget: u32) -> bool {
         let additional_tokens = Self::estimate_text_tokens(additional_content);
@@ -396,6 +459,100 @@ mod tests {
         assert!(*limits.get("gpt-4o").unwrap() > 100_000);
     }
 
+    #[test]
+    fn test_unlisted_models_get_default_output_limit() {
+        for model in ["deepseek-chat", "claude-sonnet-4", "o3-mini"] {
+            assert_eq!(TokenCalculator::max_output_tokens_for_model(model), 8192);
+        }
+        assert_eq!(
+            TokenCalculator::max_output_tokens_for_model("gpt-4o-2024-08-06"),
+            16_384
+        );
+    }
+
+    #[test]
+    fn test_count_messages_text_only() {
+        let messages = vec![
+            LlmMessage::system("You are a helpful assistant."),
+            LlmMessage::user("Please list the files in the current directory."),
+            LlmMessage::assistant("Sure, here they are."),
+        ];
+
+        let total = TokenCalculator::count_messages(&messages);
+        let per_message: usize = messages.iter().map(TokenCalculator::count_message).sum();
+
+        assert_eq!(total, per_message);
+        // ~28 content tokens + 12 role overhead
+        assert!(
+            (30..=50).contains(&total),
+            "Expected ~40 tokens, got {}",
+            total
+        );
+    }
+
+    #[test]
+    fn test_count_messages_tool_heavy() {
+        let tool_output = "src/main.rs\nsrc/lib.rs\n".repeat(50);
+        let messages = vec![
+            LlmMessage::user("List files"),
+            LlmMessage {
+                role: MessageRole::Assistant,
+                content: MessageContent::MultiModal(vec![ContentBlock::ToolUse {
+                    id: "call_1".to_string(),
+                    name: "bash".to_string(),
+                    input: json!({"command": "find src -name '*.rs'"}),
+                }]),
+                metadata: None,
+            },
+            LlmMessage {
+                role: MessageRole::Tool,
+                content: MessageContent::MultiModal(vec![ContentBlock::ToolResult {
+                    tool_use_id: "call_1".to_string(),
+                    is_error: Some(false),
+                    content: tool_output.clone(),
+                }]),
+                metadata: None,
+            },
+        ];
+
+        let total = TokenCalculator::count_messages(&messages);
+        let output_tokens = TokenCalculator::estimate_text_tokens(&tool_output) as usize;
+
+        // Dominated by the tool output (~300 tokens) plus call/result overhead
+        assert!(total > output_tokens);
+        assert!(
+            (300..=400).contains(&total),
+            "Expected ~340 tokens, got {}",
+            total
+        );
+    }
+
+    #[test]
+    fn test_count_request_includes_tool_schemas() {
+        let messages = vec![LlmMessage::user("Hello")];
+        let tools = vec![ToolDefinition {
+            tool_type: "function".to_string(),
+            function: crate::llm::FunctionDefinition {
+                name: "bash".to_string(),
+                description: "Run a shell command".to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {"command": {"type": "string"}},
+                    "required": ["command"]
+                }),
+            },
+        }];
+
+        let without_tools = TokenCalculator::count_request(&messages, None);
+        let with_tools = TokenCalculator::count_request(&messages, Some(&tools));
+
+        assert_eq!(
+            with_tools - without_tools,
+            TokenCalculator::count_tool_definitions(&tools)
+        );
+        assert!((20..=60).contains(&(with_tools - without_tools)));
+    }
+
     #[test]
     fn test_budget_check() {
         let current = 1000;
@@ -7,11 +7,13 @@ use super::calculator::TokenCalculator;
 use crate::error::Result;
 use crate::llm::{ChatOptions, ContentBlock, LlmClient, LlmMessage, MessageContent, MessageRole};
 use crate::output::AgentExecutionContext;
+use crate::tools::utils::truncate_to_char_boundary;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::sync::Arc;
 
 /// Compression level for different strategies
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum CompressionLevel {
     /// Light compression: compress tool outputs and redundant content
     Light,
@@ -43,6 +45,19 @@ pub struct CompressionSummary {
     pub summary: String,
 }
 
+/// Why the last `maybe_compress` call did or didn't compress
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompressionDecision {
+    /// Estimated conversation tokens when the decision was made
+    pub estimated_tokens: u32,
+    /// Token count that triggered `level_chosen`, or the lowest threshold if none was crossed
+    pub threshold: u32,
+    /// Level picked because its threshold was crossed
+    pub level_chosen: Option<CompressionLevel>,
+    /// Whether compression completed (false when skipped or when it failed)
+    pub ran: bool,
+}
+
 /// Result of maybe applying compression
 #[derive(Debug)]
 pub struct MaybeCompressedResult {
@@ -76,6 +91,8 @@ pub struct ConversationManager {
     tool_output_budget: u32,
     /// Maximum tokens for generated summaries
     max_summary_tokens: u32,
+    /// Outcome of the most recent `maybe_compress` evaluation
+    last_decision: CompressionDecision,
 }
 
 impl ConversationManager {
@@ -90,6 +107,7 @@ impl ConversationManager {
             preserve_recent_pairs: 3,
             tool_output_budget: 2000,
             max_summary_tokens: 500,
+            last_decision: CompressionDecision::default(),
         }
     }
 
@@ -106,69 +124,26 @@ impl ConversationManager {
         self.current_tokens = TokenCalculator::estimate_conversation_tokens(&messages);
 
         // Check if compression is needed
-        let usage_ratio = self.current_tokens as f64 / self.max_tokens as f64;
+        let compression_level = self.level_for(self.current_tokens);
 
-        let compression_level = if usage_ratio >= self.compression_thresholds[2] {
-            Some((
-                CompressionLevel::Heavy,
-                "Token usage exceeding heavy threshold",
-            ))
-        } else if usage_ratio >= self.compression_thresholds[1] {
-            Some((
-                CompressionLevel::Medium,
-                "Token usage exceeding medium threshold",
-            ))
-        } else if usage_ratio >= self.compression_thresholds[0] {
-            Some((
-                CompressionLevel::Light,
-                "Token usage exceeding light threshold",
-            ))
-        } else {
-            None
+        let threshold_index = match compression_level {
+            Some(CompressionLevel::Heavy) => 2,
+            Some(CompressionLevel::Medium) => 1,
+            _ => 0,
+        };
+        self.last_decision = CompressionDecision {
+            estimated_tokens: self.current_tokens,
+            threshold: (self.max_tokens as f64 * self.compression_thresholds[threshold_index])
+                as u32,
+            level_chosen: compression_level,
+            ran: false,
         };
 
         match compression_level {
-            Some((level, _reason)) => {
-                let target_tokens =
-                    (self.max_tokens as f64 * self.get_compression_target(level)) as u32;
-                let messages_before_count = messages.len() as u32;
-                let compressed_messages = self
-                    .apply_compression(messages, level, target_tokens, context)
-                    .await?;
-                let tokens_after =
-                    TokenCalculator::estimate_conversation_tokens(&compressed_messages);
-
-                let summary = CompressionSummary {
-                    level,
-                    tokens_before: self.current_tokens,
-                    tokens_after,
-                    tokens_saved: self.current_tokens.saturating_sub(tokens_after),
-                    messages_before: messages_before_count,
-                    messages_after: compressed_messages.len() as u32,
-                    summary: format!(
-                        "{} compression: {} -> {} messages ({} -> {} tokens, {:.1}% reduction)",
-                        level.as_str(),
-                        messages_before_count,
-                        compressed_messages.len(),
-                        self.current_tokens,
-                        tokens_after,
-                        if self.current_tokens > 0 {
-                            ((self.current_tokens - tokens_after) as f64
-                                / self.current_tokens as f64)
-                                * 100.0
-                        } else {
-                            0.0
-                        }
-                    ),
-                };
-
-                // Update current token count
-                self.current_tokens = tokens_after;
-
-                Ok(MaybeCompressedResult {
-                    messages: compressed_messages,
-                    compression_applied: Some(summary),
-                })
+            Some(level) => {
+                let result = self.compress(messages, level, context).await?;
+                self.last_decision.ran = true;
+                Ok(result)
             }
             None => {
                 // No compression needed
@@ -180,6 +155,91 @@ impl ConversationManager {
         }
     }
 
+    /// Compress ahead of `incoming_tokens` being added to the conversation
+    ///
+    /// The level is chosen as if the incoming content were already part of
+    /// `messages`, so room is made before a large addition overflows the
+    /// context.
+    pub async fn make_room(
+        &mut self,
+        messages: Vec<LlmMessage>,
+        incoming_tokens: u32,
+        context: Option<&AgentExecutionContext>,
+    ) -> Result<MaybeCompressedResult> {
+        let projected_tokens = TokenCalculator::estimate_conversation_tokens(&messages)
+            .saturating_add(incoming_tokens);
+
+        match self.level_for(projected_tokens) {
+            Some(level) => self.compress(messages, level, context).await,
+            None => Ok(MaybeCompressedResult {
+                messages,
+                compression_applied: None,
+            }),
+        }
+    }
+
+    /// Apply compression at `level` regardless of current token usage
+    pub async fn compress(
+        &mut self,
+        messages: Vec<LlmMessage>,
+        level: CompressionLevel,
+        context: Option<&AgentExecutionContext>,
+    ) -> Result<MaybeCompressedResult> {
+        self.current_tokens = TokenCalculator::estimate_conversation_tokens(&messages);
+
+        let target_tokens = (self.max_tokens as f64 * self.get_compression_target(level)) as u32;
+        let messages_before_count = messages.len() as u32;
+        let compressed_messages = self
+            .apply_compression(messages, level, target_tokens, context)
+            .await?;
+        let tokens_after = TokenCalculator::estimate_conversation_tokens(&compressed_messages);
+
+        let summary = CompressionSummary {
+            level,
+            tokens_before: self.current_tokens,
+            tokens_after,
+            tokens_saved: self.current_tokens.saturating_sub(tokens_after),
+            messages_before: messages_before_count,
+            messages_after: compressed_messages.len() as u32,
+            summary: format!(
+                "{} compression: {} -> {} messages ({} -> {} tokens, {:.1}% reduction)",
+                level.as_str(),
+                messages_before_count,
+                compressed_messages.len(),
+                self.current_tokens,
+                tokens_after,
+                if self.current_tokens > 0 {
+                    ((self.current_tokens - tokens_after) as f64 / self.current_tokens as f64)
+                        * 100.0
+                } else {
+                    0.0
+                }
+            ),
+        };
+
+        // Update current token count
+        self.current_tokens = tokens_after;
+
+        Ok(MaybeCompressedResult {
+            messages: compressed_messages,
+            compression_applied: Some(summary),
+        })
+    }
+
+    /// Summarize `messages` into a state snapshot the agent can resume from
+    pub async fn summarize(
+        &self,
+        messages: &[LlmMessage],
+        context: Option<&AgentExecutionContext>,
+    ) -> Result<String> {
+        self.generate_summary(messages, context).await
+    }
+
+    /// Why the last `maybe_compress` call did or didn't compress
+    pub fn last_decision(&self) -> CompressionDecision {
+        self.last_decision
+    }
+
     /// Get current token count estimate
     pub fn current_tokens(&self) -> u32 {
         self.current_tokens
@@ -197,6 +257,21 @@ impl ConversationManager {
 
     // --- Internal Implementation ---
 
+    /// Highest compression level whose threshold `tokens` reaches
+    fn level_for(&self, tokens: u32) -> Option<CompressionLevel> {
+        let usage_ratio = tokens as f64 / self.max_tokens as f64;
+
+        if usage_ratio >= self.compression_thresholds[2] {
+            Some(CompressionLevel::Heavy)
+        } else if usage_ratio >= self.compression_thresholds[1] {
+            Some(CompressionLevel::Medium)
+        } else if usage_ratio >= self.compression_thresholds[0] {
+            Some(CompressionLevel::Light)
+        } else {
+            None
+        }
+    }
+
     fn get_compression_target(&self, level: CompressionLevel) -> f64 {
         match level {
             CompressionLevel::Light => self.compression_targets[0],
@@ -220,7 +295,8 @@ impl ConversationManager {
     }
 
     async fn light_compression(&self, mut messages: Vec<LlmMessage>) -> Result<Vec<LlmMessage>> {
-        for message in &mut messages {
+        // Pinned messages are kept verbatim
+        for message in messages.iter_mut().filter(|msg| !msg.is_pinned()) {
             if let MessageContent::MultiModal(blocks) = &mut message.content {
                 for block in blocks {
                     if let ContentBlock::ToolResult { content, .. } = block {
@@ -244,14 +320,10 @@ impl ConversationManager {
             return Ok(messages);
         }
 
-        // Separate system messages from conversation
-        let (system_messages, conversation_messages): (Vec<_>, Vec<_>) = messages
-            .into_iter()
-            .partition(|msg| matches!(msg.role, MessageRole::System));
-
-        if conversation_messages.is_empty() {
-            return Ok(system_messages);
-        }
+        // System and pinned messages stay where they are
+        let conversation = Self::conversation_indices(&messages);
+        let conversation_messages: Vec<_> =
+            conversation.iter().map(|&i| messages[i].clone()).collect();
 
         // Calculate how much to preserve
         let preserve_count = std::cmp::max(
@@ -260,13 +332,11 @@ impl ConversationManager {
         );
 
         // Use tool-call-aware splitting
-        let (to_compress, to_preserve) =
+        let (to_compress, _) =
             self.split_preserving_tool_pairs(&conversation_messages, preserve_count);
 
         if to_compress.is_empty() {
-            let mut result = system_messages;
-            result.extend(to_preserve);
-            return self.light_compression(result).await;
+            return self.light_compression(messages).await;
         }
 
         // Generate summary
@@ -274,10 +344,12 @@ impl ConversationManager {
         let summary_msg =
             LlmMessage::system(format!("[Previous conversation summary]: {}", summary));
 
-        // Reconstruct conversation
-        let mut result = system_messages;
-        result.push(summary_msg);
-        result.extend(to_preserve);
+        // The summary takes the place of the first summarized message
+        let result = Self::replace_messages(
+            messages,
+            &conversation[..to_compress.len()],
+            Some(summary_msg),
+        );
 
         // Apply light compression to preserved messages
         self.light_compression(result).await
@@ -288,29 +360,19 @@ impl ConversationManager {
             return Ok(messages);
         }
 
-        let mut result = Vec::new();
-
-        // Preserve all system messages
-        for message in &messages {
-            if matches!(message.role, MessageRole::System) {
-                result.push(message.clone());
-            }
-        }
+        // Preserve all system and pinned messages in place
+        let conversation = Self::conversation_indices(&messages);
+        let conversation_messages: Vec<_> =
+            conversation.iter().map(|&i| messages[i].clone()).collect();
 
         // Keep only recent conversation with tool call preservation
-        let conversation_messages: Vec<_> = messages
-            .iter()
-            .filter(|msg| !matches!(msg.role, MessageRole::System))
-            .cloned()
-            .collect();
-
         let keep_count = std::cmp::min(
             self.preserve_recent_pairs as usize * 2,
             conversation_messages.len(),
         );
-        let (_, to_keep) = self.split_preserving_tool_pairs(&conversation_messages, keep_count);
+        let (to_drop, _) = self.split_preserving_tool_pairs(&conversation_messages, keep_count);
 
-        result.extend(to_keep);
+        let result = Self::replace_messages(messages, &conversation[..to_drop.len()], None);
 
         // Apply light compression to remaining content
         self.light_compression(result).await
@@ -326,15 +388,7 @@ impl ConversationManager {
         let half_sample = sample_size / 2;
 
         // Safe UTF-8 character boundary slicing
-        let beginning = if output.len() > half_sample {
-            let mut boundary = half_sample;
-            while boundary < output.len() && !output.is_char_boundary(boundary) {
-                boundary += 1;
-            }
-            &output[..boundary]
-        } else {
-            output
-        };
+        let beginning = truncate_to_char_boundary(output, half_sample);
 
         let ending = if output.len() > sample_size {
             let start_pos = output.len().saturating_sub(half_sample);
@@ -378,24 +432,22 @@ impl ConversationManager {
                     Ok(format!("[Compressed output]: {}", summary))
                 } else {
                     // Fallback to truncation
-                    Ok(format!(
-                        "{}...[truncated {} chars]",
-                        &output[..sample_size.min(output.len())],
-                        output.len().saturating_sub(sample_size)
-                    ))
+                    Ok(Self::truncate_tool_output(output, sample_size))
                 }
             }
             Err(_e) => {
                 // Fallback to truncation
-                Ok(format!(
-                    "{}...[truncated {} chars]",
-                    &output[..sample_size.min(output.len())],
-                    output.len().saturating_sub(sample_size)
-                ))
+                Ok(Self::truncate_tool_output(output, sample_size))
             }
         }
     }
 
+    /// Fallback when the output cannot be summarized: keep a prefix of `sample_size` bytes
+    fn truncate_tool_output(output: &str, sample_size: usize) -> String {
+        let kept = truncate_to_char_boundary(output, sample_size);
+        format!("{}...[truncated {} chars]", kept, output.len() - kept.len())
+    }
+
     async fn generate_summary(
         &self,
         messages: &[LlmMessage],
@@ -425,12 +477,15 @@ impl ConversationManager {
                 msg.get_text().map(|content| {
                     let truncated = if matches!(msg.role, MessageRole::Tool) {
                         if content.len() > 2000 {
-                            format!("{}...[truncated]", &content[..2000])
+                            format!(
+                                "{}...[truncated]",
+                                truncate_to_char_boundary(&content, 2000)
+                            )
                         } else {
                             content
                         }
                     } else if content.len() > 800 {
-                        format!("{}...", &content[..800])
+                        format!("{}...", truncate_to_char_boundary(&content, 800))
                     } else {
                         content
                     };
@@ -441,7 +496,10 @@ impl ConversationManager {
             .join("\\n");
 
         let limited_text = if conversation_text.len() > 15000 {
-            format!("{}...[conversation continues]", &conversation_text[..15000])
+            format!(
+                "{}...[conversation continues]",
+                truncate_to_char_boundary(&conversation_text, 15000)
+            )
         } else {
             conversation_text
         };
@@ -579,6 +637,90 @@ The structure MUST be as follows:
         }
     }
 
+    /// Indices of the messages compression may summarize or drop
+    ///
+    /// System and pinned messages are excluded, and so are the tool calls a
+    /// pinned tool result answers, together with the other results of those
+    /// calls, so that compression never leaves an orphaned tool result or an
+    /// unanswered tool call behind.
+    fn conversation_indices(messages: &[LlmMessage]) -> Vec<usize> {
+        let mut kept: Vec<bool> = messages
+            .iter()
+            .map(|msg| matches!(msg.role, MessageRole::System) || msg.is_pinned())
+            .collect();
+
+        let pinned_results: HashSet<&str> = messages
+            .iter()
+            .filter(|msg| msg.is_pinned())
+            .flat_map(Self::tool_result_ids)
+            .collect();
+        if !pinned_results.is_empty() {
+            let mut paired_calls = HashSet::new();
+            for (index, message) in messages.iter().enumerate() {
+                let calls = Self::tool_use_ids(message);
+                if calls.iter().any(|id| pinned_results.contains(id)) {
+                    kept[index] = true;
+                    paired_calls.extend(calls);
+                }
+            }
+            for (index, message) in messages.iter().enumerate() {
+                if Self::tool_result_ids(message)
+                    .iter()
+                    .any(|id| paired_calls.contains(id))
+                {
+                    kept[index] = true;
+                }
+            }
+        }
+
+        (0..messages.len()).filter(|&i| !kept[i]).collect()
+    }
+
+    fn tool_use_ids(message: &LlmMessage) -> Vec<&str> {
+        match &message.content {
+            MessageContent::Text(_) => Vec::new(),
+            MessageContent::MultiModal(blocks) => blocks
+                .iter()
+                .filter_map(|block| match block {
+                    ContentBlock::ToolUse { id, .. } => Some(id.as_str()),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+
+    fn tool_result_ids(message: &LlmMessage) -> Vec<&str> {
+        match &message.content {
+            MessageContent::Text(_) => Vec::new(),
+            MessageContent::MultiModal(blocks) => blocks
+                .iter()
+                .filter_map(|block| match block {
+                    ContentBlock::ToolResult { tool_use_id, .. } => Some(tool_use_id.as_str()),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+
+    /// Remove the messages at `removed`, putting `replacement` (if any) where
+    /// the first of them was; every other message keeps its relative order
+    fn replace_messages(
+        messages: Vec<LlmMessage>,
+        removed: &[usize],
+        mut replacement: Option<LlmMessage>,
+    ) -> Vec<LlmMessage> {
+        let removed: HashSet<usize> = removed.iter().copied().collect();
+        let mut result = Vec::with_capacity(messages.len() - removed.len() + 1);
+        for (index, message) in messages.into_iter().enumerate() {
+            if !removed.contains(&index) {
+                result.push(message);
+            } else if let Some(replacement) = replacement.take() {
+                result.push(replacement);
+            }
+        }
+        result
+    }
+
     fn split_preserving_tool_pairs(
         &self,
         messages: &[LlmMessage],
@@ -686,6 +828,26 @@ mod tests {
 
         assert!(result.compression_applied.is_none());
         assert_eq!(result.messages.len(), messages.len());
+
+        let decision = manager.last_decision();
+        assert!(!decision.ran);
+        assert_eq!(decision.level_chosen, None);
+        assert_eq!(decision.threshold, 7000); // Light threshold, 70% of 10000
+        assert_eq!(
+            decision.estimated_tokens,
+            TokenCalculator::estimate_conversation_tokens(&messages)
+        );
+    }
+
+    #[test]
+    fn test_truncated_tool_output_keeps_valid_utf8() {
+        // 3-byte characters, so a 10 byte sample ends mid-character
+        let output = "日本語".repeat(10);
+
+        let truncated = ConversationManager::truncate_tool_output(&output, 10);
+
+        assert!(truncated.starts_with("日本語"));
+        assert!(truncated.contains("...[truncated 81 chars]"));
     }
 
     #[tokio::test]
@@ -702,12 +864,138 @@ mod tests {
             messages.push(LlmMessage::assistant(format!("Response {}", i)));
         }
 
+        let estimated_tokens = TokenCalculator::estimate_conversation_tokens(&messages);
         let result = manager.maybe_compress(messages, None).await.unwrap();
 
         assert!(result.compression_applied.is_some());
         let summary = result.compression_applied.unwrap();
         assert!(summary.tokens_saved > 0);
         assert!(result.messages.len() < 100); // Should be compressed
+
+        assert_eq!(
+            manager.last_decision(),
+            CompressionDecision {
+                estimated_tokens,
+                threshold: 90, // Heavy threshold, 90% of 100
+                level_chosen: Some(CompressionLevel::Heavy),
+                ran: true,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pinned_messages_survive_heavy_compression() {
+        let mock_client = Arc::new(MockLlmClient::new(vec![]));
+        let mut manager = ConversationManager::new(100, mock_client); // Force heavy compression
+
+        let task_spec = "Task spec: keep the public API stable";
+        let big_tool_output = "x".repeat(5000);
+        let mut messages = vec![
+            LlmMessage::system("System message"),
+            LlmMessage::pinned_user(task_spec),
+            LlmMessage::user(big_tool_output.clone()).pinned(),
+        ];
+        for i in 0..50 {
+            messages.push(LlmMessage::user(format!("Message {}", i)));
+            messages.push(LlmMessage::assistant(format!("Response {}", i)));
+        }
+
+        let result = manager.maybe_compress(messages, None).await.unwrap();
+
+        let summary = result.compression_applied.unwrap();
+        assert_eq!(summary.level, CompressionLevel::Heavy);
+
+        let texts: Vec<String> = result
+            .messages
+            .iter()
+            .filter_map(|msg| msg.get_text())
+            .collect();
+        assert!(texts.iter().any(|t| t == task_spec));
+        assert!(texts.iter().any(|t| t == &big_tool_output));
+        assert!(!texts.iter().any(|t| t == "Message 0"));
+        assert_eq!(result.messages.iter().filter(|m| m.is_pinned()).count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_pinned_messages_kept_out_of_summary() {
+        let mock_client = Arc::new(MockLlmClient::new(vec![
+            "<state_snapshot>summary</state_snapshot>".to_string(),
+        ]));
+        let manager = ConversationManager::new(10000, mock_client);
+
+        let mut messages = vec![
+            LlmMessage::system("System message"),
+            LlmMessage::pinned_user("Task spec"),
+        ];
+        for i in 0..20 {
+            messages.push(LlmMessage::user(format!("Message {}", i)));
+            messages.push(LlmMessage::assistant(format!("Response {}", i)));
+        }
+
+        let result = manager.medium_compression(messages, None).await.unwrap();
+
+        assert!(result
+            .iter()
+            .any(|m| m.is_pinned() && m.get_text().as_deref() == Some("Task spec")));
+        assert!(result.iter().any(|m| m
+            .get_text()
+            .is_some_and(|t| t.starts_with("[Previous conversation summary]"))));
+        assert!(!result
+            .iter()
+            .any(|m| m.get_text().as_deref() == Some("Message 0")));
+    }
+
+    #[tokio::test]
+    async fn test_pinned_tool_result_kept_in_place_with_its_call() {
+        let mock_client = Arc::new(MockLlmClient::new(vec![]));
+        let manager = ConversationManager::new(100, mock_client);
+
+        let call = LlmMessage {
+            role: MessageRole::Assistant,
+            content: MessageContent::MultiModal(vec![ContentBlock::ToolUse {
+                id: "call_1".to_string(),
+                name: "read_file".to_string(),
+                input: serde_json::json!({"path": "spec.md"}),
+            }]),
+            metadata: None,
+        };
+        let result = LlmMessage {
+            role: MessageRole::Tool,
+            content: MessageContent::MultiModal(vec![ContentBlock::ToolResult {
+                tool_use_id: "call_1".to_string(),
+                is_error: Some(false),
+                content: "Spec contents".to_string(),
+            }]),
+            metadata: None,
+        }
+        .pinned();
+        let mut messages = vec![
+            LlmMessage::system("System message"),
+            LlmMessage::user("Read the spec"),
+            call,
+            result,
+        ];
+        for i in 0..20 {
+            messages.push(LlmMessage::user(format!("Message {}", i)));
+            messages.push(LlmMessage::assistant(format!("Response {}", i)));
+        }
+        messages.insert(10, LlmMessage::pinned_user("Pinned note"));
+
+        let compressed = manager.heavy_compression(messages).await.unwrap();
+
+        assert!(!compressed
+            .iter()
+            .any(|m| m.get_text().as_deref() == Some("Read the spec")));
+        assert!(compressed[1].has_tool_use());
+        assert_eq!(
+            ConversationManager::tool_result_ids(&compressed[2]),
+            vec!["call_1"]
+        );
+        assert_eq!(compressed[3].get_text().as_deref(), Some("Pinned note"));
+        assert_eq!(
+            compressed.last().unwrap().get_text().as_deref(),
+            Some("Response 19")
+        );
     }
 
     #[test]
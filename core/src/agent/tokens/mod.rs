@@ -27,5 +27,6 @@ pub mod conversation_manager;
 // Public API
 pub use calculator::{ConversationTokenStats, TokenCalculator};
 pub use conversation_manager::{
-    CompressionLevel, CompressionSummary, ConversationManager, MaybeCompressedResult,
+    CompressionDecision, CompressionLevel, CompressionSummary, ConversationManager,
+    MaybeCompressedResult,
 };
@@ -4,4 +4,7 @@
 
 pub mod types;
 
-pub use types::{ModelParams, Protocol, ResolvedLlmConfig};
+pub use types::{
+    HttpClientConfig, JitterStrategy, MaxTokensField, ModelParams, Protocol, ResolvedLlmConfig,
+    RetryConfig,
+};
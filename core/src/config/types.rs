@@ -65,6 +65,130 @@ pub struct ModelParams {
     pub stop_sequences: Option<Vec<String>>,
 }
 
+/// Connection pool and keep-alive tuning for provider HTTP clients
+///
+/// Unset fields keep the reqwest defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HttpClientConfig {
+    /// Maximum idle connections kept per host
+    #[serde(default)]
+    pub pool_max_idle_per_host: Option<usize>,
+    /// Seconds an idle pooled connection is kept alive
+    #[serde(default)]
+    pub pool_idle_timeout_secs: Option<u64>,
+    /// Interval in seconds for TCP keep-alive probes
+    #[serde(default)]
+    pub tcp_keepalive_secs: Option<u64>,
+}
+
+/// Randomization applied to retry delays
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JitterStrategy {
+    /// Plain exponential backoff without randomization
+    None,
+    /// Uniform delay between zero and the exponential backoff
+    Full,
+    /// Each delay is drawn between the base delay and three times the previous one
+    #[default]
+    Decorrelated,
+}
+
+/// Retry policy for transient provider errors (rate limits, 5xx, network failures)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryConfig {
+    /// Retries after the first attempt; 0 disables retrying
+    #[serde(default = "RetryConfig::default_max_retries")]
+    pub max_retries: u32,
+    /// Smallest delay between attempts in milliseconds
+    #[serde(default = "RetryConfig::default_base_delay_ms")]
+    pub base_delay_ms: u64,
+    /// Largest delay between attempts in milliseconds
+    #[serde(default = "RetryConfig::default_max_delay_ms")]
+    pub max_delay_ms: u64,
+    /// How delays are randomized
+    #[serde(default)]
+    pub jitter: JitterStrategy,
+    /// Send the request once more, without delay, when the response body
+    /// cannot be parsed
+    #[serde(default = "RetryConfig::default_retry_parse_failure")]
+    pub retry_parse_failure: bool,
+}
+
+impl RetryConfig {
+    fn default_max_retries() -> u32 {
+        3
+    }
+
+    fn default_base_delay_ms() -> u64 {
+        500
+    }
+
+    fn default_retry_parse_failure() -> bool {
+        true
+    }
+
+    fn default_max_delay_ms() -> u64 {
+        30_000
+    }
+
+    /// Policy that never retries
+    pub fn disabled() -> Self {
+        Self {
+            max_retries: 0,
+            retry_parse_failure: false,
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: Self::default_max_retries(),
+            base_delay_ms: Self::default_base_delay_ms(),
+            max_delay_ms: Self::default_max_delay_ms(),
+            jitter: JitterStrategy::default(),
+            retry_parse_failure: Self::default_retry_parse_failure(),
+        }
+    }
+}
+
+/// Request field used to send the completion token limit to OpenAI-compatible APIs
+///
+/// Newer reasoning models reject `max_tokens` and require `max_completion_tokens`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaxTokensField {
+    /// Pick the field based on the model name
+    #[default]
+    Auto,
+    /// Always send `max_tokens`
+    MaxTokens,
+    /// Always send `max_completion_tokens`
+    MaxCompletionTokens,
+}
+
+impl MaxTokensField {
+    /// Model name prefixes known to require `max_completion_tokens`
+    const COMPLETION_TOKENS_MODEL_PREFIXES: &'static [&'static str] = &["o1", "o3", "o4", "gpt-5"];
+
+    /// Whether `max_completion_tokens` should be sent for `model`
+    pub fn uses_max_completion_tokens(&self, model: &str) -> bool {
+        match self {
+            Self::MaxTokens => false,
+            Self::MaxCompletionTokens => true,
+            Self::Auto => {
+                // Proxies often namespace models, e.g. "openai/o3-mini"
+                let name = model.rsplit('/').next().unwrap_or(model).to_lowercase();
+                Self::COMPLETION_TOKENS_MODEL_PREFIXES
+                    .iter()
+                    .any(|prefix| name.starts_with(prefix))
+            }
+        }
+    }
+}
+
 /// A fully resolved LLM configuration ready for use by core
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResolvedLlmConfig {
@@ -82,6 +206,26 @@ pub struct ResolvedLlmConfig {
     /// Additional headers for requests
     #[serde(default)]
     pub headers: HashMap<String, String>,
+    /// HTTP connection pooling and keep-alive settings
+    #[serde(default)]
+    pub http: HttpClientConfig,
+    /// Field used to send the token limit for OpenAI-compatible APIs
+    #[serde(default)]
+    pub max_tokens_field: MaxTokensField,
+    /// Retry policy for transient provider errors
+    #[serde(default)]
+    pub retry: RetryConfig,
+    /// Log raw request and response bodies at `trace` level, with the API key
+    /// and credential headers masked
+    #[serde(default)]
+    pub log_raw_bodies: bool,
+    /// Merge adjacent messages with the same role before sending, for
+    /// providers that require roles to alternate
+    #[serde(default = "ResolvedLlmConfig::default_merge_consecutive_roles")]
+    pub merge_consecutive_roles: bool,
+    /// Middleware wrapping the client's HTTP requests
+    #[serde(skip)]
+    pub middleware: crate::llm::http::HttpMiddlewareStack,
 }
 
 impl ResolvedLlmConfig {
@@ -94,9 +238,19 @@ impl ResolvedLlmConfig {
             model,
             params: ModelParams::default(),
             headers: HashMap::new(),
+            http: HttpClientConfig::default(),
+            max_tokens_field: MaxTokensField::default(),
+            retry: RetryConfig::default(),
+            log_raw_bodies: false,
+            merge_consecutive_roles: Self::default_merge_consecutive_roles(),
+            middleware: Default::default(),
         }
     }
 
+    fn default_merge_consecutive_roles() -> bool {
+        true
+    }
+
     /// Set model parameters
     pub fn with_params(mut self, params: ModelParams) -> Self {
         self.params = params;
@@ -115,6 +269,48 @@ impl ResolvedLlmConfig {
         self
     }
 
+    /// Set HTTP connection pooling and keep-alive settings
+    pub fn with_http_config(mut self, http: HttpClientConfig) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Set the field used to send the token limit for OpenAI-compatible APIs
+    pub fn with_max_tokens_field(mut self, max_tokens_field: MaxTokensField) -> Self {
+        self.max_tokens_field = max_tokens_field;
+        self
+    }
+
+    /// Set the retry policy for transient provider errors
+    pub fn with_retry_config(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Log raw request and response bodies for debugging provider errors
+    pub fn with_log_raw_bodies(mut self, enabled: bool) -> Self {
+        self.log_raw_bodies = enabled;
+        self
+    }
+
+    /// Merge adjacent same-role messages before sending
+    pub fn with_merge_consecutive_roles(mut self, enabled: bool) -> Self {
+        self.merge_consecutive_roles = enabled;
+        self
+    }
+
+    /// Wrap the client's HTTP requests in `middleware`, inside any added before
+    ///
+    /// The OpenAI client cannot wrap streamed requests, which async-openai
+    /// sends itself; it logs a warning when asked to.
+    pub fn with_http_middleware(
+        mut self,
+        middleware: impl crate::llm::http::HttpMiddleware + 'static,
+    ) -> Self {
+        self.middleware.push(std::sync::Arc::new(middleware));
+        self
+    }
+
     /// Validate the configuration
     pub fn validate(&self) -> Result<(), String> {
         if self.api_key.is_empty() {
@@ -91,11 +91,36 @@ pub enum LlmError {
     #[error("Invalid request: {message}")]
     InvalidRequest { message: String },
 
-    #[error("API error: {status} - {message}")]
-    ApiError { status: u16, message: String },
+    #[error("Context length exceeded: {message}")]
+    ContextLengthExceeded { message: String },
+
+    #[error("Server error: {message}")]
+    ServerError { message: String },
+
+    /// `request_id` is the provider's id for the failed call, for support tickets
+    #[error("API error: {status} - {message}{}", request_id_suffix(.request_id))]
+    ApiError {
+        status: u16,
+        message: String,
+        request_id: Option<String>,
+    },
 
     #[error("Network error: {message}")]
     Network { message: String },
+
+    /// The response body arrived but was truncated or not the expected JSON
+    #[error("Failed to parse response: {message}")]
+    ResponseParse { message: String },
+
+    #[error("Request cancelled")]
+    Cancelled,
+}
+
+fn request_id_suffix(request_id: &Option<String>) -> String {
+    request_id
+        .as_ref()
+        .map(|id| format!(" (request id: {})", id))
+        .unwrap_or_default()
 }
 
 /// Tool execution errors
@@ -112,6 +137,9 @@ pub enum ToolError {
 
     #[error("Tool timeout: {name}")]
     Timeout { name: String },
+
+    #[error("Tool requires network access, which is disabled: {name}")]
+    NetworkDisabled { name: String },
 }
 
 /// Agent execution errors
@@ -128,6 +156,12 @@ pub enum AgentError {
 
     #[error("Agent not initialized")]
     NotInitialized,
+
+    #[error("Conversation history has unmatched tool calls: {tool_use_ids}")]
+    UnmatchedToolCalls { tool_use_ids: String },
+
+    #[error("Model refused to respond ({reason})")]
+    ModelRefused { reason: String },
 }
 
 /// Trajectory recording errors
@@ -141,6 +175,9 @@ pub enum TrajectoryError {
 
     #[error("Invalid trajectory format")]
     InvalidFormat,
+
+    #[error("Replayed tool call does not match the recording: expected {expected}, got {actual}")]
+    ReplayMismatch { expected: String, actual: String },
 }
 
 impl From<String> for Error {
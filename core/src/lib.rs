@@ -11,6 +11,7 @@ pub mod agent;
 pub mod config;
 pub mod error;
 pub mod llm;
+pub mod metrics;
 pub mod output;
 pub mod tools;
 pub mod trajectory;
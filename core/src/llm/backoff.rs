@@ -0,0 +1,250 @@
+//! Shared retry timing for LLM providers
+//!
+//! [`Backoff`] produces the delays between attempts according to a
+//! [`RetryConfig`], and [`retry_with_backoff`] drives an async operation with
+//! it. Providers use these so retry behavior is the same everywhere;
+//! [`retry_parse_failure`] adds the single immediate retry for unparseable
+//! responses.
+
+use crate::config::{JitterStrategy, RetryConfig};
+use crate::error::{Error, LlmError, Result};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::future::Future;
+use std::time::Duration;
+
+/// Generator of delays between retry attempts
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    base: Duration,
+    max: Duration,
+    jitter: JitterStrategy,
+    attempt: u32,
+    previous: Duration,
+    rng: StdRng,
+}
+
+impl Backoff {
+    /// Create a backoff seeded from system entropy
+    pub fn new(config: &RetryConfig) -> Self {
+        Self::with_rng(config, StdRng::from_entropy())
+    }
+
+    /// Create a backoff with a fixed seed, producing a deterministic sequence
+    pub fn with_seed(config: &RetryConfig, seed: u64) -> Self {
+        Self::with_rng(config, StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(config: &RetryConfig, rng: StdRng) -> Self {
+        let base = Duration::from_millis(config.base_delay_ms);
+        let max = Duration::from_millis(config.max_delay_ms).max(base);
+        Self {
+            base,
+            max,
+            jitter: config.jitter,
+            attempt: 0,
+            previous: base,
+            rng,
+        }
+    }
+
+    /// Delay to wait before the next attempt
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = match self.jitter {
+            JitterStrategy::None => self.exponential(),
+            JitterStrategy::Full => {
+                let ceiling = self.exponential();
+                self.random_between(Duration::ZERO, ceiling)
+            }
+            JitterStrategy::Decorrelated => {
+                let ceiling = (self.previous * 3).min(self.max);
+                self.random_between(self.base, ceiling)
+            }
+        };
+
+        self.attempt = self.attempt.saturating_add(1);
+        self.previous = delay;
+        delay
+    }
+
+    /// Start over from the base delay
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+        self.previous = self.base;
+    }
+
+    /// `base * 2^attempt`, capped at the maximum delay
+    fn exponential(&self) -> Duration {
+        let factor = 1u32.checked_shl(self.attempt).unwrap_or(u32::MAX);
+        self.base.saturating_mul(factor).min(self.max)
+    }
+
+    fn random_between(&mut self, low: Duration, high: Duration) -> Duration {
+        if high <= low {
+            return low;
+        }
+        let millis = self
+            .rng
+            .gen_range(low.as_millis() as u64..=high.as_millis() as u64);
+        Duration::from_millis(millis)
+    }
+}
+
+/// Whether an error is transient and worth retrying
+pub fn is_retryable(error: &Error) -> bool {
+    match error {
+        Error::Llm(LlmError::RateLimit)
+        | Error::Llm(LlmError::Network { .. })
+        | Error::Llm(LlmError::ServerError { .. }) => true,
+        Error::Llm(LlmError::ApiError { status, .. }) => {
+            matches!(status, 408 | 429 | 500..=599)
+        }
+        _ => false,
+    }
+}
+
+/// Run `operation`, retrying failures accepted by `should_retry` with delays from a [`Backoff`]
+///
+/// Most providers pass [`is_retryable`].
+pub async fn retry_with_backoff<T, F, Fut, P>(
+    config: &RetryConfig,
+    should_retry: P,
+    mut operation: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+    P: Fn(&Error) -> bool,
+{
+    let mut backoff = Backoff::new(config);
+    let mut retries = 0;
+
+    loop {
+        match operation().await {
+            Err(e) if retries < config.max_retries && should_retry(&e) => {
+                retries += 1;
+                let delay = backoff.next_delay();
+                tracing::warn!(
+                    "LLM request failed ({}), retrying in {:?} ({}/{})",
+                    e,
+                    delay,
+                    retries,
+                    config.max_retries
+                );
+                tokio::time::sleep(delay).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Run `operation` once more, without delay, if its response could not be parsed
+///
+/// Truncated or malformed bodies are usually transient, unlike other request
+/// errors. Only retries when `config.retry_parse_failure` is set.
+pub async fn retry_parse_failure<T, F, Fut>(config: &RetryConfig, mut operation: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    match operation().await {
+        Err(Error::Llm(LlmError::ResponseParse { message })) if config.retry_parse_failure => {
+            tracing::warn!("LLM response could not be parsed ({}), retrying", message);
+            operation().await
+        }
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn config(jitter: JitterStrategy) -> RetryConfig {
+        RetryConfig {
+            max_retries: 3,
+            base_delay_ms: 100,
+            max_delay_ms: 5_000,
+            jitter,
+            retry_parse_failure: true,
+        }
+    }
+
+    #[test]
+    fn test_decorrelated_jitter_with_fixed_seed() {
+        let config = config(JitterStrategy::Decorrelated);
+        let mut backoff = Backoff::with_seed(&config, 42);
+        let mut replay = Backoff::with_seed(&config, 42);
+
+        let mut previous = Duration::from_millis(config.base_delay_ms);
+        for _ in 0..20 {
+            let delay = backoff.next_delay();
+            let upper = (previous * 3).min(Duration::from_millis(config.max_delay_ms));
+            assert!(delay >= Duration::from_millis(config.base_delay_ms));
+            assert!(delay <= upper, "{:?} exceeds {:?}", delay, upper);
+            assert_eq!(delay, replay.next_delay());
+            previous = delay;
+        }
+    }
+
+    #[test]
+    fn test_full_jitter_stays_below_exponential_ceiling() {
+        let config = config(JitterStrategy::Full);
+        let mut backoff = Backoff::with_seed(&config, 7);
+
+        for attempt in 0..10u32 {
+            let ceiling =
+                Duration::from_millis(100 * 2u64.pow(attempt)).min(Duration::from_secs(5));
+            assert!(backoff.next_delay() <= ceiling);
+        }
+    }
+
+    #[test]
+    fn test_no_jitter_is_exponential() {
+        let mut backoff = Backoff::new(&config(JitterStrategy::None));
+
+        let delays: Vec<u64> = (0..7)
+            .map(|_| backoff.next_delay().as_millis() as u64)
+            .collect();
+        assert_eq!(delays, vec![100, 200, 400, 800, 1600, 3200, 5000]);
+
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn test_retry_only_transient_errors() {
+        let config = RetryConfig {
+            base_delay_ms: 1,
+            max_delay_ms: 1,
+            ..config(JitterStrategy::None)
+        };
+
+        let calls = AtomicU32::new(0);
+        let result = retry_with_backoff(&config, is_retryable, || async {
+            if calls.fetch_add(1, Ordering::SeqCst) < 2 {
+                Err(LlmError::RateLimit.into())
+            } else {
+                Ok("done")
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), "done");
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        let calls = AtomicU32::new(0);
+        let result: Result<()> = retry_with_backoff(&config, is_retryable, || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(LlmError::ApiError {
+                status: 400,
+                message: "bad request".to_string(),
+                request_id: None,
+            }
+            .into())
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,195 @@
+//! Model capability lookup
+//!
+//! Not every configured model supports the same request features. Base
+//! completion models reject tool definitions, some models can't take images,
+//! and a few don't accept a `system` role. The agent consults
+//! [`ModelCapabilities`] before each request so it can leave out what the
+//! model can't handle instead of failing.
+//!
+//! [`ModelCapabilities::max_tool_result_bytes`] cuts oversized tool results
+//! down instead of letting the provider reject them. The limit is set by the
+//! caller only: the built-in registry leaves it unset, so nothing is cut by
+//! default. Set it with `AgentCore::set_model_capabilities` or a registry
+//! entry of your own when a provider or gateway enforces one.
+
+use serde::{Deserialize, Serialize};
+
+/// Features a model supports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelCapabilities {
+    /// Native function/tool calling
+    #[serde(default = "default_true")]
+    pub supports_tools: bool,
+    /// Image content blocks
+    #[serde(default = "default_true")]
+    pub supports_images: bool,
+    /// Messages with the `system` role
+    #[serde(default = "default_true")]
+    pub supports_system_role: bool,
+    /// Streaming responses
+    #[serde(default = "default_true")]
+    pub supports_streaming: bool,
+    /// Largest tool result, in bytes, accepted in a single block
+    ///
+    /// Only set by callers; the built-in registry never sets it.
+    #[serde(default)]
+    pub max_tool_result_bytes: Option<usize>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for ModelCapabilities {
+    fn default() -> Self {
+        Self {
+            supports_tools: true,
+            supports_images: true,
+            supports_system_role: true,
+            supports_streaming: true,
+            max_tool_result_bytes: None,
+        }
+    }
+}
+
+impl ModelCapabilities {
+    /// Capabilities of a plain completion model without tools, images or system role
+    pub fn text_only() -> Self {
+        Self {
+            supports_tools: false,
+            supports_images: false,
+            supports_system_role: false,
+            supports_streaming: true,
+            max_tool_result_bytes: None,
+        }
+    }
+
+    /// Look up capabilities for a model using the built-in registry
+    pub fn for_model(model: &str) -> Self {
+        ModelCapabilityRegistry::default().lookup(model)
+    }
+}
+
+/// Maps model name prefixes to their capabilities
+///
+/// Lookups use the longest matching prefix (case-insensitive) and fall back to
+/// [`ModelCapabilities::default`] for unknown models.
+#[derive(Debug, Clone)]
+pub struct ModelCapabilityRegistry {
+    entries: Vec<(String, ModelCapabilities)>,
+}
+
+impl ModelCapabilityRegistry {
+    /// Create an empty registry where every model gets the default capabilities
+    pub fn empty() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Register capabilities for all models whose name starts with `prefix`
+    pub fn register(&mut self, prefix: impl Into<String>, capabilities: ModelCapabilities) {
+        let prefix = prefix.into().to_lowercase();
+        self.entries.retain(|(existing, _)| existing != &prefix);
+        self.entries.push((prefix, capabilities));
+    }
+
+    /// Look up capabilities for a model name
+    pub fn lookup(&self, model: &str) -> ModelCapabilities {
+        let model = model.to_lowercase();
+        self.entries
+            .iter()
+            .filter(|(prefix, _)| model.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, capabilities)| *capabilities)
+            .unwrap_or_default()
+    }
+}
+
+impl Default for ModelCapabilityRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+
+        // Legacy completion models
+        for prefix in [
+            "gpt-3.5-turbo-instruct",
+            "davinci",
+            "babbage",
+            "text-davinci",
+            "text-curie",
+        ] {
+            registry.register(prefix, ModelCapabilities::text_only());
+        }
+
+        // Reasoning previews without tools or system messages
+        registry.register(
+            "o1-mini",
+            ModelCapabilities {
+                supports_tools: false,
+                supports_images: false,
+                supports_system_role: false,
+                supports_streaming: false,
+                max_tool_result_bytes: None,
+            },
+        );
+        registry.register(
+            "o1-preview",
+            ModelCapabilities {
+                supports_tools: false,
+                supports_images: false,
+                supports_system_role: false,
+                supports_streaming: false,
+                max_tool_result_bytes: None,
+            },
+        );
+
+        // Text-only chat models
+        registry.register(
+            "gpt-3.5-turbo",
+            ModelCapabilities {
+                supports_images: false,
+                ..Default::default()
+            },
+        );
+        registry.register(
+            "deepseek",
+            ModelCapabilities {
+                supports_images: false,
+                ..Default::default()
+            },
+        );
+
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unknown_model_gets_defaults() {
+        let caps = ModelCapabilities::for_model("claude-sonnet-4-20250514");
+        assert_eq!(caps, ModelCapabilities::default());
+    }
+
+    #[test]
+    fn test_longest_prefix_wins() {
+        let instruct = ModelCapabilities::for_model("gpt-3.5-turbo-instruct-0914");
+        assert!(!instruct.supports_tools);
+
+        let chat = ModelCapabilities::for_model("GPT-3.5-Turbo-0125");
+        assert!(chat.supports_tools);
+        assert!(!chat.supports_images);
+    }
+
+    #[test]
+    fn test_register_overrides_existing_prefix() {
+        let mut registry = ModelCapabilityRegistry::empty();
+        registry.register("local-", ModelCapabilities::text_only());
+        assert!(!registry.lookup("local-llama").supports_tools);
+
+        registry.register("local-", ModelCapabilities::default());
+        assert!(registry.lookup("local-llama").supports_tools);
+    }
+}
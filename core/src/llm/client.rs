@@ -63,6 +63,43 @@ pub struct LlmResponse {
     pub metadata: Option<HashMap<String, serde_json::Value>>,
 }
 
+/// Metadata key for the model that served a response, as reported by the provider
+pub const SERVED_MODEL_METADATA_KEY: &str = "served_model";
+
+/// Metadata key for the provider's id of the response body, e.g. `chatcmpl-...`
+pub const RESPONSE_ID_METADATA_KEY: &str = "response_id";
+
+/// Metadata key for the request id header of the HTTP response
+pub const REQUEST_ID_METADATA_KEY: &str = "request_id";
+
+/// Metadata key for the trace id header some gateways add to responses
+pub const TRACE_ID_METADATA_KEY: &str = "trace_id";
+
+/// Metadata key for the URL the request was sent to
+pub const ENDPOINT_METADATA_KEY: &str = "endpoint";
+
+impl LlmResponse {
+    /// String value stored under `key` in the metadata
+    pub fn metadata_str(&self, key: &str) -> Option<&str> {
+        self.metadata.as_ref()?.get(key)?.as_str()
+    }
+
+    /// Model that actually served the response, if the client reported it
+    pub fn served_model(&self) -> Option<&str> {
+        self.metadata_str(SERVED_MODEL_METADATA_KEY)
+    }
+
+    /// Request id the provider or gateway assigned, if the client reported it
+    pub fn request_id(&self) -> Option<&str> {
+        self.metadata_str(REQUEST_ID_METADATA_KEY)
+    }
+
+    /// Endpoint the response came from, if the client reported it
+    pub fn endpoint(&self) -> Option<&str> {
+        self.metadata_str(ENDPOINT_METADATA_KEY)
+    }
+}
+
 /// Streaming chunk from an LLM
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LlmStreamChunk {
@@ -108,6 +145,9 @@ pub enum FinishReason {
     /// Content was filtered
     ContentFilter,
 
+    /// The model declined to respond
+    Refusal,
+
     /// Other reason
     Other(String),
 }
@@ -139,7 +179,8 @@ pub struct FunctionDefinition {
 /// Options for chat completion
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatOptions {
-    /// Maximum tokens to generate
+    /// Maximum tokens to generate; `None` leaves the limit to the client,
+    /// which uses the model's known output limit or the provider default
     pub max_tokens: Option<u32>,
 
     /// Temperature for generation
@@ -157,13 +198,17 @@ pub struct ChatOptions {
     /// Whether to stream the response
     pub stream: Option<bool>,
 
-    /// Tool choice strategy
+    /// Tool choice strategy, sent only when tools are provided
     pub tool_choice: Option<ToolChoice>,
+
+    /// Cancellation signal; clients abort the in-flight HTTP request when it fires
+    #[serde(skip)]
+    pub cancellation: Option<crate::agent::AbortRegistration>,
 }
 
 /// Tool choice strategy
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(untagged)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ToolChoice {
     /// Let the model decide
     Auto,
@@ -171,20 +216,48 @@ pub enum ToolChoice {
     /// Never use tools
     None,
 
-    /// Force use of a specific tool
-    Required { name: String },
+    /// Force use of at least one tool
+    Required,
+
+    /// Force use of the named tool
+    Specific(String),
 }
 
 impl Default for ChatOptions {
     fn default() -> Self {
         Self {
-            max_tokens: Some(8192),
+            max_tokens: None,
             temperature: Some(0.7),
             top_p: Some(1.0),
             top_k: None,
             stop: None,
             stream: Some(false),
             tool_choice: Some(ToolChoice::Auto),
+            cancellation: None,
+        }
+    }
+}
+
+/// Run a request future, aborting it with `LlmError::Cancelled` when the
+/// cancellation signal fires
+///
+/// Dropping the future drops the underlying HTTP request, so the connection is
+/// released right away instead of when the runtime gets around to it.
+pub async fn with_cancellation<T, F>(
+    cancellation: Option<crate::agent::AbortRegistration>,
+    request: F,
+) -> Result<T>
+where
+    F: std::future::Future<Output = Result<T>>,
+{
+    match cancellation {
+        Some(mut registration) => {
+            tokio::select! {
+                biased;
+                _ = registration.cancelled() => Err(LlmError::Cancelled.into()),
+                result = request => result,
+            }
         }
+        None => request.await,
     }
 }
@@ -0,0 +1,417 @@
+//! HTTP client construction for LLM providers
+
+use crate::config::{HttpClientConfig, ResolvedLlmConfig};
+use crate::error::{LlmError, Result};
+use crate::llm::client::{
+    ENDPOINT_METADATA_KEY, REQUEST_ID_METADATA_KEY, RESPONSE_ID_METADATA_KEY,
+    SERVED_MODEL_METADATA_KEY, TRACE_ID_METADATA_KEY,
+};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The subset of `reqwest::ClientBuilder` tuned by [`HttpClientConfig`]
+pub trait HttpClientBuilder: Sized {
+    /// Limit idle connections kept per host
+    fn pool_max_idle_per_host(self, max: usize) -> Self;
+    /// Set how long idle pooled connections are kept
+    fn pool_idle_timeout(self, timeout: Duration) -> Self;
+    /// Set the TCP keep-alive interval
+    fn tcp_keepalive(self, interval: Duration) -> Self;
+}
+
+impl HttpClientBuilder for reqwest::ClientBuilder {
+    fn pool_max_idle_per_host(self, max: usize) -> Self {
+        reqwest::ClientBuilder::pool_max_idle_per_host(self, max)
+    }
+
+    fn pool_idle_timeout(self, timeout: Duration) -> Self {
+        reqwest::ClientBuilder::pool_idle_timeout(self, timeout)
+    }
+
+    fn tcp_keepalive(self, interval: Duration) -> Self {
+        reqwest::ClientBuilder::tcp_keepalive(self, interval)
+    }
+}
+
+/// Apply the configured pool and keep-alive settings to a builder
+pub fn apply_http_config<B: HttpClientBuilder>(config: &HttpClientConfig, mut builder: B) -> B {
+    if let Some(max) = config.pool_max_idle_per_host {
+        builder = builder.pool_max_idle_per_host(max);
+    }
+    if let Some(secs) = config.pool_idle_timeout_secs {
+        builder = builder.pool_idle_timeout(Duration::from_secs(secs));
+    }
+    if let Some(secs) = config.tcp_keepalive_secs {
+        builder = builder.tcp_keepalive(Duration::from_secs(secs));
+    }
+    builder
+}
+
+/// Build a `reqwest::Client` with the configured pool and keep-alive settings
+pub fn build_http_client(config: &HttpClientConfig) -> Result<reqwest::Client> {
+    apply_http_config(config, reqwest::Client::builder())
+        .build()
+        .map_err(|e| {
+            LlmError::InvalidRequest {
+                message: format!("Failed to build HTTP client: {}", e),
+            }
+            .into()
+        })
+}
+
+/// Wraps the HTTP requests a provider client sends
+///
+/// Implementations can adjust the request (auth headers, signing), observe the
+/// response, or both, and must pass the request on with [`Next::run`].
+#[async_trait]
+pub trait HttpMiddleware: Send + Sync {
+    /// Handle `request`, calling `next` to send it on
+    async fn handle(&self, request: reqwest::Request, next: Next<'_>) -> Result<reqwest::Response>;
+}
+
+/// The rest of the middleware chain, ending at the HTTP client
+pub struct Next<'a> {
+    client: &'a reqwest::Client,
+    middleware: &'a [Arc<dyn HttpMiddleware>],
+}
+
+impl Next<'_> {
+    /// Pass `request` to the next middleware, or send it
+    pub async fn run(self, request: reqwest::Request) -> Result<reqwest::Response> {
+        match self.middleware.split_first() {
+            Some((first, rest)) => {
+                let next = Next {
+                    client: self.client,
+                    middleware: rest,
+                };
+                first.handle(request, next).await
+            }
+            None => self.client.execute(request).await.map_err(|e| {
+                LlmError::Network {
+                    message: e.to_string(),
+                }
+                .into()
+            }),
+        }
+    }
+}
+
+/// Middleware applied to provider requests, outermost first
+#[derive(Clone, Default)]
+pub struct HttpMiddlewareStack(Vec<Arc<dyn HttpMiddleware>>);
+
+impl HttpMiddlewareStack {
+    /// Add a middleware inside the ones already added
+    pub fn push(&mut self, middleware: Arc<dyn HttpMiddleware>) {
+        self.0.push(middleware);
+    }
+
+    /// Whether no middleware is configured
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl std::fmt::Debug for HttpMiddlewareStack {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HttpMiddlewareStack({} layers)", self.0.len())
+    }
+}
+
+/// A `reqwest::Client` that sends requests through a middleware stack
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    client: reqwest::Client,
+    middleware: HttpMiddlewareStack,
+}
+
+impl HttpClient {
+    /// Build a client from the pool settings and middleware in `config`
+    pub fn from_config(config: &ResolvedLlmConfig) -> Result<Self> {
+        Ok(Self {
+            client: build_http_client(&config.http)?,
+            middleware: config.middleware.clone(),
+        })
+    }
+
+    /// Start a POST request; send it with [`execute`](Self::execute)
+    pub fn post(&self, url: &str) -> reqwest::RequestBuilder {
+        self.client.post(url)
+    }
+
+    /// Whether any middleware wraps the requests
+    pub fn has_middleware(&self) -> bool {
+        !self.middleware.is_empty()
+    }
+
+    /// Send `request` through the middleware
+    pub async fn execute(&self, request: reqwest::Request) -> Result<reqwest::Response> {
+        Next {
+            client: &self.client,
+            middleware: &self.middleware.0,
+        }
+        .run(request)
+        .await
+    }
+}
+
+/// Placeholder written in place of secrets in logged bodies and headers
+const REDACTED: &str = "***";
+
+/// Logs raw request and response bodies at `trace` level when enabled
+///
+/// The API key and the values of credential headers are masked wherever
+/// they appear.
+#[derive(Debug, Clone)]
+pub(crate) struct RawBodyLogger {
+    enabled: bool,
+    secrets: Vec<String>,
+}
+
+impl RawBodyLogger {
+    pub(crate) fn new(config: &ResolvedLlmConfig) -> Self {
+        let mut secrets = vec![config.api_key.clone()];
+        secrets.extend(
+            config
+                .headers
+                .iter()
+                .filter(|(name, _)| Self::is_credential_header(name))
+                .map(|(_, value)| value.clone()),
+        );
+        secrets.retain(|secret| !secret.is_empty());
+        Self {
+            enabled: config.log_raw_bodies,
+            secrets,
+        }
+    }
+
+    fn is_credential_header(name: &str) -> bool {
+        let name = name.to_ascii_lowercase();
+        name == "authorization"
+            || name.contains("api-key")
+            || name.contains("token")
+            || name.contains("secret")
+    }
+
+    fn redact(&self, text: &str) -> String {
+        self.secrets.iter().fold(text.to_string(), |text, secret| {
+            text.replace(secret, REDACTED)
+        })
+    }
+
+    /// Log an outgoing request
+    pub(crate) fn request(
+        &self,
+        provider: &str,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: &impl serde::Serialize,
+    ) {
+        if !self.enabled {
+            return;
+        }
+        let body = serde_json::to_string(body).unwrap_or_default();
+        let headers: Vec<String> = headers
+            .iter()
+            .map(|(name, value)| {
+                if Self::is_credential_header(name) {
+                    format!("{}: {}", name, REDACTED)
+                } else {
+                    format!("{}: {}", name, self.redact(value))
+                }
+            })
+            .collect();
+        tracing::trace!(
+            "{} request to {}\nheaders: {}\nbody: {}",
+            provider,
+            url,
+            headers.join(", "),
+            self.redact(&body)
+        );
+    }
+
+    /// Log a response body
+    pub(crate) fn response(&self, provider: &str, status: u16, body: &str) {
+        if !self.enabled {
+            return;
+        }
+        tracing::trace!(
+            "{} response ({})\nbody: {}",
+            provider,
+            status,
+            self.redact(body)
+        );
+    }
+}
+
+/// Response headers carrying a request id, checked in order
+const REQUEST_ID_HEADERS: &[&str] = &["request-id", "x-request-id"];
+
+/// Response headers carrying a trace id, checked in order
+const TRACE_ID_HEADERS: &[&str] = &["x-trace-id", "traceparent"];
+
+/// `LlmResponse` metadata identifying which model and endpoint served it
+///
+/// `headers` are the HTTP response headers, when the client can see them.
+/// Empty values are left out.
+pub(crate) fn response_origin_metadata(
+    served_model: &str,
+    response_id: &str,
+    endpoint: &str,
+    headers: Option<&reqwest::header::HeaderMap>,
+) -> HashMap<String, serde_json::Value> {
+    let header = |names: &[&str]| header_value(headers?, names);
+
+    [
+        (SERVED_MODEL_METADATA_KEY, Some(served_model.to_string())),
+        (RESPONSE_ID_METADATA_KEY, Some(response_id.to_string())),
+        (ENDPOINT_METADATA_KEY, Some(endpoint.to_string())),
+        (REQUEST_ID_METADATA_KEY, header(REQUEST_ID_HEADERS)),
+        (TRACE_ID_METADATA_KEY, header(TRACE_ID_HEADERS)),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| {
+        value
+            .filter(|value| !value.is_empty())
+            .map(|value| (key.to_string(), serde_json::Value::String(value)))
+    })
+    .collect()
+}
+
+/// The provider's request id from response headers, if it sent one
+pub(crate) fn request_id(headers: &reqwest::header::HeaderMap) -> Option<String> {
+    header_value(headers, REQUEST_ID_HEADERS).filter(|id| !id.is_empty())
+}
+
+/// The first of `names` present in `headers`
+fn header_value(headers: &reqwest::header::HeaderMap, names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .find_map(|name| headers.get(*name)?.to_str().ok())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records which settings were applied
+    #[derive(Default)]
+    struct SpyBuilder {
+        pool_max_idle_per_host: Option<usize>,
+        pool_idle_timeout: Option<Duration>,
+        tcp_keepalive: Option<Duration>,
+    }
+
+    impl HttpClientBuilder for SpyBuilder {
+        fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+            self.pool_max_idle_per_host = Some(max);
+            self
+        }
+
+        fn pool_idle_timeout(mut self, timeout: Duration) -> Self {
+            self.pool_idle_timeout = Some(timeout);
+            self
+        }
+
+        fn tcp_keepalive(mut self, interval: Duration) -> Self {
+            self.tcp_keepalive = Some(interval);
+            self
+        }
+    }
+
+    fn custom_config() -> HttpClientConfig {
+        HttpClientConfig {
+            pool_max_idle_per_host: Some(32),
+            pool_idle_timeout_secs: Some(90),
+            tcp_keepalive_secs: Some(30),
+        }
+    }
+
+    #[test]
+    fn test_custom_config_applied_to_builder() {
+        let spy = apply_http_config(&custom_config(), SpyBuilder::default());
+
+        assert_eq!(spy.pool_max_idle_per_host, Some(32));
+        assert_eq!(spy.pool_idle_timeout, Some(Duration::from_secs(90)));
+        assert_eq!(spy.tcp_keepalive, Some(Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn test_default_config_leaves_builder_untouched() {
+        let spy = apply_http_config(&HttpClientConfig::default(), SpyBuilder::default());
+
+        assert!(spy.pool_max_idle_per_host.is_none());
+        assert!(spy.pool_idle_timeout.is_none());
+        assert!(spy.tcp_keepalive.is_none());
+    }
+
+    #[test]
+    fn test_build_http_client_with_custom_config() {
+        assert!(build_http_client(&custom_config()).is_ok());
+    }
+
+    /// Collects everything written by a tracing subscriber
+    #[derive(Clone, Default)]
+    struct CapturedLogs(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn captured_request_log(config: &ResolvedLlmConfig) -> String {
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+
+        let logger = RawBodyLogger::new(config);
+        tracing::subscriber::with_default(subscriber, || {
+            logger.request(
+                "openai",
+                "https://api.example.com/v1/chat/completions",
+                &[
+                    ("authorization", "Bearer sk-secret-123"),
+                    ("content-type", "application/json"),
+                ],
+                &serde_json::json!({
+                    "model": "mistral-large",
+                    "messages": [{"role": "user", "content": "sk-secret-123"}],
+                }),
+            );
+        });
+
+        let bytes = logs.0.lock().unwrap().clone();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn test_raw_request_body_logged_with_api_key_masked() {
+        let config = ResolvedLlmConfig::new(
+            crate::config::Protocol::OpenAICompat,
+            "https://api.example.com".to_string(),
+            "sk-secret-123".to_string(),
+            "mistral-large".to_string(),
+        );
+
+        assert!(captured_request_log(&config).is_empty());
+
+        let log = captured_request_log(&config.with_log_raw_bodies(true));
+        assert!(log.contains(r#""model":"mistral-large""#));
+        assert!(log.contains("authorization: ***"));
+        assert!(log.contains(r#""content":"***""#));
+        assert!(!log.contains("sk-secret-123"));
+    }
+}
@@ -1,8 +1,15 @@
 //! LLM message structures
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+/// Metadata key marking a message as pinned (never compressed or summarized)
+pub const PINNED_METADATA_KEY: &str = "pinned";
+
+/// Metadata key holding the RFC 3339 time a message was added to a conversation
+pub const CREATED_AT_METADATA_KEY: &str = "created_at";
+
 /// Represents a message in an LLM conversation
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LlmMessage {
@@ -117,6 +124,53 @@ impl LlmMessage {
         }
     }
 
+    /// Create a user message that is never compressed or summarized
+    pub fn pinned_user<S: Into<String>>(content: S) -> Self {
+        Self::user(content).pinned()
+    }
+
+    /// Create a system message that is never compressed or summarized
+    pub fn pinned_system<S: Into<String>>(content: S) -> Self {
+        Self::system(content).pinned()
+    }
+
+    /// Mark this message as pinned so compression keeps it verbatim
+    pub fn pinned(mut self) -> Self {
+        self.metadata.get_or_insert_with(HashMap::new).insert(
+            PINNED_METADATA_KEY.to_string(),
+            serde_json::Value::Bool(true),
+        );
+        self
+    }
+
+    /// Check whether the message carries `"pinned": true` metadata
+    pub fn is_pinned(&self) -> bool {
+        self.metadata
+            .as_ref()
+            .and_then(|metadata| metadata.get(PINNED_METADATA_KEY))
+            .and_then(|value| value.as_bool())
+            .unwrap_or(false)
+    }
+
+    /// Record when the message was added to a conversation
+    pub fn with_created_at(mut self, created_at: DateTime<Utc>) -> Self {
+        self.metadata.get_or_insert_with(HashMap::new).insert(
+            CREATED_AT_METADATA_KEY.to_string(),
+            serde_json::Value::String(created_at.to_rfc3339()),
+        );
+        self
+    }
+
+    /// When the message was added to a conversation, if recorded
+    pub fn created_at(&self) -> Option<DateTime<Utc>> {
+        self.metadata
+            .as_ref()
+            .and_then(|metadata| metadata.get(CREATED_AT_METADATA_KEY))
+            .and_then(|value| value.as_str())
+            .and_then(|value| DateTime::parse_from_rfc3339(value).ok())
+            .map(|time| time.with_timezone(&Utc))
+    }
+
     /// Get the text content of the message
     pub fn get_text(&self) -> Option<String> {
         match &self.content {
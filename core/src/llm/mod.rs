@@ -1,12 +1,33 @@
 //! LLM client abstractions and implementations
 
+pub mod backoff;
+pub mod capabilities;
 pub mod client;
+pub mod http;
 pub mod message;
 pub mod providers;
+pub mod rate_limit;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod text_tools;
+pub mod wire;
 
+pub use backoff::{retry_with_backoff, Backoff};
+pub use capabilities::{ModelCapabilities, ModelCapabilityRegistry};
 pub use client::{
     ChatOptions, FinishReason, FunctionDefinition, LlmClient, LlmResponse, LlmStreamChunk,
-    ToolChoice, ToolDefinition, Usage,
+    ToolChoice, ToolDefinition, Usage, ENDPOINT_METADATA_KEY, REQUEST_ID_METADATA_KEY,
+    RESPONSE_ID_METADATA_KEY, SERVED_MODEL_METADATA_KEY, TRACE_ID_METADATA_KEY,
+};
+pub use message::{
+    ContentBlock, LlmMessage, MessageContent, MessageRole, CREATED_AT_METADATA_KEY,
+    PINNED_METADATA_KEY,
 };
-pub use message::{ContentBlock, LlmMessage, MessageContent, MessageRole};
 pub use providers::*;
+pub use rate_limit::{RateLimitConfig, RateLimitedClient, RateLimiter};
+#[cfg(any(test, feature = "testing"))]
+pub use testing::{RecordedRequest, ScriptedLlmClient};
+pub use wire::{
+    from_anthropic_conversation, from_openai_conversation, merge_consecutive_roles,
+    to_anthropic_conversation, to_openai_conversation,
+};
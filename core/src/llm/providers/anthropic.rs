@@ -1,23 +1,30 @@
 //! Anthropic Claude client implementation
 
+use crate::agent::tokens::TokenCalculator;
 use crate::config::ResolvedLlmConfig;
+use crate::config::RetryConfig;
 use crate::error::{LlmError, Result};
+use crate::llm::backoff::{is_retryable, retry_parse_failure, retry_with_backoff};
+use crate::llm::client::with_cancellation;
+use crate::llm::http::{HttpClient, RawBodyLogger};
 use crate::llm::{
     ChatOptions, FinishReason, LlmClient, LlmMessage, LlmResponse, LlmStreamChunk, MessageRole,
-    ToolDefinition, Usage,
+    ToolChoice, ToolDefinition, Usage,
 };
 use async_trait::async_trait;
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 
 /// Anthropic Claude client
 pub struct AnthropicClient {
-    client: Client,
+    client: HttpClient,
     api_key: String,
     base_url: String,
     model: String,
     #[allow(dead_code)]
     headers: std::collections::HashMap<String, String>,
+    retry: RetryConfig,
+    raw_logger: RawBodyLogger,
+    merge_consecutive_roles: bool,
 }
 
 impl AnthropicClient {
@@ -29,7 +36,7 @@ impl AnthropicClient {
             }));
         }
 
-        let client = Client::new();
+        let client = HttpClient::from_config(config)?;
 
         Ok(Self {
             client,
@@ -37,49 +44,96 @@ impl AnthropicClient {
             base_url: config.base_url.clone(),
             model: config.model.clone(),
             headers: config.headers.clone(),
+            retry: config.retry.clone(),
+            raw_logger: RawBodyLogger::new(config),
+            merge_consecutive_roles: config.merge_consecutive_roles,
         })
     }
-}
 
-#[async_trait]
-impl LlmClient for AnthropicClient {
-    async fn chat_completion(
+    /// Send one request to the messages endpoint and parse the reply
+    async fn send_message(
         &self,
-        messages: Vec<LlmMessage>,
-        tools: Option<Vec<ToolDefinition>>,
-        options: Option<ChatOptions>,
-    ) -> Result<LlmResponse> {
-        let request = self.build_request(messages, tools, options)?;
-
-        let response = self
+        url: &str,
+        request: &AnthropicRequest,
+    ) -> Result<(AnthropicResponse, reqwest::header::HeaderMap)> {
+        self.raw_logger.request(
+            "anthropic",
+            url,
+            &[
+                ("x-api-key", &self.api_key),
+                ("anthropic-version", "2023-06-01"),
+                ("content-type", "application/json"),
+            ],
+            request,
+        );
+        let http_request = self
             .client
-            .post(format!("{}/v1/messages", self.base_url))
+            .post(url)
             .header("x-api-key", &self.api_key)
             .header("anthropic-version", "2023-06-01")
             .header("content-type", "application/json")
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| LlmError::Network {
-                message: e.to_string(),
+            .json(request)
+            .build()
+            .map_err(|e| LlmError::InvalidRequest {
+                message: format!("Failed to build request: {}", e),
             })?;
+        let response = self.client.execute(http_request).await?;
 
-        if !response.status().is_success() {
-            let status = response.status().as_u16();
+        let status = response.status();
+        let headers = response.headers().clone();
+        if !status.is_success() {
             let error_text = response.text().await.unwrap_or_default();
+            self.raw_logger
+                .response("anthropic", status.as_u16(), &error_text);
             return Err((LlmError::ApiError {
-                status,
+                status: status.as_u16(),
                 message: error_text,
+                request_id: crate::llm::http::request_id(&headers),
             })
             .into());
         }
 
-        let anthropic_response: AnthropicResponse =
-            response.json().await.map_err(|e| LlmError::Network {
-                message: format!("Failed to parse response: {}", e),
+        let body = response.text().await.map_err(|e| LlmError::Network {
+            message: format!("Failed to read response: {}", e),
+        })?;
+        self.raw_logger
+            .response("anthropic", status.as_u16(), &body);
+        let parsed: AnthropicResponse =
+            serde_json::from_str(&body).map_err(|e| LlmError::ResponseParse {
+                message: e.to_string(),
             })?;
+        Ok((parsed, headers))
+    }
+}
 
-        Ok(self.convert_response(anthropic_response))
+#[async_trait]
+impl LlmClient for AnthropicClient {
+    async fn chat_completion(
+        &self,
+        messages: Vec<LlmMessage>,
+        tools: Option<Vec<ToolDefinition>>,
+        options: Option<ChatOptions>,
+    ) -> Result<LlmResponse> {
+        let cancellation = options.as_ref().and_then(|o| o.cancellation.clone());
+        let request = self.build_request(messages, tools, options)?;
+
+        // Race the whole HTTP exchange, retries included, against cancellation so
+        // the request is dropped (and its connection closed) as soon as the task is cancelled
+        let url = format!("{}/v1/messages", self.base_url);
+        let exchange = retry_with_backoff(&self.retry, is_retryable, || {
+            retry_parse_failure(&self.retry, || self.send_message(&url, &request))
+        });
+        let (anthropic_response, headers) = with_cancellation(cancellation, exchange).await?;
+
+        let metadata = crate::llm::http::response_origin_metadata(
+            &anthropic_response.model,
+            &anthropic_response.id,
+            &url,
+            Some(&headers),
+        );
+        let mut response = self.convert_response(anthropic_response);
+        response.metadata = Some(metadata);
+        Ok(response)
     }
 
     fn model_name(&self) -> &str {
@@ -131,8 +185,13 @@ impl AnthropicClient {
                 _ => conversation_messages.push(message),
             }
         }
+        if self.merge_consecutive_roles {
+            conversation_messages = crate::llm::merge_consecutive_roles(conversation_messages);
+        }
 
-        let max_tokens = options.max_tokens.unwrap_or(4096);
+        let max_tokens = options
+            .max_tokens
+            .unwrap_or_else(|| TokenCalculator::max_output_tokens_for_model(&self.model));
 
         let temperature = options.temperature.unwrap_or(0.5);
 
@@ -142,11 +201,25 @@ impl AnthropicClient {
             temperature,
             system: system_message,
             messages: conversation_messages,
+            tool_choice: tools
+                .as_ref()
+                .and(options.tool_choice.as_ref())
+                .map(Self::convert_tool_choice),
             tools: tools.map(|t| t.into_iter().map(|tool| tool.function).collect()),
             stop_sequences: options.stop,
         })
     }
 
+    /// Map a tool choice to Anthropic's `tool_choice` object
+    fn convert_tool_choice(choice: &ToolChoice) -> serde_json::Value {
+        match choice {
+            ToolChoice::Auto => serde_json::json!({"type": "auto"}),
+            ToolChoice::None => serde_json::json!({"type": "none"}),
+            ToolChoice::Required => serde_json::json!({"type": "any"}),
+            ToolChoice::Specific(name) => serde_json::json!({"type": "tool", "name": name}),
+        }
+    }
+
     fn convert_response(&self, response: AnthropicResponse) -> LlmResponse {
         let message = LlmMessage::assistant(
             response
@@ -166,6 +239,7 @@ impl AnthropicClient {
             "end_turn" => Some(FinishReason::Stop),
             "max_tokens" => Some(FinishReason::Length),
             "tool_use" => Some(FinishReason::ToolCalls),
+            "refusal" => Some(FinishReason::Refusal),
             _ => Some(FinishReason::Other(response.stop_reason)),
         };
 
@@ -190,12 +264,13 @@ struct AnthropicRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     tools: Option<Vec<crate::llm::FunctionDefinition>>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     stop_sequences: Option<Vec<String>>,
 }
 
 #[derive(Debug, Deserialize)]
 struct AnthropicResponse {
-    #[allow(dead_code)]
     id: String,
     model: String,
     #[serde(rename = "type")]
@@ -223,3 +298,426 @@ struct AnthropicUsage {
     input_tokens: u32,
     output_tokens: u32,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Protocol;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_client_builds_with_custom_http_config() {
+        let config = ResolvedLlmConfig::new(
+            Protocol::Anthropic,
+            "https://api.anthropic.com".to_string(),
+            "test-key".to_string(),
+            "claude-test".to_string(),
+        )
+        .with_http_config(crate::config::HttpClientConfig {
+            pool_max_idle_per_host: Some(16),
+            pool_idle_timeout_secs: Some(120),
+            tcp_keepalive_secs: Some(15),
+        });
+
+        assert!(AnthropicClient::new(&config).is_ok());
+    }
+
+    fn tool_choice_json(tool_choice: Option<ToolChoice>, with_tools: bool) -> serde_json::Value {
+        let config = ResolvedLlmConfig::new(
+            Protocol::Anthropic,
+            "https://api.anthropic.com".to_string(),
+            "test-key".to_string(),
+            "claude-test".to_string(),
+        );
+        let client = AnthropicClient::new(&config).unwrap();
+        let tools = with_tools.then(|| {
+            vec![ToolDefinition {
+                tool_type: "function".to_string(),
+                function: crate::llm::FunctionDefinition {
+                    name: "bash".to_string(),
+                    description: "Run a command".to_string(),
+                    parameters: serde_json::json!({"type": "object"}),
+                },
+            }]
+        });
+        let request = client
+            .build_request(
+                vec![LlmMessage::user("Hello")],
+                tools,
+                Some(ChatOptions {
+                    tool_choice,
+                    ..Default::default()
+                }),
+            )
+            .unwrap();
+        serde_json::to_value(request).unwrap()
+    }
+
+    #[test]
+    fn test_adjacent_same_role_messages_merged() {
+        let config = ResolvedLlmConfig::new(
+            Protocol::Anthropic,
+            "https://api.anthropic.com".to_string(),
+            "test-key".to_string(),
+            "claude-test".to_string(),
+        );
+        let messages = vec![
+            LlmMessage::system("Be brief."),
+            LlmMessage::user("Fix the build."),
+            LlmMessage::user("It fails on CI too."),
+            LlmMessage::assistant("Looking."),
+            LlmMessage {
+                role: MessageRole::Assistant,
+                content: crate::llm::MessageContent::MultiModal(vec![
+                    crate::llm::ContentBlock::ToolUse {
+                        id: "call_1".to_string(),
+                        name: "bash".to_string(),
+                        input: serde_json::json!({"command": "cargo build"}),
+                    },
+                ]),
+                metadata: None,
+            },
+        ];
+
+        let client = AnthropicClient::new(&config).unwrap();
+        let request = client.build_request(messages.clone(), None, None).unwrap();
+        let request = serde_json::to_value(request).unwrap();
+        let sent = request["messages"].as_array().unwrap();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0]["role"], "user");
+        assert_eq!(sent[0]["content"], "Fix the build.\n\nIt fails on CI too.");
+        assert_eq!(sent[1]["role"], "assistant");
+        assert_eq!(sent[1]["content"][0]["text"], "Looking.");
+        assert_eq!(sent[1]["content"][1]["id"], "call_1");
+
+        let client = AnthropicClient::new(&config.with_merge_consecutive_roles(false)).unwrap();
+        let request = client.build_request(messages, None, None).unwrap();
+        assert_eq!(request.messages.len(), 4);
+    }
+
+    #[test]
+    fn test_max_tokens_defaults_to_model_output_limit() {
+        let client = |model: &str| {
+            AnthropicClient::new(&ResolvedLlmConfig::new(
+                Protocol::Anthropic,
+                "https://api.anthropic.com".to_string(),
+                "test-key".to_string(),
+                model.to_string(),
+            ))
+            .unwrap()
+        };
+        let messages = vec![LlmMessage::user("Hello")];
+
+        let sonnet = client("claude-3-5-sonnet-20241022");
+        let request = sonnet.build_request(messages.clone(), None, None).unwrap();
+        assert_eq!(request.max_tokens, 8_192);
+        let request = sonnet
+            .build_request(
+                messages.clone(),
+                None,
+                Some(ChatOptions {
+                    max_tokens: Some(2_000),
+                    ..Default::default()
+                }),
+            )
+            .unwrap();
+        assert_eq!(request.max_tokens, 2_000);
+
+        let request = client("claude-test")
+            .build_request(messages, None, None)
+            .unwrap();
+        assert_eq!(
+            request.max_tokens,
+            TokenCalculator::DEFAULT_MAX_OUTPUT_TOKENS
+        );
+    }
+
+    #[test]
+    fn test_tool_choice_serialized_for_each_variant() {
+        let cases = [
+            (ToolChoice::Auto, serde_json::json!({"type": "auto"})),
+            (ToolChoice::None, serde_json::json!({"type": "none"})),
+            (ToolChoice::Required, serde_json::json!({"type": "any"})),
+            (
+                ToolChoice::Specific("bash".to_string()),
+                serde_json::json!({"type": "tool", "name": "bash"}),
+            ),
+        ];
+        for (choice, expected) in cases {
+            assert_eq!(
+                tool_choice_json(Some(choice), true)["tool_choice"],
+                expected
+            );
+        }
+
+        // Anthropic rejects tool_choice without tools
+        let request = tool_choice_json(Some(ToolChoice::Required), false);
+        assert!(request.get("tool_choice").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_aborts_in_flight_request() {
+        // Server that accepts connections but never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                held.push(stream);
+            }
+        });
+
+        let config = ResolvedLlmConfig::new(
+            Protocol::Anthropic,
+            format!("http://{}", addr),
+            "test-key".to_string(),
+            "claude-test".to_string(),
+        );
+        let client = AnthropicClient::new(&config).unwrap();
+
+        let (controller, registration) = crate::agent::AbortController::new();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            controller.cancel();
+        });
+
+        let options = ChatOptions {
+            cancellation: Some(registration),
+            ..Default::default()
+        };
+        let result = tokio::time::timeout(
+            Duration::from_secs(2),
+            client.chat_completion(vec![LlmMessage::user("hello")], None, Some(options)),
+        )
+        .await
+        .expect("cancellation should return promptly");
+
+        assert!(matches!(
+            result,
+            Err(crate::error::Error::Llm(LlmError::Cancelled))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_response_metadata_identifies_served_model_and_request() {
+        let body = serde_json::json!({
+            "id": "msg_01XFDUDYJgAACzvnptvVoYEL",
+            "type": "message",
+            "role": "assistant",
+            "model": "claude-sonnet-4-20250514",
+            "content": [{"type": "text", "text": "Hi"}],
+            "stop_reason": "end_turn",
+            "stop_sequence": null,
+            "usage": {"input_tokens": 3, "output_tokens": 1}
+        });
+        let base_url = crate::llm::testing::serve_http_once(
+            &[("request-id", "req_018EeWyXxfu5pfWkrYcMdjWG")],
+            &body.to_string(),
+        )
+        .await;
+        let config = ResolvedLlmConfig::new(
+            Protocol::Anthropic,
+            base_url.clone(),
+            "test-key".to_string(),
+            "claude-sonnet-4".to_string(),
+        );
+        let client = AnthropicClient::new(&config).unwrap();
+
+        let response = client
+            .chat_completion(vec![LlmMessage::user("hello")], None, None)
+            .await
+            .unwrap();
+
+        assert_eq!(response.served_model(), Some("claude-sonnet-4-20250514"));
+        assert_eq!(response.request_id(), Some("req_018EeWyXxfu5pfWkrYcMdjWG"));
+        assert_eq!(
+            response.metadata_str(crate::llm::RESPONSE_ID_METADATA_KEY),
+            Some("msg_01XFDUDYJgAACzvnptvVoYEL")
+        );
+        assert_eq!(
+            response.endpoint(),
+            Some(format!("{}/v1/messages", base_url).as_str())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refusal_stop_reason_mapped() {
+        let body = serde_json::json!({
+            "id": "msg_01",
+            "type": "message",
+            "role": "assistant",
+            "model": "claude-sonnet-4-20250514",
+            "content": [],
+            "stop_reason": "refusal",
+            "stop_sequence": null,
+            "usage": {"input_tokens": 3, "output_tokens": 0}
+        });
+        let base_url = crate::llm::testing::serve_http_once(&[], &body.to_string()).await;
+        let config = ResolvedLlmConfig::new(
+            Protocol::Anthropic,
+            base_url,
+            "test-key".to_string(),
+            "claude-sonnet-4".to_string(),
+        );
+        let client = AnthropicClient::new(&config).unwrap();
+
+        let response = client
+            .chat_completion(vec![LlmMessage::user("hello")], None, None)
+            .await
+            .unwrap();
+
+        assert_eq!(response.finish_reason, Some(FinishReason::Refusal));
+    }
+
+    #[tokio::test]
+    async fn test_malformed_response_retried_once() {
+        let body = serde_json::json!({
+            "id": "msg_01",
+            "type": "message",
+            "role": "assistant",
+            "model": "claude-sonnet-4-20250514",
+            "content": [{"type": "text", "text": "hi"}],
+            "stop_reason": "end_turn",
+            "stop_sequence": null,
+            "usage": {"input_tokens": 3, "output_tokens": 1}
+        })
+        .to_string();
+        let truncated = r#"{"id": "msg_01", "type": "mess"#;
+
+        let base_url = crate::llm::testing::serve_http_sequence(&[truncated, &body]).await;
+        let config = ResolvedLlmConfig::new(
+            Protocol::Anthropic,
+            base_url,
+            "test-key".to_string(),
+            "claude-sonnet-4".to_string(),
+        );
+        let response = AnthropicClient::new(&config)
+            .unwrap()
+            .chat_completion(vec![LlmMessage::user("hello")], None, None)
+            .await
+            .unwrap();
+        assert_eq!(response.message.get_text().as_deref(), Some("hi"));
+
+        let base_url = crate::llm::testing::serve_http_sequence(&[truncated]).await;
+        let config = ResolvedLlmConfig::new(
+            Protocol::Anthropic,
+            base_url,
+            "test-key".to_string(),
+            "claude-sonnet-4".to_string(),
+        )
+        .with_retry_config(crate::config::RetryConfig {
+            retry_parse_failure: false,
+            ..crate::config::RetryConfig::default()
+        });
+        let error = AnthropicClient::new(&config)
+            .unwrap()
+            .chat_completion(vec![LlmMessage::user("hello")], None, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            crate::error::Error::Llm(LlmError::ResponseParse { .. })
+        ));
+    }
+
+    /// Middleware recording the URL and status of every request
+    struct RecordingMiddleware {
+        seen: Arc<std::sync::Mutex<Vec<(String, u16)>>>,
+    }
+
+    #[async_trait]
+    impl crate::llm::http::HttpMiddleware for RecordingMiddleware {
+        async fn handle(
+            &self,
+            request: reqwest::Request,
+            next: crate::llm::http::Next<'_>,
+        ) -> Result<reqwest::Response> {
+            let url = request.url().path().to_string();
+            let response = next.run(request).await?;
+            self.seen
+                .lock()
+                .unwrap()
+                .push((url, response.status().as_u16()));
+            Ok(response)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_middleware_observes_each_request() {
+        let body = serde_json::json!({
+            "id": "msg_01",
+            "type": "message",
+            "role": "assistant",
+            "model": "claude-sonnet-4-20250514",
+            "content": [{"type": "text", "text": "Hi"}],
+            "stop_reason": "end_turn",
+            "stop_sequence": null,
+            "usage": {"input_tokens": 3, "output_tokens": 1}
+        });
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        for status in ["200 OK", "400 Bad Request"] {
+            let base_url =
+                crate::llm::testing::serve_http_status_once(status, &[], &body.to_string()).await;
+            let config = ResolvedLlmConfig::new(
+                Protocol::Anthropic,
+                base_url,
+                "test-key".to_string(),
+                "claude-sonnet-4".to_string(),
+            )
+            .with_http_middleware(RecordingMiddleware { seen: seen.clone() });
+            let client = AnthropicClient::new(&config).unwrap();
+            let _ = client
+                .chat_completion(vec![LlmMessage::user("hello")], None, None)
+                .await;
+        }
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                ("/v1/messages".to_string(), 200),
+                ("/v1/messages".to_string(), 400)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_api_error_carries_request_id() {
+        let body = serde_json::json!({
+            "type": "error",
+            "error": {"type": "invalid_request_error", "message": "max_tokens: too large"}
+        });
+        let base_url = crate::llm::testing::serve_http_status_once(
+            "400 Bad Request",
+            &[("request-id", "req_011CSHoEeqs5C35K2UUqR7Fy")],
+            &body.to_string(),
+        )
+        .await;
+        let config = ResolvedLlmConfig::new(
+            Protocol::Anthropic,
+            base_url,
+            "test-key".to_string(),
+            "claude-sonnet-4".to_string(),
+        );
+        let client = AnthropicClient::new(&config).unwrap();
+
+        let error = client
+            .chat_completion(vec![LlmMessage::user("hello")], None, None)
+            .await
+            .unwrap_err();
+
+        match &error {
+            crate::error::Error::Llm(LlmError::ApiError {
+                status, request_id, ..
+            }) => {
+                assert_eq!(*status, 400);
+                assert_eq!(request_id.as_deref(), Some("req_011CSHoEeqs5C35K2UUqR7Fy"));
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert!(error
+            .to_string()
+            .ends_with("(request id: req_011CSHoEeqs5C35K2UUqR7Fy)"));
+    }
+}
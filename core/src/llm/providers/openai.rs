@@ -1,21 +1,27 @@
 //! OpenAI client implementation using async-openai library
 
 use crate::config::ResolvedLlmConfig;
+use crate::config::RetryConfig;
 use crate::error::{LlmError, Result};
+use crate::llm::backoff::{is_retryable, retry_parse_failure, retry_with_backoff};
+use crate::llm::client::with_cancellation;
+use crate::llm::http::{HttpClient, RawBodyLogger};
 use crate::llm::{
     ChatOptions, ContentBlock, FinishReason, LlmClient, LlmMessage, LlmResponse, LlmStreamChunk,
-    MessageContent, MessageRole, ToolDefinition, Usage,
+    MessageContent, MessageRole, ToolChoice, ToolDefinition, Usage,
 };
 use crate::tools::ToolCall;
 use async_openai::{
     config::OpenAIConfig,
+    error::{ApiError, OpenAIError, WrappedError},
     types::{
-        ChatCompletionMessageToolCall, ChatCompletionRequestAssistantMessage,
-        ChatCompletionRequestAssistantMessageContent, ChatCompletionRequestMessage,
-        ChatCompletionRequestSystemMessage, ChatCompletionRequestToolMessage,
-        ChatCompletionRequestToolMessageContent, ChatCompletionRequestUserMessage,
-        ChatCompletionTool, ChatCompletionToolType, CreateChatCompletionRequestArgs,
-        FunctionObject,
+        ChatCompletionMessageToolCall, ChatCompletionNamedToolChoice,
+        ChatCompletionRequestAssistantMessage, ChatCompletionRequestAssistantMessageContent,
+        ChatCompletionRequestMessage, ChatCompletionRequestSystemMessage,
+        ChatCompletionRequestToolMessage, ChatCompletionRequestToolMessageContent,
+        ChatCompletionRequestUserMessage, ChatCompletionTool, ChatCompletionToolChoiceOption,
+        ChatCompletionToolType, CreateChatCompletionRequest, CreateChatCompletionRequestArgs,
+        CreateChatCompletionResponse, FunctionName, FunctionObject,
     },
     Client,
 };
@@ -23,15 +29,26 @@ use async_trait::async_trait;
 use futures::StreamExt;
 use serde_json::Value;
 
+/// Path of the chat endpoint relative to the API base
+const CHAT_COMPLETIONS_PATH: &str = "/chat/completions";
+
 /// OpenAI client using async-openai library
 pub struct OpenAiClient {
     client: Client<OpenAIConfig>,
     model: String,
     // Store base URL to determine streaming compatibility at runtime
-    #[allow(dead_code)]
     base_url: String,
-    #[allow(dead_code)]
+    // Custom headers, sent on requests this client posts itself
     headers: std::collections::HashMap<String, String>,
+    // Send the token limit as `max_completion_tokens` instead of `max_tokens`
+    use_max_completion_tokens: bool,
+    retry: RetryConfig,
+    raw_logger: RawBodyLogger,
+    // Kept only to mask it in raw body logs
+    api_key: String,
+    merge_consecutive_roles: bool,
+    // Sends non-streamed requests, through any configured middleware
+    http: HttpClient,
 }
 
 impl OpenAiClient {
@@ -43,29 +60,140 @@ impl OpenAiClient {
             }));
         }
 
-        let mut openai_config = OpenAIConfig::new().with_api_key(&config.api_key);
-
-        // Set custom base URL if provided
         let base_url = &config.base_url;
-        if base_url != "https://api.openai.com" {
-            openai_config = openai_config.with_api_base(base_url);
-        }
+        let openai_config = OpenAIConfig::new()
+            .with_api_key(&config.api_key)
+            .with_api_base(Self::api_base(base_url));
 
-        let client = Client::with_config(openai_config);
+        let http_client = crate::llm::http::build_http_client(&config.http)?;
+        let client = Client::with_config(openai_config).with_http_client(http_client);
 
         Ok(Self {
             client,
             model: config.model.clone(),
             base_url: base_url.clone(),
             headers: config.headers.clone(),
+            use_max_completion_tokens: config
+                .max_tokens_field
+                .uses_max_completion_tokens(&config.model),
+            retry: config.retry.clone(),
+            raw_logger: RawBodyLogger::new(config),
+            api_key: config.api_key.clone(),
+            merge_consecutive_roles: config.merge_consecutive_roles,
+            http: HttpClient::from_config(config)?,
         })
     }
 
+    /// API base that async-openai appends `/chat/completions` to
+    ///
+    /// Gateways host the endpoint at different paths, so the configured base
+    /// URL may be the full endpoint, end in `/v1`, or be a bare host.
+    fn api_base(base_url: &str) -> String {
+        let base_url = base_url.trim_end_matches('/');
+        if let Some(api_base) = base_url.strip_suffix(CHAT_COMPLETIONS_PATH) {
+            api_base.to_string()
+        } else if base_url.ends_with("/v1") {
+            base_url.to_string()
+        } else {
+            format!("{}/v1", base_url)
+        }
+    }
+
+    /// Send `request` through the configured HTTP middleware
+    ///
+    /// async-openai neither lets callers wrap its requests nor exposes status
+    /// codes and response headers, so the request is posted here instead,
+    /// with the configured headers, and the response parsed the same way.
+    async fn send_request(
+        &self,
+        request: &CreateChatCompletionRequest,
+    ) -> Result<(CreateChatCompletionResponse, reqwest::header::HeaderMap)> {
+        let url = format!(
+            "{}{}",
+            Self::api_base(&self.base_url),
+            CHAT_COMPLETIONS_PATH
+        );
+        let mut builder = self.http.post(&url).bearer_auth(&self.api_key);
+        for (name, value) in &self.headers {
+            builder = builder.header(name, value);
+        }
+        let request = builder
+            .json(request)
+            .build()
+            .map_err(|e| LlmError::InvalidRequest {
+                message: format!("Failed to build request: {}", e),
+            })?;
+        let response = self.http.execute(request).await?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.text().await.map_err(|e| LlmError::Network {
+            message: e.to_string(),
+        })?;
+        self.raw_logger.response("openai", status.as_u16(), &body);
+        if !status.is_success() {
+            let request_id = crate::llm::http::request_id(&headers);
+            return Err(parse_error_body(status.as_u16(), &body, request_id).into());
+        }
+        let parsed = serde_json::from_str(&body).map_err(|e| LlmError::ResponseParse {
+            message: e.to_string(),
+        })?;
+        Ok((parsed, headers))
+    }
+
+    /// Log the outgoing request as async-openai will send it
+    fn log_raw_request(&self, request: &async_openai::types::CreateChatCompletionRequest) {
+        let url = format!(
+            "{}{}",
+            Self::api_base(&self.base_url),
+            CHAT_COMPLETIONS_PATH
+        );
+        let authorization = format!("Bearer {}", self.api_key);
+        self.raw_logger.request(
+            "openai",
+            &url,
+            &[("authorization", authorization.as_str())],
+            request,
+        );
+    }
+
+    /// Map a tool choice to OpenAI's `tool_choice` option
+    fn convert_tool_choice(choice: &ToolChoice) -> ChatCompletionToolChoiceOption {
+        match choice {
+            ToolChoice::Auto => ChatCompletionToolChoiceOption::Auto,
+            ToolChoice::None => ChatCompletionToolChoiceOption::None,
+            ToolChoice::Required => ChatCompletionToolChoiceOption::Required,
+            ToolChoice::Specific(name) => {
+                ChatCompletionToolChoiceOption::Named(ChatCompletionNamedToolChoice {
+                    r#type: ChatCompletionToolType::Function,
+                    function: FunctionName { name: name.clone() },
+                })
+            }
+        }
+    }
+
+    /// Set the token limit using the field the endpoint expects
+    fn set_max_tokens(
+        &self,
+        request_builder: &mut CreateChatCompletionRequestArgs,
+        max_tokens: u32,
+    ) {
+        if self.use_max_completion_tokens {
+            request_builder.max_completion_tokens(max_tokens);
+        } else {
+            request_builder.max_tokens(max_tokens);
+        }
+    }
+
     /// Convert our internal message format to async-openai format
     fn convert_messages(
         &self,
         messages: Vec<LlmMessage>,
     ) -> Result<Vec<ChatCompletionRequestMessage>> {
+        let messages = if self.merge_consecutive_roles {
+            crate::llm::merge_consecutive_roles(messages)
+        } else {
+            messages
+        };
         let mut converted = Vec::new();
 
         for message in messages {
@@ -232,6 +360,7 @@ impl LlmClient for OpenAiClient {
         tools: Option<Vec<ToolDefinition>>,
         options: Option<ChatOptions>,
     ) -> Result<LlmResponse> {
+        let cancellation = options.as_ref().and_then(|o| o.cancellation.clone());
         let converted_messages = self.convert_messages(messages)?;
         let converted_tools = tools.map(|t| self.convert_tools(t));
 
@@ -246,11 +375,14 @@ impl LlmClient for OpenAiClient {
 
         if let Some(tools) = converted_tools {
             request_builder.tools(tools);
+            if let Some(choice) = options.as_ref().and_then(|o| o.tool_choice.as_ref()) {
+                request_builder.tool_choice(Self::convert_tool_choice(choice));
+            }
         }
 
         if let Some(opts) = options {
             if let Some(max_tokens) = opts.max_tokens {
-                request_builder.max_tokens(max_tokens);
+                self.set_max_tokens(&mut request_builder, max_tokens);
             }
             if let Some(temperature) = opts.temperature {
                 request_builder.temperature(temperature);
@@ -267,15 +399,31 @@ impl LlmClient for OpenAiClient {
             }
         })?;
 
-        let response = self.client.chat().create(request).await.map_err(|e| {
-            tracing::error!("OpenAI API call failed: {}", e);
-            LlmError::ApiError {
-                status: 500, // async-openai doesn't expose status codes directly
-                message: e.to_string(),
-            }
-        })?;
+        let exchange = retry_with_backoff(&self.retry, is_retryable, || {
+            retry_parse_failure(&self.retry, || async {
+                self.log_raw_request(&request);
+                self.send_request(&request).await.inspect_err(|e| {
+                    tracing::error!("OpenAI API call failed: {}", e);
+                })
+            })
+        });
+        let (response, headers) = with_cancellation(cancellation, exchange).await?;
 
-        let result = self.convert_response(response);
+        let endpoint = format!(
+            "{}{}",
+            Self::api_base(&self.base_url),
+            CHAT_COMPLETIONS_PATH
+        );
+        let metadata = crate::llm::http::response_origin_metadata(
+            &response.model,
+            &response.id,
+            &endpoint,
+            Some(&headers),
+        );
+        let result = self.convert_response(response).map(|mut response| {
+            response.metadata = Some(metadata);
+            response
+        });
         match &result {
             Ok(response) => {
                 // Log tool usage in response - critical for debugging tool calls
@@ -339,11 +487,14 @@ impl LlmClient for OpenAiClient {
 
         if let Some(tools) = converted_tools {
             request_builder.tools(tools);
+            if let Some(choice) = options.as_ref().and_then(|o| o.tool_choice.as_ref()) {
+                request_builder.tool_choice(Self::convert_tool_choice(choice));
+            }
         }
 
         if let Some(opts) = options {
             if let Some(max_tokens) = opts.max_tokens {
-                request_builder.max_tokens(max_tokens);
+                self.set_max_tokens(&mut request_builder, max_tokens);
             }
             if let Some(temperature) = opts.temperature {
                 request_builder.temperature(temperature);
@@ -358,24 +509,21 @@ impl LlmClient for OpenAiClient {
             .map_err(|e| LlmError::InvalidRequest {
                 message: format!("Failed to build request: {}", e),
             })?;
+        self.log_raw_request(&request);
+        if self.http.has_middleware() {
+            tracing::warn!("HTTP middleware is not applied to streamed OpenAI requests");
+        }
 
         let stream = self
             .client
             .chat()
             .create_stream(request)
             .await
-            .map_err(|e| LlmError::ApiError {
-                status: 500,
-                message: e.to_string(),
-            })?;
+            .map_err(map_openai_error)?;
 
         let converted_stream = stream.map(|result| match result {
             Ok(chunk) => self.convert_stream_chunk(chunk),
-            Err(e) => Err((LlmError::ApiError {
-                status: 500,
-                message: e.to_string(),
-            })
-            .into()),
+            Err(e) => Err(map_openai_error(e).into()),
         });
 
         Ok(Box::new(Box::pin(converted_stream)))
@@ -397,7 +545,10 @@ impl OpenAiClient {
                     message: "No choices in response".to_string(),
                 })?;
 
-        let message_content = if let Some(content) = choice.message.content {
+        // A refusal replaces the answer; surface its explanation as the text
+        let refusal = choice.message.refusal;
+        let content = choice.message.content.or(refusal.clone());
+        let message_content = if let Some(content) = content {
             if let Some(tool_calls) = choice.message.tool_calls {
                 // Multi-modal content with text and tool calls
                 let mut blocks = vec![ContentBlock::Text { text: content }];
@@ -452,6 +603,7 @@ impl OpenAiClient {
         });
 
         let finish_reason = choice.finish_reason.map(|reason| match reason {
+            async_openai::types::FinishReason::Stop if refusal.is_some() => FinishReason::Refusal,
             async_openai::types::FinishReason::Stop => FinishReason::Stop,
             async_openai::types::FinishReason::Length => FinishReason::Length,
             async_openai::types::FinishReason::ToolCalls => FinishReason::ToolCalls,
@@ -537,3 +689,624 @@ impl OpenAiClient {
         })
     }
 }
+
+/// Map an async-openai error to the closest `LlmError`
+fn map_openai_error(error: OpenAIError) -> LlmError {
+    match error {
+        OpenAIError::Reqwest(e) => LlmError::Network {
+            message: e.to_string(),
+        },
+        OpenAIError::ApiError(api_error) => classify_api_error(&api_error).unwrap_or_else(|| {
+            LlmError::ApiError {
+                // Only streamed requests go through async-openai, which hides
+                // the status code and response headers
+                status: 500,
+                message: serde_json::to_string(&WrappedError { error: api_error })
+                    .unwrap_or_default(),
+                request_id: None,
+            }
+        }),
+        OpenAIError::JSONDeserialize(e) => LlmError::ResponseParse {
+            message: e.to_string(),
+        },
+        e => LlmError::ApiError {
+            status: 500,
+            message: e.to_string(),
+            request_id: None,
+        },
+    }
+}
+
+/// Parse an OpenAI-style `{ "error": { "type", "code", "message" } }` body
+///
+/// Bodies that don't match the envelope or carry an unrecognized type are
+/// returned as `LlmError::ApiError` with the raw body as the message and the
+/// provider's `request_id`.
+fn parse_error_body(status: u16, body: &str, request_id: Option<String>) -> LlmError {
+    serde_json::from_str::<WrappedError>(body)
+        .ok()
+        .and_then(|wrapped| classify_api_error(&wrapped.error))
+        .unwrap_or_else(|| LlmError::ApiError {
+            status,
+            message: body.to_string(),
+            request_id,
+        })
+}
+
+/// Map known error `type`/`code` values to specific variants
+fn classify_api_error(error: &ApiError) -> Option<LlmError> {
+    let message = error.message.clone();
+    let code = error.code.as_deref().unwrap_or_default();
+    let error_type = error.r#type.as_deref().unwrap_or_default();
+
+    match (error_type, code) {
+        (_, "context_length_exceeded") => Some(LlmError::ContextLengthExceeded { message }),
+        (_, "invalid_api_key") | ("authentication_error", _) | ("invalid_authentication", _) => {
+            Some(LlmError::Authentication { message })
+        }
+        (_, "rate_limit_exceeded") | ("rate_limit_error", _) | ("requests", _) | ("tokens", _) => {
+            Some(LlmError::RateLimit)
+        }
+        ("invalid_request_error", _) => Some(LlmError::InvalidRequest { message }),
+        ("server_error", _) | ("api_error", _) => Some(LlmError::ServerError { message }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{MaxTokensField, Protocol};
+    use async_openai::config::Config;
+
+    fn client(model: &str, max_tokens_field: MaxTokensField) -> OpenAiClient {
+        let config = ResolvedLlmConfig::new(
+            Protocol::OpenAICompat,
+            "https://api.openai.com".to_string(),
+            "test-key".to_string(),
+            model.to_string(),
+        )
+        .with_max_tokens_field(max_tokens_field);
+        OpenAiClient::new(&config).unwrap()
+    }
+
+    fn request_json(client: &OpenAiClient) -> Value {
+        let mut request_builder = CreateChatCompletionRequestArgs::default();
+        request_builder.model(&client.model);
+        request_builder.messages(
+            client
+                .convert_messages(vec![LlmMessage::user("Hello")])
+                .unwrap(),
+        );
+        client.set_max_tokens(&mut request_builder, 256);
+        serde_json::to_value(request_builder.build().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_adjacent_same_role_messages_merged() {
+        let tool_result = |id: &str, content: &str| LlmMessage {
+            role: MessageRole::Tool,
+            content: MessageContent::MultiModal(vec![ContentBlock::ToolResult {
+                tool_use_id: id.to_string(),
+                is_error: Some(false),
+                content: content.to_string(),
+            }]),
+            metadata: None,
+        };
+        let messages = vec![
+            LlmMessage::user("Fix the build."),
+            LlmMessage::user("It fails on CI too."),
+            LlmMessage {
+                role: MessageRole::Assistant,
+                content: MessageContent::MultiModal(vec![
+                    ContentBlock::ToolUse {
+                        id: "call_1".to_string(),
+                        name: "bash".to_string(),
+                        input: serde_json::json!({"command": "cargo build"}),
+                    },
+                    ContentBlock::ToolUse {
+                        id: "call_2".to_string(),
+                        name: "bash".to_string(),
+                        input: serde_json::json!({"command": "git status"}),
+                    },
+                ]),
+                metadata: None,
+            },
+            tool_result("call_1", "error[E0425]"),
+            tool_result("call_2", "clean"),
+            LlmMessage::assistant("The build is broken."),
+            LlmMessage::assistant("It needs a missing import."),
+        ];
+
+        let converted = client("gpt-4o", MaxTokensField::Auto)
+            .convert_messages(messages)
+            .unwrap();
+        let sent = serde_json::to_value(converted).unwrap();
+        let roles: Vec<&str> = sent
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|message| message["role"].as_str().unwrap())
+            .collect();
+        assert_eq!(roles, ["user", "assistant", "tool", "tool", "assistant"]);
+        assert_eq!(sent[0]["content"], "Fix the build.\n\nIt fails on CI too.");
+        assert_eq!(sent[3]["tool_call_id"], "call_2");
+        assert_eq!(
+            sent[4]["content"],
+            "The build is broken.\n\nIt needs a missing import."
+        );
+    }
+
+    #[test]
+    fn test_max_completion_tokens_sent_under_flag() {
+        let request = request_json(&client("gpt-4o", MaxTokensField::MaxCompletionTokens));
+
+        assert_eq!(request["max_completion_tokens"], 256);
+        assert!(request.get("max_tokens").is_none());
+    }
+
+    #[test]
+    fn test_max_tokens_sent_by_default() {
+        let request = request_json(&client("gpt-4o", MaxTokensField::Auto));
+
+        assert_eq!(request["max_tokens"], 256);
+        assert!(request.get("max_completion_tokens").is_none());
+    }
+
+    #[test]
+    fn test_tool_choice_serialized_for_each_variant() {
+        let client = client("gpt-4o", MaxTokensField::Auto);
+        let tools = client.convert_tools(vec![ToolDefinition {
+            tool_type: "function".to_string(),
+            function: crate::llm::FunctionDefinition {
+                name: "bash".to_string(),
+                description: "Run a command".to_string(),
+                parameters: serde_json::json!({"type": "object"}),
+            },
+        }]);
+        let cases = [
+            (ToolChoice::Auto, serde_json::json!("auto")),
+            (ToolChoice::None, serde_json::json!("none")),
+            (ToolChoice::Required, serde_json::json!("required")),
+            (
+                ToolChoice::Specific("bash".to_string()),
+                serde_json::json!({"type": "function", "function": {"name": "bash"}}),
+            ),
+        ];
+
+        for (choice, expected) in cases {
+            let mut request_builder = CreateChatCompletionRequestArgs::default();
+            request_builder.model(&client.model);
+            request_builder.messages(
+                client
+                    .convert_messages(vec![LlmMessage::user("Hello")])
+                    .unwrap(),
+            );
+            request_builder.tools(tools.clone());
+            request_builder.tool_choice(OpenAiClient::convert_tool_choice(&choice));
+
+            let request = serde_json::to_value(request_builder.build().unwrap()).unwrap();
+            assert_eq!(request["tool_choice"], expected);
+        }
+    }
+
+    #[test]
+    fn test_max_tokens_field_auto_detects_reasoning_models() {
+        let auto = MaxTokensField::Auto;
+        assert!(auto.uses_max_completion_tokens("o3-mini"));
+        assert!(auto.uses_max_completion_tokens("openai/GPT-5"));
+        assert!(!auto.uses_max_completion_tokens("gpt-4o"));
+        assert!(!MaxTokensField::MaxTokens.uses_max_completion_tokens("o1"));
+
+        let request = request_json(&client("o1-preview", MaxTokensField::Auto));
+        assert_eq!(request["max_completion_tokens"], 256);
+    }
+
+    #[test]
+    fn test_parse_error_body_maps_known_errors() {
+        let body = |error_type: &str, code: &str| {
+            serde_json::json!({
+                "error": {
+                    "message": "details",
+                    "type": error_type,
+                    "param": null,
+                    "code": code,
+                }
+            })
+            .to_string()
+        };
+
+        assert!(matches!(
+            parse_error_body(401, &body("invalid_request_error", "invalid_api_key"), None),
+            LlmError::Authentication { message } if message == "details"
+        ));
+        assert!(matches!(
+            parse_error_body(429, &body("requests", "rate_limit_exceeded"), None),
+            LlmError::RateLimit
+        ));
+        assert!(matches!(
+            parse_error_body(
+                400,
+                &body("invalid_request_error", "context_length_exceeded"),
+                None
+            ),
+            LlmError::ContextLengthExceeded { .. }
+        ));
+        assert!(matches!(
+            parse_error_body(
+                400,
+                &body("invalid_request_error", "unsupported_value"),
+                None
+            ),
+            LlmError::InvalidRequest { .. }
+        ));
+        assert!(matches!(
+            parse_error_body(500, &body("server_error", ""), None),
+            LlmError::ServerError { .. }
+        ));
+    }
+
+    #[test]
+    fn test_api_errors_mapped_by_type_and_code() {
+        let api_error = |error_type: &str, code: &str| {
+            OpenAIError::ApiError(ApiError {
+                message: "details".to_string(),
+                r#type: Some(error_type.to_string()),
+                param: None,
+                code: Some(code.to_string()),
+            })
+        };
+
+        assert!(matches!(
+            map_openai_error(api_error("invalid_request_error", "context_length_exceeded")),
+            LlmError::ContextLengthExceeded { message } if message == "details"
+        ));
+        assert!(matches!(
+            map_openai_error(api_error("tokens", "")),
+            LlmError::RateLimit
+        ));
+        assert!(matches!(
+            map_openai_error(api_error("insufficient_quota", "insufficient_quota")),
+            LlmError::ApiError { status: 500, message, .. } if message.contains("insufficient_quota")
+        ));
+    }
+
+    #[test]
+    fn test_parse_error_body_keeps_raw_body_for_unknowns() {
+        let unknown = r#"{"error":{"message":"quota","type":"insufficient_quota","code":"insufficient_quota"}}"#;
+        assert!(matches!(
+            parse_error_body(429, unknown, None),
+            LlmError::ApiError { status: 429, message, .. } if message == unknown
+        ));
+
+        let not_json = "<html>Bad Gateway</html>";
+        assert!(matches!(
+            parse_error_body(502, not_json, None),
+            LlmError::ApiError { status: 502, message, .. } if message == not_json
+        ));
+    }
+
+    #[test]
+    fn test_base_url_shapes_resolve_to_chat_endpoint() {
+        let cases = [
+            (
+                "https://api.openai.com",
+                "https://api.openai.com/v1/chat/completions",
+            ),
+            (
+                "https://proxy.example.com/v1",
+                "https://proxy.example.com/v1/chat/completions",
+            ),
+            (
+                "https://proxy.example.com/v1/",
+                "https://proxy.example.com/v1/chat/completions",
+            ),
+            (
+                "https://gateway.example.com/api/v1/chat/completions",
+                "https://gateway.example.com/api/v1/chat/completions",
+            ),
+            (
+                "https://gateway.example.com/openai/",
+                "https://gateway.example.com/openai/v1/chat/completions",
+            ),
+        ];
+
+        for (base_url, expected) in cases {
+            let config = ResolvedLlmConfig::new(
+                Protocol::OpenAICompat,
+                base_url.to_string(),
+                "test-key".to_string(),
+                "gpt-4o".to_string(),
+            );
+            let client = OpenAiClient::new(&config).unwrap();
+            assert_eq!(
+                client.client.config().url(CHAT_COMPLETIONS_PATH),
+                expected,
+                "base_url {}",
+                base_url
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_response_metadata_identifies_served_model() {
+        let body = serde_json::json!({
+            "id": "chatcmpl-B9MBs8CjcvOU2jLn4n570S5qMJKcT",
+            "object": "chat.completion",
+            "created": 1741569952,
+            "model": "gpt-4o-2024-08-06",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "Hi"},
+                "finish_reason": "stop",
+                "logprobs": null
+            }],
+            "usage": {"prompt_tokens": 3, "completion_tokens": 1, "total_tokens": 4}
+        });
+        let base_url = crate::llm::testing::serve_http_once(
+            &[("x-request-id", "req_6f8c1f2b")],
+            &body.to_string(),
+        )
+        .await;
+        let config = ResolvedLlmConfig::new(
+            Protocol::OpenAICompat,
+            base_url.clone(),
+            "test-key".to_string(),
+            "gpt-4o".to_string(),
+        );
+        let client = OpenAiClient::new(&config).unwrap();
+
+        let response = client
+            .chat_completion(vec![LlmMessage::user("hello")], None, None)
+            .await
+            .unwrap();
+
+        assert_eq!(response.served_model(), Some("gpt-4o-2024-08-06"));
+        assert_eq!(
+            response.metadata_str(crate::llm::RESPONSE_ID_METADATA_KEY),
+            Some("chatcmpl-B9MBs8CjcvOU2jLn4n570S5qMJKcT")
+        );
+        assert_eq!(
+            response.endpoint(),
+            Some(format!("{}/v1/chat/completions", base_url).as_str())
+        );
+        assert_eq!(response.request_id(), Some("req_6f8c1f2b"));
+    }
+
+    /// Middleware recording the path and status of each request
+    struct RecordingMiddleware {
+        seen: std::sync::Arc<std::sync::Mutex<Vec<(String, u16)>>>,
+    }
+
+    #[async_trait]
+    impl crate::llm::http::HttpMiddleware for RecordingMiddleware {
+        async fn handle(
+            &self,
+            request: reqwest::Request,
+            next: crate::llm::http::Next<'_>,
+        ) -> Result<reqwest::Response> {
+            let path = request.url().path().to_string();
+            let response = next.run(request).await?;
+            self.seen
+                .lock()
+                .unwrap()
+                .push((path, response.status().as_u16()));
+            Ok(response)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_middleware_wraps_openai_requests() {
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let client_for = |base_url: String| {
+            let config = ResolvedLlmConfig::new(
+                Protocol::OpenAICompat,
+                base_url,
+                "test-key".to_string(),
+                "gpt-4o".to_string(),
+            )
+            .with_http_middleware(RecordingMiddleware { seen: seen.clone() });
+            OpenAiClient::new(&config).unwrap()
+        };
+
+        let body = serde_json::json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "created": 1741569952,
+            "model": "gpt-4o-2024-08-06",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "Hi"},
+                "finish_reason": "stop",
+                "logprobs": null
+            }]
+        });
+        let base_url = crate::llm::testing::serve_http_once(&[], &body.to_string()).await;
+        let response = client_for(base_url)
+            .chat_completion(vec![LlmMessage::user("hello")], None, None)
+            .await
+            .unwrap();
+        assert_eq!(response.message.get_text().as_deref(), Some("Hi"));
+
+        let error = serde_json::json!({
+            "error": {"message": "Incorrect API key", "type": "invalid_request_error", "code": "invalid_api_key"}
+        });
+        let base_url = crate::llm::testing::serve_http_status_once(
+            "401 Unauthorized",
+            &[],
+            &error.to_string(),
+        )
+        .await;
+        let error = client_for(base_url)
+            .chat_completion(vec![LlmMessage::user("hello")], None, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            crate::error::Error::Llm(LlmError::Authentication { .. })
+        ));
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                ("/v1/chat/completions".to_string(), 200),
+                ("/v1/chat/completions".to_string(), 401)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_api_error_carries_status_and_request_id() {
+        let body = serde_json::json!({
+            "error": {
+                "message": "Country, region, or territory not supported",
+                "type": "request_forbidden",
+                "param": null,
+                "code": "unsupported_country_region_territory"
+            }
+        });
+        let base_url = crate::llm::testing::serve_http_status_once(
+            "403 Forbidden",
+            &[("x-request-id", "req_9a1b7c4d")],
+            &body.to_string(),
+        )
+        .await;
+        let config = ResolvedLlmConfig::new(
+            Protocol::OpenAICompat,
+            base_url,
+            "test-key".to_string(),
+            "gpt-4o".to_string(),
+        );
+        let client = OpenAiClient::new(&config).unwrap();
+
+        let error = client
+            .chat_completion(vec![LlmMessage::user("hello")], None, None)
+            .await
+            .unwrap_err();
+
+        match &error {
+            crate::error::Error::Llm(LlmError::ApiError {
+                status, request_id, ..
+            }) => {
+                assert_eq!(*status, 403);
+                assert_eq!(request_id.as_deref(), Some("req_9a1b7c4d"));
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert!(error.to_string().ends_with("(request id: req_9a1b7c4d)"));
+    }
+
+    /// Middleware recording a request header
+    struct HeaderMiddleware {
+        name: &'static str,
+        seen: std::sync::Arc<std::sync::Mutex<Option<String>>>,
+    }
+
+    #[async_trait]
+    impl crate::llm::http::HttpMiddleware for HeaderMiddleware {
+        async fn handle(
+            &self,
+            request: reqwest::Request,
+            next: crate::llm::http::Next<'_>,
+        ) -> Result<reqwest::Response> {
+            *self.seen.lock().unwrap() = request
+                .headers()
+                .get(self.name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string);
+            next.run(request).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_middleware_requests_keep_headers_and_request_id() {
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(None));
+        let body = serde_json::json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "created": 1741569952,
+            "model": "gpt-4o-2024-08-06",
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "Hi"},
+                "finish_reason": "stop",
+                "logprobs": null
+            }]
+        });
+        let base_url = crate::llm::testing::serve_http_once(
+            &[("x-request-id", "req_6f8c1f2b")],
+            &body.to_string(),
+        )
+        .await;
+        let config = ResolvedLlmConfig::new(
+            Protocol::OpenAICompat,
+            base_url,
+            "test-key".to_string(),
+            "gpt-4o".to_string(),
+        )
+        .with_header("x-gateway-team".to_string(), "agents".to_string())
+        .with_http_middleware(HeaderMiddleware {
+            name: "x-gateway-team",
+            seen: seen.clone(),
+        });
+        let client = OpenAiClient::new(&config).unwrap();
+
+        let response = client
+            .chat_completion(vec![LlmMessage::user("hello")], None, None)
+            .await
+            .unwrap();
+
+        assert_eq!(seen.lock().unwrap().as_deref(), Some("agents"));
+        assert_eq!(response.request_id(), Some("req_6f8c1f2b"));
+        assert_eq!(response.served_model(), Some("gpt-4o-2024-08-06"));
+    }
+
+    #[tokio::test]
+    async fn test_refusal_and_content_filter_mapped() {
+        let cases = [
+            (
+                serde_json::json!({"role": "assistant", "content": null, "refusal": "I can't help with that."}),
+                "stop",
+                FinishReason::Refusal,
+                "I can't help with that.",
+            ),
+            (
+                serde_json::json!({"role": "assistant", "content": ""}),
+                "content_filter",
+                FinishReason::ContentFilter,
+                "",
+            ),
+        ];
+
+        for (message, finish_reason, expected, text) in cases {
+            let body = serde_json::json!({
+                "id": "chatcmpl-1",
+                "object": "chat.completion",
+                "created": 1741569952,
+                "model": "gpt-4o-2024-08-06",
+                "choices": [{
+                    "index": 0,
+                    "message": message,
+                    "finish_reason": finish_reason,
+                    "logprobs": null
+                }]
+            });
+            let base_url = crate::llm::testing::serve_http_once(&[], &body.to_string()).await;
+            let config = ResolvedLlmConfig::new(
+                Protocol::OpenAICompat,
+                base_url,
+                "test-key".to_string(),
+                "gpt-4o".to_string(),
+            );
+            let client = OpenAiClient::new(&config).unwrap();
+
+            let response = client
+                .chat_completion(vec![LlmMessage::user("hello")], None, None)
+                .await
+                .unwrap();
+
+            assert_eq!(response.finish_reason, Some(expected));
+            assert_eq!(response.message.get_text().unwrap_or_default(), text);
+        }
+    }
+}
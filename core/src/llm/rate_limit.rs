@@ -0,0 +1,207 @@
+//! Client-side request rate limiting shared across agents
+//!
+//! A single [`RateLimiter`] can be shared (via `Arc`) by every client that
+//! talks to the same account. [`RateLimitedClient`] consults it before each
+//! request and waits until the request fits within the configured
+//! requests-per-minute and tokens-per-minute budgets.
+
+use crate::agent::tokens::TokenCalculator;
+use crate::error::Result;
+use crate::llm::{ChatOptions, LlmClient, LlmMessage, LlmResponse, LlmStreamChunk, ToolDefinition};
+use async_trait::async_trait;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant};
+
+/// Length of the sliding window the limits apply to
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Per-key request budgets; `None` leaves that dimension unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RateLimitConfig {
+    /// Maximum requests started within any 60 second window
+    pub requests_per_minute: Option<u32>,
+    /// Maximum estimated prompt tokens sent within any 60 second window
+    pub tokens_per_minute: Option<u32>,
+}
+
+/// Requests admitted within the current window for one key
+#[derive(Debug, Default)]
+struct Window {
+    requests: VecDeque<(Instant, u32)>,
+    tokens: u64,
+}
+
+impl Window {
+    fn expire(&mut self, now: Instant) {
+        while let Some(&(started, tokens)) = self.requests.front() {
+            if now.duration_since(started) < WINDOW {
+                break;
+            }
+            self.requests.pop_front();
+            self.tokens -= tokens as u64;
+        }
+    }
+
+    /// Whether a request of `tokens` fits, or when the oldest entry leaves the window
+    fn admit(&self, config: &RateLimitConfig, tokens: u32) -> std::result::Result<(), Instant> {
+        let Some(&(oldest, _)) = self.requests.front() else {
+            // An empty window always admits, even a request larger than the token budget
+            return Ok(());
+        };
+
+        let over_requests = config
+            .requests_per_minute
+            .is_some_and(|rpm| self.requests.len() as u64 >= rpm as u64);
+        let over_tokens = config
+            .tokens_per_minute
+            .is_some_and(|tpm| self.tokens + tokens as u64 > tpm as u64);
+
+        if over_requests || over_tokens {
+            Err(oldest + WINDOW)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Sliding-window limiter keyed by provider or endpoint
+#[derive(Debug)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    windows: Mutex<HashMap<String, Window>>,
+}
+
+impl RateLimiter {
+    /// Create a limiter applying `config` to every key
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The budgets applied to each key
+    pub fn config(&self) -> &RateLimitConfig {
+        &self.config
+    }
+
+    /// Wait until a request of `estimated_tokens` fits the budget for `key`, then record it
+    pub async fn acquire(&self, key: &str, estimated_tokens: u32) {
+        loop {
+            let retry_at = {
+                let mut windows = self.windows.lock().await;
+                let window = windows.entry(key.to_string()).or_default();
+                let now = Instant::now();
+                window.expire(now);
+
+                match window.admit(&self.config, estimated_tokens) {
+                    Ok(()) => {
+                        window.requests.push_back((now, estimated_tokens));
+                        window.tokens += estimated_tokens as u64;
+                        return;
+                    }
+                    Err(retry_at) => retry_at,
+                }
+            };
+
+            tracing::debug!("Rate limit reached for '{}', waiting", key);
+            tokio::time::sleep_until(retry_at).await;
+        }
+    }
+}
+
+/// `LlmClient` wrapper that waits on a shared [`RateLimiter`] before each request
+pub struct RateLimitedClient {
+    inner: Arc<dyn LlmClient>,
+    limiter: Arc<RateLimiter>,
+    key: String,
+}
+
+impl RateLimitedClient {
+    /// Wrap `inner`, keying its budget by provider name
+    pub fn new(inner: Arc<dyn LlmClient>, limiter: Arc<RateLimiter>) -> Self {
+        let key = inner.provider_name().to_string();
+        Self {
+            inner,
+            limiter,
+            key,
+        }
+    }
+
+    /// Use a custom key, e.g. the endpoint URL, to group clients sharing a budget
+    pub fn with_key(mut self, key: impl Into<String>) -> Self {
+        self.key = key.into();
+        self
+    }
+
+    async fn acquire(&self, messages: &[LlmMessage], tools: Option<&[ToolDefinition]>) {
+        let estimated = TokenCalculator::count_request(messages, tools);
+        self.limiter
+            .acquire(&self.key, u32::try_from(estimated).unwrap_or(u32::MAX))
+            .await;
+    }
+}
+
+#[async_trait]
+impl LlmClient for RateLimitedClient {
+    async fn chat_completion(
+        &self,
+        messages: Vec<LlmMessage>,
+        tools: Option<Vec<ToolDefinition>>,
+        options: Option<ChatOptions>,
+    ) -> Result<LlmResponse> {
+        self.acquire(&messages, tools.as_deref()).await;
+        self.inner.chat_completion(messages, tools, options).await
+    }
+
+    fn model_name(&self) -> &str {
+        self.inner.model_name()
+    }
+
+    fn provider_name(&self) -> &str {
+        self.inner.provider_name()
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.inner.supports_streaming()
+    }
+
+    async fn chat_completion_stream(
+        &self,
+        messages: Vec<LlmMessage>,
+        tools: Option<Vec<ToolDefinition>>,
+        options: Option<ChatOptions>,
+    ) -> Result<Box<dyn futures::Stream<Item = Result<LlmStreamChunk>> + Send + Unpin + '_>> {
+        self.acquire(&messages, tools.as_deref()).await;
+        self.inner
+            .chat_completion_stream(messages, tools, options)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_token_budget_delays_until_window_expires() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            requests_per_minute: None,
+            tokens_per_minute: Some(1_000),
+        });
+        let start = Instant::now();
+
+        limiter.acquire("openai", 600).await;
+        limiter.acquire("anthropic", 600).await;
+        assert_eq!(
+            start.elapsed(),
+            Duration::ZERO,
+            "keys have separate budgets"
+        );
+
+        limiter.acquire("openai", 600).await;
+        assert_eq!(start.elapsed(), WINDOW);
+    }
+}
@@ -0,0 +1,376 @@
+//! Scripted LLM client for tests
+//!
+//! Available to downstream crates through the `testing` feature.
+//! [`ScriptedLlmClient`] replays a fixed sequence of responses and records
+//! every request it receives, so agent behavior can be tested without a
+//! provider.
+
+use crate::error::{LlmError, Result};
+use crate::llm::{
+    ChatOptions, ContentBlock, FinishReason, LlmClient, LlmMessage, LlmResponse, MessageContent,
+    MessageRole, ToolDefinition,
+};
+use async_trait::async_trait;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// A request received by a [`ScriptedLlmClient`]
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    /// Messages sent with the request
+    pub messages: Vec<LlmMessage>,
+    /// Tool definitions sent with the request
+    pub tools: Option<Vec<ToolDefinition>>,
+    /// Options sent with the request
+    pub options: Option<ChatOptions>,
+}
+
+impl RecordedRequest {
+    /// Names of the tools offered to the model, in the order they were sent
+    pub fn tool_names(&self) -> Vec<&str> {
+        self.tools
+            .iter()
+            .flatten()
+            .map(|tool| tool.function.name.as_str())
+            .collect()
+    }
+
+    /// The last message of the request
+    pub fn last_message(&self) -> Option<&LlmMessage> {
+        self.messages.last()
+    }
+}
+
+/// `LlmClient` that replays scripted responses in order
+///
+/// Once the script is exhausted every request fails with
+/// `LlmError::InvalidRequest`.
+pub struct ScriptedLlmClient {
+    model: String,
+    script: Mutex<VecDeque<std::result::Result<LlmResponse, LlmError>>>,
+    requests: Mutex<Vec<RecordedRequest>>,
+}
+
+impl ScriptedLlmClient {
+    /// Create a client with an empty script
+    pub fn new() -> Self {
+        Self {
+            model: "scripted-model".to_string(),
+            script: Mutex::new(VecDeque::new()),
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Report a different model name
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    /// Append a response to the script
+    pub fn with_response(self, response: LlmResponse) -> Self {
+        self.script.lock().unwrap().push_back(Ok(response));
+        self
+    }
+
+    /// Append a plain text reply to the script
+    pub fn with_text(self, text: impl Into<String>) -> Self {
+        let response = Self::text_response(&self.model, text);
+        self.with_response(response)
+    }
+
+    /// Append a reply calling a single tool to the script
+    pub fn with_tool_call(self, name: impl Into<String>, input: serde_json::Value) -> Self {
+        let id = format!("call_{}", self.script.lock().unwrap().len() + 1);
+        let response = Self::tool_call_response(&self.model, id, name, input);
+        self.with_response(response)
+    }
+
+    /// Append a failed request to the script
+    pub fn with_error(self, error: LlmError) -> Self {
+        self.script.lock().unwrap().push_back(Err(error));
+        self
+    }
+
+    /// Build a plain text response
+    pub fn text_response(model: &str, text: impl Into<String>) -> LlmResponse {
+        LlmResponse {
+            message: LlmMessage::assistant(text),
+            usage: None,
+            model: model.to_string(),
+            finish_reason: Some(FinishReason::Stop),
+            metadata: None,
+        }
+    }
+
+    /// Build a response calling a single tool
+    pub fn tool_call_response(
+        model: &str,
+        id: impl Into<String>,
+        name: impl Into<String>,
+        input: serde_json::Value,
+    ) -> LlmResponse {
+        LlmResponse {
+            message: LlmMessage {
+                role: MessageRole::Assistant,
+                content: MessageContent::MultiModal(vec![ContentBlock::ToolUse {
+                    id: id.into(),
+                    name: name.into(),
+                    input,
+                }]),
+                metadata: None,
+            },
+            usage: None,
+            model: model.to_string(),
+            finish_reason: Some(FinishReason::ToolCalls),
+            metadata: None,
+        }
+    }
+
+    /// Requests received so far, oldest first
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Number of scripted responses not yet returned
+    pub fn remaining(&self) -> usize {
+        self.script.lock().unwrap().len()
+    }
+
+    /// Panic unless exactly `expected` requests were received
+    pub fn assert_request_count(&self, expected: usize) {
+        let actual = self.requests.lock().unwrap().len();
+        assert_eq!(
+            actual, expected,
+            "expected {} LLM requests, got {}",
+            expected, actual
+        );
+    }
+
+    /// Panic unless every scripted response was used
+    pub fn assert_exhausted(&self) {
+        let remaining = self.remaining();
+        assert_eq!(
+            remaining, 0,
+            "{} scripted responses were never requested",
+            remaining
+        );
+    }
+}
+
+impl Default for ScriptedLlmClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl LlmClient for ScriptedLlmClient {
+    async fn chat_completion(
+        &self,
+        messages: Vec<LlmMessage>,
+        tools: Option<Vec<ToolDefinition>>,
+        options: Option<ChatOptions>,
+    ) -> Result<LlmResponse> {
+        self.requests.lock().unwrap().push(RecordedRequest {
+            messages,
+            tools,
+            options,
+        });
+
+        match self.script.lock().unwrap().pop_front() {
+            Some(response) => response.map_err(Into::into),
+            None => Err(LlmError::InvalidRequest {
+                message: "Scripted responses exhausted".to_string(),
+            }
+            .into()),
+        }
+    }
+
+    fn model_name(&self) -> &str {
+        &self.model
+    }
+
+    fn provider_name(&self) -> &str {
+        "scripted"
+    }
+}
+
+/// Answer the next HTTP request on a local port with a canned `200 OK`
+///
+/// Returns the server's base URL, e.g. `http://127.0.0.1:4321`.
+#[cfg(test)]
+pub(crate) async fn serve_http_once(headers: &[(&str, &str)], body: &str) -> String {
+    serve_http_status_once("200 OK", headers, body).await
+}
+
+/// Like [`serve_http_once`], answering with `status`, e.g. `"529 Overloaded"`
+#[cfg(test)]
+pub(crate) async fn serve_http_status_once(
+    status: &str,
+    headers: &[(&str, &str)],
+    body: &str,
+) -> String {
+    serve_http_responses(vec![http_response(status, headers, body)]).await
+}
+
+/// Answer the next requests with canned `200 OK` responses carrying `bodies`, in order
+#[cfg(test)]
+pub(crate) async fn serve_http_sequence(bodies: &[&str]) -> String {
+    serve_http_responses(
+        bodies
+            .iter()
+            .map(|body| http_response("200 OK", &[], body))
+            .collect(),
+    )
+    .await
+}
+
+#[cfg(test)]
+fn http_response(status: &str, headers: &[(&str, &str)], body: &str) -> String {
+    let mut response = format!(
+        "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n",
+        status,
+        body.len()
+    );
+    for (name, value) in headers {
+        response.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    response.push_str("\r\n");
+    response.push_str(body);
+    response
+}
+
+/// Answer one connection with each of `responses`, in order
+#[cfg(test)]
+async fn serve_http_responses(responses: Vec<String>) -> String {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        for response in responses {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            // Read the whole request first so the client is not cut off mid-send
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            loop {
+                let read = stream.read(&mut buf).await.unwrap();
+                if read == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..read]);
+                let text = String::from_utf8_lossy(&request);
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text[..end]
+                        .lines()
+                        .find_map(|line| {
+                            let (name, value) = line.split_once(':')?;
+                            name.eq_ignore_ascii_case("content-length")
+                                .then(|| value.trim().parse::<usize>().ok())?
+                        })
+                        .unwrap_or(0);
+                    if request.len() >= end + 4 + length {
+                        break;
+                    }
+                }
+            }
+
+            stream.write_all(response.as_bytes()).await.unwrap();
+            let _ = stream.shutdown().await;
+        }
+    });
+
+    format!("http://{}", addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::{AgentConfig, AgentCore};
+    use crate::output::events::NullOutput;
+    use crate::tools::ToolRegistry;
+    use serde_json::json;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_tool_call_then_text_sequence() {
+        let client = Arc::new(
+            ScriptedLlmClient::new()
+                .with_tool_call(
+                    "sequentialthinking",
+                    json!({
+                        "thought": "Check the input first",
+                        "thought_number": 1,
+                        "total_thoughts": 1,
+                        "next_thought_needed": false,
+                    }),
+                )
+                .with_text("The input looks fine."),
+        );
+        let config = AgentConfig {
+            max_steps: 2,
+            tools: vec!["sequentialthinking".to_string(), "task_done".to_string()],
+            ..Default::default()
+        };
+        let mut agent = AgentCore::new_with_client(
+            config,
+            client.clone(),
+            Box::new(NullOutput),
+            &ToolRegistry::default(),
+        );
+
+        let execution = agent
+            .execute_task_with_context("Check the input", Path::new("."))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            execution.final_message.as_deref(),
+            Some("The input looks fine.")
+        );
+        client.assert_request_count(2);
+        client.assert_exhausted();
+
+        let requests = client.requests();
+        let mut tools = requests[0].tool_names();
+        tools.sort();
+        assert_eq!(tools, vec!["sequentialthinking", "task_done"]);
+
+        // The second request carries the result of the scripted tool call
+        let last = requests[1].last_message().unwrap();
+        assert_eq!(last.role, MessageRole::Tool);
+        assert!(matches!(
+            &last.content,
+            MessageContent::MultiModal(blocks) if matches!(
+                &blocks[0],
+                ContentBlock::ToolResult { tool_use_id, is_error: Some(false), .. }
+                    if tool_use_id == "call_1"
+            )
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_scripted_errors_and_exhaustion() {
+        let client = ScriptedLlmClient::new().with_error(LlmError::RateLimit);
+
+        let error = client
+            .chat_completion(vec![LlmMessage::user("Hi")], None, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            crate::error::Error::Llm(LlmError::RateLimit)
+        ));
+
+        let error = client
+            .chat_completion(vec![LlmMessage::user("Hi again")], None, None)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("exhausted"));
+        client.assert_request_count(2);
+    }
+}
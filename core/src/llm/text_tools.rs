@@ -0,0 +1,247 @@
+//! Text-based tool calling for models without native function calling
+//!
+//! The model is asked to write tool invocations as tagged blocks:
+//!
+//! ```text
+//! <tool name="bash">{"command": "ls"}</tool>
+//! ```
+//!
+//! These are parsed out of the plain text response into `ToolUse` blocks so
+//! the rest of the agent loop works unchanged. Previous tool calls and results
+//! in the history are rendered back into the same text form before sending.
+
+use crate::llm::{ContentBlock, LlmMessage, MessageContent, MessageRole};
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Instructions appended to the system prompt describing the call format
+pub const TOOL_CALL_INSTRUCTIONS: &str = "\
+[Tool Calling]:
+You cannot call tools natively. To call a tool, write a block of the form
+<tool name=\"TOOL_NAME\">{\"param\": \"value\"}</tool>
+where the body is a JSON object matching the tool's parameters. You may call
+several tools in one response. Results are returned in the next message.";
+
+fn tool_tag_regex() -> &'static Regex {
+    static TOOL_TAG: OnceLock<Regex> = OnceLock::new();
+    TOOL_TAG.get_or_init(|| {
+        Regex::new(r#"(?s)<tool\s+name\s*=\s*["']([^"']+)["']\s*>(.*?)</tool\s*>"#)
+            .expect("valid tool tag regex")
+    })
+}
+
+/// Strip an optional markdown code fence around a JSON body
+fn strip_code_fence(body: &str) -> &str {
+    let trimmed = body.trim();
+    match trimmed.strip_prefix("```") {
+        Some(rest) => {
+            let rest = rest.strip_prefix("json").unwrap_or(rest);
+            rest.strip_suffix("```").unwrap_or(rest).trim()
+        }
+        None => trimmed,
+    }
+}
+
+/// Parse tagged tool calls out of a text response
+///
+/// Returns the remaining prose and the parsed `ToolUse` blocks. Tags whose
+/// body is not a JSON object are left in the prose untouched.
+pub fn parse_tool_calls(text: &str) -> (String, Vec<ContentBlock>) {
+    let mut prose = String::new();
+    let mut tool_uses = Vec::new();
+    let mut last_end = 0;
+
+    for captures in tool_tag_regex().captures_iter(text) {
+        let whole = captures.get(0).expect("match has a whole group");
+        let name = captures[1].trim().to_string();
+        let input = match serde_json::from_str::<serde_json::Value>(strip_code_fence(&captures[2]))
+        {
+            Ok(value @ serde_json::Value::Object(_)) => value,
+            _ => continue,
+        };
+
+        prose.push_str(&text[last_end..whole.start()]);
+        last_end = whole.end();
+
+        tool_uses.push(ContentBlock::ToolUse {
+            id: format!("text_call_{}", uuid::Uuid::new_v4().simple()),
+            name,
+            input,
+        });
+    }
+    prose.push_str(&text[last_end..]);
+
+    (prose.trim().to_string(), tool_uses)
+}
+
+/// Convert tagged tool calls in an assistant text response into `ToolUse` blocks
+pub fn extract_tool_calls(message: LlmMessage) -> LlmMessage {
+    let text = match &message.content {
+        MessageContent::Text(text) => text.clone(),
+        MessageContent::MultiModal(_) => return message,
+    };
+
+    let (prose, tool_uses) = parse_tool_calls(&text);
+    if tool_uses.is_empty() {
+        return message;
+    }
+
+    let mut blocks = Vec::with_capacity(tool_uses.len() + 1);
+    if !prose.is_empty() {
+        blocks.push(ContentBlock::Text { text: prose });
+    }
+    blocks.extend(tool_uses);
+
+    LlmMessage {
+        content: MessageContent::MultiModal(blocks),
+        ..message
+    }
+}
+
+/// Render a tool call in the tagged text format
+pub fn render_tool_call(name: &str, input: &serde_json::Value) -> String {
+    format!("<tool name=\"{}\">{}</tool>", name, input)
+}
+
+/// Rewrite native tool blocks in a message as plain text
+///
+/// Assistant `ToolUse` blocks become tagged calls and tool messages become
+/// user messages, so the history stays readable for a model without tool support.
+pub fn render_message_as_text(mut message: LlmMessage) -> LlmMessage {
+    if matches!(message.role, MessageRole::Tool) {
+        message.role = MessageRole::User;
+    }
+
+    let blocks = match &message.content {
+        MessageContent::MultiModal(blocks)
+            if blocks.iter().any(|block| {
+                matches!(
+                    block,
+                    ContentBlock::ToolUse { .. } | ContentBlock::ToolResult { .. }
+                )
+            }) =>
+        {
+            blocks
+        }
+        _ => return message,
+    };
+
+    let mut parts = Vec::with_capacity(blocks.len());
+    for block in blocks {
+        match block {
+            ContentBlock::Text { text } => parts.push(text.clone()),
+            ContentBlock::ToolUse { name, input, .. } => {
+                parts.push(render_tool_call(name, input));
+            }
+            ContentBlock::ToolResult {
+                is_error, content, ..
+            } => {
+                let label = if is_error.unwrap_or(false) {
+                    "Tool error"
+                } else {
+                    "Tool result"
+                };
+                parts.push(format!("[{}]:\n{}", label, content));
+            }
+            ContentBlock::Image { mime_type, .. } => {
+                parts.push(format!("[{} image]", mime_type));
+            }
+        }
+    }
+
+    LlmMessage {
+        role: message.role,
+        content: MessageContent::Text(parts.join("\n\n")),
+        metadata: message.metadata,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tool_call_surrounded_by_prose() {
+        let text = "Let me look around first.\n\n\
+                    <tool name=\"bash\">{\"command\": \"ls -la\"}</tool>\n\n\
+                    Then I'll report back.";
+
+        let (prose, calls) = parse_tool_calls(text);
+
+        assert_eq!(calls.len(), 1);
+        match &calls[0] {
+            ContentBlock::ToolUse { name, input, .. } => {
+                assert_eq!(name, "bash");
+                assert_eq!(input["command"], "ls -la");
+            }
+            other => panic!("Expected ToolUse, got {:?}", other),
+        }
+        assert!(prose.starts_with("Let me look around first."));
+        assert!(prose.ends_with("Then I'll report back."));
+        assert!(!prose.contains("<tool"));
+    }
+
+    #[test]
+    fn test_parse_multiple_and_fenced_calls() {
+        let text = "<tool name='str_replace_based_edit_tool'>\n```json\n\
+                    {\"command\": \"view\", \"path\": \"src/main.rs\"}\n```\n</tool>\
+                    <tool name=\"task_done\">{}</tool>";
+
+        let (prose, calls) = parse_tool_calls(text);
+
+        assert!(prose.is_empty());
+        let names: Vec<_> = calls
+            .iter()
+            .filter_map(|c| match c {
+                ContentBlock::ToolUse { name, .. } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(names, vec!["str_replace_based_edit_tool", "task_done"]);
+    }
+
+    #[test]
+    fn test_invalid_json_body_is_left_as_text() {
+        let text = "Example: <tool name=\"bash\">not json</tool>";
+
+        let (prose, calls) = parse_tool_calls(text);
+
+        assert!(calls.is_empty());
+        assert_eq!(prose, text);
+    }
+
+    #[test]
+    fn test_render_message_round_trips_tool_use() {
+        let message = LlmMessage {
+            role: MessageRole::Assistant,
+            content: MessageContent::MultiModal(vec![
+                ContentBlock::Text {
+                    text: "Checking.".to_string(),
+                },
+                ContentBlock::ToolUse {
+                    id: "call_1".to_string(),
+                    name: "bash".to_string(),
+                    input: serde_json::json!({"command": "pwd"}),
+                },
+            ]),
+            metadata: None,
+        };
+
+        let rendered = render_message_as_text(message);
+        let text = rendered.get_text().unwrap();
+        let (_, calls) = parse_tool_calls(&text);
+        assert_eq!(calls.len(), 1);
+
+        let result = render_message_as_text(LlmMessage {
+            role: MessageRole::Tool,
+            content: MessageContent::MultiModal(vec![ContentBlock::ToolResult {
+                tool_use_id: "call_1".to_string(),
+                is_error: Some(false),
+                content: "/tmp".to_string(),
+            }]),
+            metadata: None,
+        });
+        assert!(matches!(result.role, MessageRole::User));
+        assert!(result.get_text().unwrap().contains("/tmp"));
+    }
+}
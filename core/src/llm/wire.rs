@@ -0,0 +1,622 @@
+//! Conversion between internal messages and provider wire formats
+//!
+//! Transcripts saved as raw OpenAI chat completions or Anthropic messages
+//! JSON can be imported into [`LlmMessage`]s, e.g. to seed an agent with an
+//! existing conversation, and exported back to inspect what a provider sees.
+//!
+//! Internally every tool result is a `Tool` message. OpenAI sends one `tool`
+//! message per result, while Anthropic sends the results of a turn together
+//! in a single `user` message; the conversation helpers split and merge
+//! these as needed.
+
+use crate::error::{LlmError, Result};
+use crate::llm::{ContentBlock, LlmMessage, MessageContent, MessageRole};
+use serde_json::{json, Map, Value};
+
+fn invalid(message: impl Into<String>) -> crate::error::Error {
+    LlmError::InvalidRequest {
+        message: message.into(),
+    }
+    .into()
+}
+
+fn str_field<'a>(value: &'a Value, key: &str) -> Result<&'a str> {
+    value
+        .get(key)
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid(format!("Missing string field '{}' in {}", key, value)))
+}
+
+/// Text of a content value that is either a string or an array of text parts
+fn joined_text(content: &Value) -> Result<String> {
+    match content {
+        Value::String(text) => Ok(text.clone()),
+        Value::Null => Ok(String::new()),
+        Value::Array(parts) => Ok(parts
+            .iter()
+            .filter(|part| part.get("type").and_then(Value::as_str) == Some("text"))
+            .map(|part| str_field(part, "text"))
+            .collect::<Result<Vec<_>>>()?
+            .join("\n")),
+        other => Err(invalid(format!("Unsupported message content: {}", other))),
+    }
+}
+
+impl LlmMessage {
+    fn tool_result_message(blocks: Vec<ContentBlock>) -> Self {
+        Self {
+            role: MessageRole::Tool,
+            content: MessageContent::MultiModal(blocks),
+            metadata: None,
+        }
+    }
+
+    /// Parse one message in OpenAI chat completions format
+    pub fn from_openai_json(value: &Value) -> Result<Self> {
+        let role = match str_field(value, "role")? {
+            "system" | "developer" => MessageRole::System,
+            "user" => MessageRole::User,
+            "assistant" => MessageRole::Assistant,
+            "tool" => {
+                return Ok(Self::tool_result_message(vec![ContentBlock::ToolResult {
+                    tool_use_id: str_field(value, "tool_call_id")?.to_string(),
+                    is_error: None,
+                    content: joined_text(value.get("content").unwrap_or(&Value::Null))?,
+                }]))
+            }
+            other => return Err(invalid(format!("Unsupported OpenAI role '{}'", other))),
+        };
+
+        let mut blocks = match value.get("content").unwrap_or(&Value::Null) {
+            Value::Null => Vec::new(),
+            Value::String(text) if value.get("tool_calls").is_none() => {
+                return Ok(Self {
+                    role,
+                    content: MessageContent::Text(text.clone()),
+                    metadata: None,
+                })
+            }
+            Value::String(text) if text.is_empty() => Vec::new(),
+            Value::String(text) => vec![ContentBlock::Text { text: text.clone() }],
+            Value::Array(parts) => parts
+                .iter()
+                .map(Self::openai_part_to_block)
+                .collect::<Result<_>>()?,
+            other => return Err(invalid(format!("Unsupported message content: {}", other))),
+        };
+
+        for call in value
+            .get("tool_calls")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let function = call
+                .get("function")
+                .ok_or_else(|| invalid(format!("Tool call without a function: {}", call)))?;
+            let arguments = str_field(function, "arguments")?;
+            blocks.push(ContentBlock::ToolUse {
+                id: str_field(call, "id")?.to_string(),
+                name: str_field(function, "name")?.to_string(),
+                // Keep malformed arguments verbatim rather than failing the import
+                input: serde_json::from_str(arguments)
+                    .unwrap_or_else(|_| Value::String(arguments.to_string())),
+            });
+        }
+
+        Ok(Self {
+            role,
+            content: MessageContent::MultiModal(blocks),
+            metadata: None,
+        })
+    }
+
+    fn openai_part_to_block(part: &Value) -> Result<ContentBlock> {
+        match str_field(part, "type")? {
+            "text" => Ok(ContentBlock::Text {
+                text: str_field(part, "text")?.to_string(),
+            }),
+            "image_url" => {
+                let url = part
+                    .get("image_url")
+                    .map(|image| str_field(image, "url"))
+                    .transpose()?
+                    .unwrap_or_default();
+                let (mime_type, data) = url
+                    .strip_prefix("data:")
+                    .and_then(|rest| rest.split_once(";base64,"))
+                    .ok_or_else(|| invalid("Only base64 data URLs are supported for images"))?;
+                Ok(ContentBlock::Image {
+                    data: data.to_string(),
+                    mime_type: mime_type.to_string(),
+                })
+            }
+            other => Err(invalid(format!(
+                "Unsupported OpenAI content part '{}'",
+                other
+            ))),
+        }
+    }
+
+    /// This message in OpenAI chat completions format
+    ///
+    /// A tool message becomes one wire message per tool result.
+    pub fn to_openai_json(&self) -> Result<Vec<Value>> {
+        let role = match self.role {
+            MessageRole::System => "system",
+            MessageRole::User => "user",
+            MessageRole::Assistant => return Ok(vec![self.assistant_to_openai()]),
+            MessageRole::Tool => return self.tool_results_to_openai(),
+        };
+
+        let content = match &self.content {
+            MessageContent::Text(text) => Value::String(text.clone()),
+            MessageContent::MultiModal(blocks) => Value::Array(
+                blocks
+                    .iter()
+                    .filter_map(|block| match block {
+                        ContentBlock::Text { text } => Some(json!({"type": "text", "text": text})),
+                        ContentBlock::Image { data, mime_type } => Some(json!({
+                            "type": "image_url",
+                            "image_url": {"url": format!("data:{};base64,{}", mime_type, data)}
+                        })),
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+        };
+        Ok(vec![json!({"role": role, "content": content})])
+    }
+
+    fn assistant_to_openai(&self) -> Value {
+        let blocks = match &self.content {
+            MessageContent::Text(text) => return json!({"role": "assistant", "content": text}),
+            MessageContent::MultiModal(blocks) => blocks,
+        };
+
+        let text = blocks
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let tool_calls: Vec<Value> = blocks
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::ToolUse { id, name, input } => Some(json!({
+                    "id": id,
+                    "type": "function",
+                    "function": {
+                        "name": name,
+                        "arguments": match input {
+                            Value::String(arguments) => arguments.clone(),
+                            input => input.to_string(),
+                        },
+                    },
+                })),
+                _ => None,
+            })
+            .collect();
+
+        let mut message = Map::new();
+        message.insert("role".to_string(), json!("assistant"));
+        message.insert(
+            "content".to_string(),
+            if text.is_empty() {
+                Value::Null
+            } else {
+                Value::String(text)
+            },
+        );
+        if !tool_calls.is_empty() {
+            message.insert("tool_calls".to_string(), Value::Array(tool_calls));
+        }
+        Value::Object(message)
+    }
+
+    fn tool_results_to_openai(&self) -> Result<Vec<Value>> {
+        let results: Vec<Value> = match &self.content {
+            MessageContent::MultiModal(blocks) => blocks
+                .iter()
+                .filter_map(|block| match block {
+                    ContentBlock::ToolResult {
+                        tool_use_id,
+                        content,
+                        ..
+                    } => Some(json!({
+                        "role": "tool",
+                        "tool_call_id": tool_use_id,
+                        "content": content,
+                    })),
+                    _ => None,
+                })
+                .collect(),
+            MessageContent::Text(_) => Vec::new(),
+        };
+        if results.is_empty() {
+            return Err(invalid("Tool message must contain ToolResult"));
+        }
+        Ok(results)
+    }
+
+    /// Parse one message in Anthropic messages format
+    ///
+    /// A user message carrying `tool_result` blocks becomes a tool message.
+    /// Block types without an internal equivalent, such as `thinking`, are
+    /// skipped.
+    pub fn from_anthropic_json(value: &Value) -> Result<Self> {
+        let mut role = match str_field(value, "role")? {
+            "user" => MessageRole::User,
+            "assistant" => MessageRole::Assistant,
+            other => return Err(invalid(format!("Unsupported Anthropic role '{}'", other))),
+        };
+
+        let blocks = match value.get("content") {
+            Some(Value::String(text)) => {
+                return Ok(Self {
+                    role,
+                    content: MessageContent::Text(text.clone()),
+                    metadata: None,
+                })
+            }
+            Some(Value::Array(blocks)) => blocks,
+            _ => return Err(invalid(format!("Unsupported message content: {}", value))),
+        };
+
+        let mut converted = Vec::new();
+        for block in blocks {
+            match str_field(block, "type")? {
+                "text" => converted.push(ContentBlock::Text {
+                    text: str_field(block, "text")?.to_string(),
+                }),
+                "image" => {
+                    let source = block
+                        .get("source")
+                        .ok_or_else(|| invalid(format!("Image without a source: {}", block)))?;
+                    converted.push(ContentBlock::Image {
+                        data: str_field(source, "data")?.to_string(),
+                        mime_type: str_field(source, "media_type")?.to_string(),
+                    });
+                }
+                "tool_use" => converted.push(ContentBlock::ToolUse {
+                    id: str_field(block, "id")?.to_string(),
+                    name: str_field(block, "name")?.to_string(),
+                    input: block.get("input").cloned().unwrap_or_else(|| json!({})),
+                }),
+                "tool_result" => {
+                    role = MessageRole::Tool;
+                    converted.push(ContentBlock::ToolResult {
+                        tool_use_id: str_field(block, "tool_use_id")?.to_string(),
+                        is_error: block.get("is_error").and_then(Value::as_bool),
+                        content: joined_text(block.get("content").unwrap_or(&Value::Null))?,
+                    });
+                }
+                other => tracing::debug!("Skipping Anthropic '{}' block on import", other),
+            }
+        }
+
+        Ok(Self {
+            role,
+            content: MessageContent::MultiModal(converted),
+            metadata: None,
+        })
+    }
+
+    /// This message in Anthropic messages format
+    ///
+    /// Anthropic takes the system prompt as a separate request field, so
+    /// system messages are rejected; use [`to_anthropic_conversation`] to
+    /// export a whole conversation.
+    pub fn to_anthropic_json(&self) -> Result<Value> {
+        let role = match self.role {
+            MessageRole::System => {
+                return Err(invalid(
+                    "Anthropic has no system role; the system prompt is a separate field",
+                ))
+            }
+            MessageRole::User | MessageRole::Tool => "user",
+            MessageRole::Assistant => "assistant",
+        };
+
+        let blocks = match &self.content {
+            MessageContent::Text(_) if self.role == MessageRole::Tool => {
+                return Err(invalid("Tool message must contain ToolResult"))
+            }
+            MessageContent::Text(text) => return Ok(json!({"role": role, "content": text})),
+            MessageContent::MultiModal(blocks) => blocks,
+        };
+
+        let content: Vec<Value> = blocks
+            .iter()
+            .map(|block| match block {
+                ContentBlock::Text { text } => json!({"type": "text", "text": text}),
+                ContentBlock::Image { data, mime_type } => json!({
+                    "type": "image",
+                    "source": {"type": "base64", "media_type": mime_type, "data": data},
+                }),
+                ContentBlock::ToolUse { id, name, input } => json!({
+                    "type": "tool_use",
+                    "id": id,
+                    "name": name,
+                    "input": input,
+                }),
+                ContentBlock::ToolResult {
+                    tool_use_id,
+                    is_error,
+                    content,
+                } => {
+                    let mut result = json!({
+                        "type": "tool_result",
+                        "tool_use_id": tool_use_id,
+                        "content": content,
+                    });
+                    if let Some(is_error) = is_error {
+                        result["is_error"] = json!(is_error);
+                    }
+                    result
+                }
+            })
+            .collect();
+        Ok(json!({"role": role, "content": content}))
+    }
+}
+
+/// Parse an array of OpenAI chat completions messages
+pub fn from_openai_conversation(value: &Value) -> Result<Vec<LlmMessage>> {
+    value
+        .as_array()
+        .ok_or_else(|| invalid("Expected an array of OpenAI messages"))?
+        .iter()
+        .map(LlmMessage::from_openai_json)
+        .collect()
+}
+
+/// A conversation as an array of OpenAI chat completions messages
+pub fn to_openai_conversation(messages: &[LlmMessage]) -> Result<Value> {
+    let mut converted = Vec::new();
+    for message in messages {
+        converted.extend(message.to_openai_json()?);
+    }
+    Ok(Value::Array(converted))
+}
+
+/// Parse an Anthropic conversation
+///
+/// Accepts either a bare array of messages or an object with `messages` and
+/// an optional `system` prompt, as in a request body. Tool results sent
+/// together in one user message become one tool message each.
+pub fn from_anthropic_conversation(value: &Value) -> Result<Vec<LlmMessage>> {
+    let (system, wire_messages) = match value {
+        Value::Array(messages) => (None, messages),
+        Value::Object(request) => (
+            request.get("system"),
+            request
+                .get("messages")
+                .and_then(Value::as_array)
+                .ok_or_else(|| invalid("Expected a 'messages' array"))?,
+        ),
+        _ => return Err(invalid("Expected an Anthropic conversation")),
+    };
+
+    let mut messages = Vec::new();
+    if let Some(system) = system {
+        messages.push(LlmMessage::system(joined_text(system)?));
+    }
+    for wire_message in wire_messages {
+        let message = LlmMessage::from_anthropic_json(wire_message)?;
+        match message.content {
+            MessageContent::MultiModal(blocks) if message.role == MessageRole::Tool => {
+                messages.extend(
+                    blocks
+                        .into_iter()
+                        .map(|block| LlmMessage::tool_result_message(vec![block])),
+                );
+            }
+            _ => messages.push(message),
+        }
+    }
+    Ok(messages)
+}
+
+/// Merge adjacent messages that have the same role
+///
+/// Two text messages are joined with a blank line; otherwise the content
+/// blocks are concatenated in order. The merged message keeps the metadata of
+/// the first message in the run.
+pub fn merge_consecutive_roles(messages: Vec<LlmMessage>) -> Vec<LlmMessage> {
+    let mut merged: Vec<LlmMessage> = Vec::with_capacity(messages.len());
+    for message in messages {
+        match merged.last_mut() {
+            Some(last) if last.role == message.role => {
+                let previous =
+                    std::mem::replace(&mut last.content, MessageContent::Text(String::new()));
+                last.content = merge_content(previous, message.content);
+            }
+            _ => merged.push(message),
+        }
+    }
+    merged
+}
+
+fn merge_content(first: MessageContent, second: MessageContent) -> MessageContent {
+    let into_blocks = |content: MessageContent| match content {
+        MessageContent::Text(text) if text.is_empty() => Vec::new(),
+        MessageContent::Text(text) => vec![ContentBlock::Text { text }],
+        MessageContent::MultiModal(blocks) => blocks,
+    };
+    match (first, second) {
+        (MessageContent::Text(first), MessageContent::Text(second)) => {
+            if first.is_empty() {
+                MessageContent::Text(second)
+            } else if second.is_empty() {
+                MessageContent::Text(first)
+            } else {
+                MessageContent::Text(format!("{}\n\n{}", first, second))
+            }
+        }
+        (first, second) => {
+            let mut blocks = into_blocks(first);
+            blocks.extend(into_blocks(second));
+            MessageContent::MultiModal(blocks)
+        }
+    }
+}
+
+/// A conversation as an Anthropic request-style object
+///
+/// System messages are joined into the top-level `system` field and
+/// consecutive tool messages are merged into a single user message.
+pub fn to_anthropic_conversation(messages: &[LlmMessage]) -> Result<Value> {
+    let mut system = Vec::new();
+    let mut converted: Vec<Value> = Vec::new();
+    let mut previous_was_tool = false;
+
+    for message in messages {
+        if message.role == MessageRole::System {
+            if let Some(text) = message.get_text() {
+                system.push(text);
+            }
+            continue;
+        }
+
+        let wire_message = message.to_anthropic_json()?;
+        let is_tool = message.role == MessageRole::Tool;
+        match converted.last_mut() {
+            Some(last) if is_tool && previous_was_tool => {
+                if let (Some(Value::Array(merged)), Some(Value::Array(results))) =
+                    (last.get_mut("content"), wire_message.get("content"))
+                {
+                    merged.extend(results.iter().cloned());
+                }
+            }
+            _ => converted.push(wire_message),
+        }
+        previous_was_tool = is_tool;
+    }
+
+    let mut conversation = Map::new();
+    if !system.is_empty() {
+        conversation.insert("system".to_string(), Value::String(system.join("\n\n")));
+    }
+    conversation.insert("messages".to_string(), Value::Array(converted));
+    Ok(Value::Object(conversation))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openai_round_trip_with_tool_call() {
+        let transcript = json!([
+            {"role": "system", "content": "You are a helpful agent."},
+            {"role": "user", "content": "What's in the current directory?"},
+            {
+                "role": "assistant",
+                "content": "Let me look.",
+                "tool_calls": [{
+                    "id": "call_1",
+                    "type": "function",
+                    "function": {"name": "bash", "arguments": "{\"command\":\"ls\"}"}
+                }]
+            },
+            {"role": "tool", "tool_call_id": "call_1", "content": "Cargo.toml\nsrc"},
+            {"role": "assistant", "content": "A Cargo.toml and a src directory."}
+        ]);
+
+        let messages = from_openai_conversation(&transcript).unwrap();
+        assert_eq!(messages.len(), 5);
+        assert!(matches!(
+            &messages[2].content,
+            MessageContent::MultiModal(blocks) if matches!(
+                &blocks[1],
+                ContentBlock::ToolUse { id, name, input }
+                    if id == "call_1" && name == "bash" && input["command"] == "ls"
+            )
+        ));
+        assert_eq!(messages[3].role, MessageRole::Tool);
+
+        assert_eq!(to_openai_conversation(&messages).unwrap(), transcript);
+    }
+
+    #[test]
+    fn test_anthropic_round_trip_with_tool_call() {
+        let transcript = json!({
+            "system": "You are a helpful agent.",
+            "messages": [
+                {"role": "user", "content": "What's in the current directory?"},
+                {
+                    "role": "assistant",
+                    "content": [
+                        {"type": "text", "text": "Let me look."},
+                        {"type": "tool_use", "id": "toolu_1", "name": "bash", "input": {"command": "ls"}},
+                        {"type": "tool_use", "id": "toolu_2", "name": "bash", "input": {"command": "pwd"}}
+                    ]
+                },
+                {
+                    "role": "user",
+                    "content": [
+                        {"type": "tool_result", "tool_use_id": "toolu_1", "content": "Cargo.toml\nsrc"},
+                        {"type": "tool_result", "tool_use_id": "toolu_2", "content": "No such command", "is_error": true}
+                    ]
+                },
+                {"role": "assistant", "content": "A Cargo.toml and a src directory."}
+            ]
+        });
+
+        let messages = from_anthropic_conversation(&transcript).unwrap();
+        let roles: Vec<_> = messages.iter().map(|m| m.role.clone()).collect();
+        assert_eq!(
+            roles,
+            vec![
+                MessageRole::System,
+                MessageRole::User,
+                MessageRole::Assistant,
+                MessageRole::Tool,
+                MessageRole::Tool,
+                MessageRole::Assistant,
+            ]
+        );
+        assert!(matches!(
+            &messages[4].content,
+            MessageContent::MultiModal(blocks) if matches!(
+                &blocks[0],
+                ContentBlock::ToolResult { tool_use_id, is_error: Some(true), .. }
+                    if tool_use_id == "toolu_2"
+            )
+        ));
+
+        assert_eq!(to_anthropic_conversation(&messages).unwrap(), transcript);
+    }
+
+    #[test]
+    fn test_convert_between_provider_formats() {
+        let openai = json!([
+            {"role": "user", "content": "List files"},
+            {
+                "role": "assistant",
+                "content": null,
+                "tool_calls": [{
+                    "id": "call_1",
+                    "type": "function",
+                    "function": {"name": "bash", "arguments": "{\"command\":\"ls\"}"}
+                }]
+            },
+            {"role": "tool", "tool_call_id": "call_1", "content": "src"}
+        ]);
+
+        let messages = from_openai_conversation(&openai).unwrap();
+        let anthropic = to_anthropic_conversation(&messages).unwrap();
+        assert_eq!(
+            anthropic["messages"][1]["content"][0],
+            json!({"type": "tool_use", "id": "call_1", "name": "bash", "input": {"command": "ls"}})
+        );
+        assert_eq!(anthropic["messages"][2]["role"], "user");
+
+        let back = from_anthropic_conversation(&anthropic).unwrap();
+        assert_eq!(to_openai_conversation(&back).unwrap(), openai);
+        assert!(LlmMessage::system("Be brief").to_anthropic_json().is_err());
+    }
+}
@@ -0,0 +1,117 @@
+//! Metrics hooks for monitoring agent runs
+//!
+//! `AgentCore` reports counters and histograms through the [`Metrics`] trait.
+//! Implement it to forward values to your metrics system; the default
+//! [`NoopMetrics`] discards everything.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Metric names reported by the agent
+pub mod names {
+    /// Number of LLM requests sent
+    pub const LLM_CALLS: &str = "llm_calls";
+    /// Number of LLM requests that failed
+    pub const LLM_ERRORS: &str = "llm_errors";
+    /// Prompt tokens reported by the provider
+    pub const INPUT_TOKENS: &str = "llm_input_tokens";
+    /// Completion tokens reported by the provider
+    pub const OUTPUT_TOKENS: &str = "llm_output_tokens";
+    /// LLM request latency in milliseconds
+    pub const LLM_LATENCY_MS: &str = "llm_latency_ms";
+    /// Number of tool invocations
+    pub const TOOL_INVOCATIONS: &str = "tool_invocations";
+    /// Number of tool invocations that returned an error
+    pub const TOOL_ERRORS: &str = "tool_errors";
+    /// Number of executed steps
+    pub const STEPS: &str = "steps";
+    /// Step duration in milliseconds
+    pub const STEP_DURATION_MS: &str = "step_duration_ms";
+}
+
+/// Sink for counters and histograms
+pub trait Metrics: Send + Sync {
+    /// Increase a counter by `value`
+    fn incr_counter(&self, _name: &str, _value: u64) {}
+
+    /// Record one observation for a histogram
+    fn observe_histogram(&self, _name: &str, _value: f64) {}
+}
+
+/// Metrics implementation that discards everything
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoopMetrics;
+
+impl Metrics for NoopMetrics {}
+
+/// Metrics implementation that keeps values in memory, useful for tests
+#[derive(Debug, Default)]
+pub struct InMemoryMetrics {
+    counters: Mutex<HashMap<String, u64>>,
+    histograms: Mutex<HashMap<String, Vec<f64>>>,
+}
+
+impl InMemoryMetrics {
+    /// Create an empty metrics store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Current value of a counter (0 if never incremented)
+    pub fn counter(&self, name: &str) -> u64 {
+        self.counters
+            .lock()
+            .unwrap()
+            .get(name)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// All observations recorded for a histogram
+    pub fn histogram(&self, name: &str) -> Vec<f64> {
+        self.histograms
+            .lock()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+impl Metrics for InMemoryMetrics {
+    fn incr_counter(&self, name: &str, value: u64) {
+        *self
+            .counters
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_insert(0) += value;
+    }
+
+    fn observe_histogram(&self, name: &str, value: f64) {
+        self.histograms
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_default()
+            .push(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_in_memory_metrics_accumulates() {
+        let metrics = InMemoryMetrics::new();
+
+        metrics.incr_counter(names::LLM_CALLS, 1);
+        metrics.incr_counter(names::LLM_CALLS, 2);
+        metrics.observe_histogram(names::STEP_DURATION_MS, 12.5);
+
+        assert_eq!(metrics.counter(names::LLM_CALLS), 3);
+        assert_eq!(metrics.counter(names::TOOL_ERRORS), 0);
+        assert_eq!(metrics.histogram(names::STEP_DURATION_MS), vec![12.5]);
+    }
+}
@@ -0,0 +1,195 @@
+//! Forwarding output over async channels, for embedding in GUIs
+
+use super::{AgentEvent, AgentOutput, ConfirmationDecision, ConfirmationRequest, UserInputRequest};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, Mutex};
+
+/// Something the agent wants the embedding application to show or answer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputMessage {
+    /// An agent event, including plain messages
+    Event { event: AgentEvent },
+    /// Approval is needed; answer with [`OutputResponse::Confirmation`]
+    ConfirmationRequested { request: ConfirmationRequest },
+    /// The agent asks the user a question; answer with [`OutputResponse::UserInput`]
+    UserInputRequested { request: UserInputRequest },
+}
+
+/// The application's answer to a request sent as an [`OutputMessage`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputResponse {
+    /// Decision for the confirmation request with this id
+    Confirmation {
+        id: String,
+        decision: ConfirmationDecision,
+    },
+    /// Answer to the user input request with this id; `None` if the user declined
+    UserInput { id: String, answer: Option<String> },
+}
+
+/// Output handler that sends everything to a channel instead of a terminal
+///
+/// Requests wait for the matching [`OutputResponse`]; responses with another
+/// id are discarded as stale. If the application closes the response channel,
+/// confirmations are denied and questions go unanswered.
+pub struct ChannelOutput {
+    messages: mpsc::Sender<OutputMessage>,
+    responses: Mutex<mpsc::Receiver<OutputResponse>>,
+}
+
+impl ChannelOutput {
+    /// Create the handler, the receiver for its messages and the sender for answers
+    ///
+    /// `capacity` bounds how many messages may wait unread before the agent
+    /// waits for the application; 0 is treated as 1.
+    pub fn new(
+        capacity: usize,
+    ) -> (
+        Self,
+        mpsc::Receiver<OutputMessage>,
+        mpsc::Sender<OutputResponse>,
+    ) {
+        let capacity = capacity.max(1);
+        let (message_tx, message_rx) = mpsc::channel(capacity);
+        let (response_tx, response_rx) = mpsc::channel(capacity);
+        (
+            Self {
+                messages: message_tx,
+                responses: Mutex::new(response_rx),
+            },
+            message_rx,
+            response_tx,
+        )
+    }
+
+    async fn send(
+        &self,
+        message: OutputMessage,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.messages
+            .send(message)
+            .await
+            .map_err(|_| "output channel closed".into())
+    }
+}
+
+#[async_trait]
+impl AgentOutput for ChannelOutput {
+    async fn emit_event(
+        &self,
+        event: AgentEvent,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.send(OutputMessage::Event { event }).await
+    }
+
+    async fn request_confirmation(
+        &self,
+        request: &ConfirmationRequest,
+    ) -> Result<ConfirmationDecision, Box<dyn std::error::Error + Send + Sync>> {
+        // Hold the receiver so concurrent requests are answered in turn
+        let mut responses = self.responses.lock().await;
+        self.send(OutputMessage::ConfirmationRequested {
+            request: request.clone(),
+        })
+        .await?;
+
+        while let Some(response) = responses.recv().await {
+            if let OutputResponse::Confirmation { id, decision } = response {
+                if id == request.id {
+                    return Ok(decision);
+                }
+            }
+        }
+        Ok(ConfirmationDecision {
+            approved: false,
+            note: Some("Response channel closed; default deny".to_string()),
+            approved_ids: None,
+        })
+    }
+
+    async fn request_user_input(
+        &self,
+        request: &UserInputRequest,
+    ) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut responses = self.responses.lock().await;
+        self.send(OutputMessage::UserInputRequested {
+            request: request.clone(),
+        })
+        .await?;
+
+        while let Some(response) = responses.recv().await {
+            if let OutputResponse::UserInput { id, answer } = response {
+                if id == request.id {
+                    return Ok(answer);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn supports_realtime_updates(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::ConfirmationKind;
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn test_confirmation_waits_for_matching_response() {
+        let (output, mut messages, responses) = ChannelOutput::new(8);
+        let request = ConfirmationRequest {
+            id: "call_2".to_string(),
+            kind: ConfirmationKind::ToolExecution,
+            title: "Run bash".to_string(),
+            message: "rm -rf target".to_string(),
+            metadata: HashMap::new(),
+            tool: None,
+        };
+
+        let application = tokio::spawn(async move {
+            let Some(OutputMessage::ConfirmationRequested { request }) = messages.recv().await
+            else {
+                panic!("expected a confirmation request");
+            };
+            for (id, approved) in [("call_1", false), (request.id.as_str(), true)] {
+                responses
+                    .send(OutputResponse::Confirmation {
+                        id: id.to_string(),
+                        decision: ConfirmationDecision {
+                            approved,
+                            note: None,
+                            approved_ids: None,
+                        },
+                    })
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let decision = output.request_confirmation(&request).await.unwrap();
+        application.await.unwrap();
+        assert!(decision.approved);
+
+        // With the application gone, the request cannot be delivered
+        assert!(output.request_confirmation(&request).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_zero_capacity_still_delivers_messages() {
+        let (output, mut messages, _responses) = ChannelOutput::new(0);
+        output.normal("hello").await.unwrap();
+        assert!(matches!(
+            messages.recv().await,
+            Some(OutputMessage::Event {
+                event: AgentEvent::Message { .. }
+            })
+        ));
+    }
+}
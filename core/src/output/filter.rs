@@ -0,0 +1,238 @@
+//! Forwarding only selected events to an output handler
+
+use super::{
+    AgentEvent, AgentOutput, ConfirmationDecision, ConfirmationRequest, MessageLevel,
+    UserInputRequest,
+};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+/// Broad kind of an [`AgentEvent`], used to select events by tag
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EventCategory {
+    /// Execution and step lifecycle
+    Execution,
+    /// Tool execution, including requests for unknown tools
+    Tool,
+    /// Model reasoning
+    Thinking,
+    /// Questions put to the user
+    Interaction,
+    /// Progress, token usage and status updates
+    Progress,
+    /// Messages below error level
+    Message,
+    /// Conversation compression and prompt trimming
+    Compression,
+    /// Error messages, failed compression and model refusals
+    Error,
+}
+
+impl AgentEvent {
+    /// Category this event is tagged with
+    pub fn category(&self) -> EventCategory {
+        match self {
+            AgentEvent::ExecutionStarted { .. }
+            | AgentEvent::ExecutionCompleted { .. }
+            | AgentEvent::ExecutionInterrupted { .. }
+            | AgentEvent::StepStarted { .. }
+            | AgentEvent::StepCompleted { .. }
+            | AgentEvent::CommitCreated { .. } => EventCategory::Execution,
+            AgentEvent::ToolCallDelta { .. }
+            | AgentEvent::ToolExecutionStarted { .. }
+            | AgentEvent::ToolExecutionUpdated { .. }
+            | AgentEvent::ToolExecutionCompleted { .. }
+            | AgentEvent::UnknownToolRequested { .. } => EventCategory::Tool,
+            AgentEvent::AgentThinking { .. } => EventCategory::Thinking,
+            AgentEvent::UserInputRequested { .. } => EventCategory::Interaction,
+            AgentEvent::ProgressUpdate { .. }
+            | AgentEvent::TokenUsageUpdated { .. }
+            | AgentEvent::StatusUpdate { .. } => EventCategory::Progress,
+            AgentEvent::Message {
+                level: MessageLevel::Error,
+                ..
+            }
+            | AgentEvent::CompressionFailed { .. }
+            | AgentEvent::ModelRefused { .. } => EventCategory::Error,
+            AgentEvent::Message { .. } => EventCategory::Message,
+            AgentEvent::CompressionStarted { .. }
+            | AgentEvent::CompressionCompleted { .. }
+            | AgentEvent::CompressionSkipped { .. }
+            | AgentEvent::PromptTrimmed { .. }
+            | AgentEvent::ConversationReset { .. } => EventCategory::Compression,
+        }
+    }
+}
+
+/// Output handler that passes on only the events matching a predicate
+///
+/// Dropped events never reach the inner handler. Confirmation and user input
+/// requests are always forwarded.
+pub struct EventFilter {
+    inner: Box<dyn AgentOutput>,
+    predicate: Box<dyn Fn(&AgentEvent) -> bool + Send + Sync>,
+}
+
+impl EventFilter {
+    /// Forward to `inner` the events for which `predicate` returns true
+    pub fn new<F>(inner: Box<dyn AgentOutput>, predicate: F) -> Self
+    where
+        F: Fn(&AgentEvent) -> bool + Send + Sync + 'static,
+    {
+        Self {
+            inner,
+            predicate: Box::new(predicate),
+        }
+    }
+
+    /// Forward to `inner` the events tagged with one of `categories`
+    pub fn categories(
+        inner: Box<dyn AgentOutput>,
+        categories: impl IntoIterator<Item = EventCategory>,
+    ) -> Self {
+        let categories: Vec<EventCategory> = categories.into_iter().collect();
+        Self::new(inner, move |event| categories.contains(&event.category()))
+    }
+
+    /// Whether `event` would be forwarded
+    pub fn accepts(&self, event: &AgentEvent) -> bool {
+        (self.predicate)(event)
+    }
+}
+
+#[async_trait]
+impl AgentOutput for EventFilter {
+    async fn emit_event(
+        &self,
+        event: AgentEvent,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if self.accepts(&event) {
+            self.inner.emit_event(event).await
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn request_confirmation(
+        &self,
+        request: &ConfirmationRequest,
+    ) -> Result<ConfirmationDecision, Box<dyn std::error::Error + Send + Sync>> {
+        self.inner.request_confirmation(request).await
+    }
+
+    async fn request_user_input(
+        &self,
+        request: &UserInputRequest,
+    ) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+        self.inner.request_user_input(request).await
+    }
+
+    fn supports_realtime_updates(&self) -> bool {
+        self.inner.supports_realtime_updates()
+    }
+
+    async fn flush(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.inner.flush().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{
+        TokenUsage, ToolExecutionInfo, ToolExecutionInfoBuilder, ToolExecutionStatus,
+    };
+    use crate::tools::{ToolCall, ToolResult};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct RecordingOutput {
+        events: Arc<Mutex<Vec<AgentEvent>>>,
+    }
+
+    #[async_trait]
+    impl AgentOutput for RecordingOutput {
+        async fn emit_event(
+            &self,
+            event: AgentEvent,
+        ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            self.events.lock().unwrap().push(event);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tool_event_filter_drops_other_events() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let inner = RecordingOutput {
+            events: events.clone(),
+        };
+        let filter = EventFilter::new(Box::new(inner), |event| {
+            matches!(
+                event,
+                AgentEvent::ToolExecutionStarted { .. }
+                    | AgentEvent::ToolExecutionUpdated { .. }
+                    | AgentEvent::ToolExecutionCompleted { .. }
+            )
+        });
+
+        let call = ToolCall::new("bash", serde_json::json!({ "command": "ls" }));
+        let result = ToolResult::success(call.id.as_str(), "Cargo.toml");
+        filter
+            .emit_event(AgentEvent::ToolExecutionStarted {
+                tool_info: ToolExecutionInfo::create_tool_execution_info(
+                    &call,
+                    ToolExecutionStatus::Executing,
+                    None,
+                ),
+            })
+            .await
+            .unwrap();
+        filter.info("listing files").await.unwrap();
+        filter.error("disk on fire").await.unwrap();
+        filter
+            .emit_token_update(TokenUsage::default())
+            .await
+            .unwrap();
+        filter.emit_status_update("working").await.unwrap();
+        filter
+            .emit_event(AgentEvent::UnknownToolRequested {
+                name: "nope".to_string(),
+            })
+            .await
+            .unwrap();
+        filter
+            .emit_event(AgentEvent::ToolExecutionCompleted {
+                tool_info: ToolExecutionInfo::create_tool_execution_info(
+                    &call,
+                    ToolExecutionStatus::Success,
+                    Some(&result),
+                ),
+            })
+            .await
+            .unwrap();
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events[0], AgentEvent::ToolExecutionStarted { .. }));
+        assert!(matches!(
+            events[1],
+            AgentEvent::ToolExecutionCompleted { .. }
+        ));
+    }
+
+    #[test]
+    fn test_error_messages_are_tagged_as_errors() {
+        let message = |level| AgentEvent::Message {
+            level,
+            content: String::new(),
+            metadata: Default::default(),
+        };
+        let filter =
+            EventFilter::categories(Box::new(RecordingOutput::default()), [EventCategory::Error]);
+
+        assert!(filter.accepts(&message(MessageLevel::Error)));
+        assert!(!filter.accepts(&message(MessageLevel::Warning)));
+        assert!(!filter.accepts(&AgentEvent::ProgressUpdate { fraction: 0.5 }));
+    }
+}
@@ -0,0 +1,71 @@
+//! Appending agent events to a log file
+
+use super::{AgentEvent, AgentOutput};
+use async_trait::async_trait;
+use std::path::Path;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+/// How events are written to a log file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// One JSON-serialized event per line
+    Json,
+    /// One human-readable line per event
+    Text,
+}
+
+/// Output handler that appends each event to a file, one line per event
+///
+/// Requests are not answered, so pair it with an interactive handler through
+/// [`super::TeeOutput`] when confirmations are needed.
+pub struct FileLogOutput {
+    file: Mutex<File>,
+    format: LogFormat,
+}
+
+impl FileLogOutput {
+    /// Open `path` for appending, creating it if needed
+    pub async fn open(path: impl AsRef<Path>, format: LogFormat) -> std::io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        Ok(Self {
+            file: Mutex::new(file),
+            format,
+        })
+    }
+
+    fn format_line(&self, event: &AgentEvent) -> Result<String, serde_json::Error> {
+        let line = match self.format {
+            LogFormat::Json => serde_json::to_string(event)?,
+            LogFormat::Text => match event {
+                AgentEvent::Message { level, content, .. } => {
+                    format!("[{:?}] {:?}: {}", event.category(), level, content)
+                }
+                _ => format!("[{:?}] {:?}", event.category(), event),
+            },
+        };
+        Ok(line + "\n")
+    }
+}
+
+#[async_trait]
+impl AgentOutput for FileLogOutput {
+    async fn emit_event(
+        &self,
+        event: AgentEvent,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let line = self.format_line(&event)?;
+        self.file.lock().await.write_all(line.as_bytes()).await?;
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.file.lock().await.flush().await?;
+        Ok(())
+    }
+}
@@ -3,11 +3,22 @@
 //! This module provides an abstract interface for outputting agent execution information,
 //! allowing different implementations for CLI, API, logging, etc.
 
-use crate::tools::{ToolCall, ToolResult};
+use crate::llm::FinishReason;
+use crate::tools::{ToolCall, ToolKind, ToolResult};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+mod channel;
+mod filter;
+mod log;
+mod tee;
+
+pub use channel::{ChannelOutput, OutputMessage, OutputResponse};
+pub use filter::{EventCategory, EventFilter};
+pub use log::{FileLogOutput, LogFormat};
+pub use tee::TeeOutput;
+
 // Core only provides abstractions - implementations are in calling modules
 
 /// Null output handler that discards all events (useful for testing and backward compatibility)
@@ -44,6 +55,9 @@ pub enum ToolExecutionStatus {
 pub enum ConfirmationKind {
     /// Confirm before executing a tool
     ToolExecution,
+    /// Confirm several tool calls from one step at once; `metadata["tool_calls"]`
+    /// lists them
+    ToolBatch,
 }
 
 /// A generic confirmation request that UI/API layers can handle
@@ -59,6 +73,61 @@ pub struct ConfirmationRequest {
     pub message: String,
     /// Arbitrary metadata (e.g., tool_name, parameters)
     pub metadata: HashMap<String, serde_json::Value>,
+    /// The tool call awaiting confirmation, for `ToolExecution` requests
+    #[serde(default)]
+    pub tool: Option<ToolCallPreview>,
+}
+
+/// Typed description of a tool call, so handlers can show a readable preview
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCallPreview {
+    /// Name of the tool being called
+    pub tool_name: String,
+    /// Resources the tool uses
+    pub kind: ToolKind,
+    /// One-line human summary, e.g. the full shell command
+    pub summary: String,
+    /// The complete call parameters
+    pub parameters: serde_json::Value,
+}
+
+impl ToolCallPreview {
+    /// Describe a call, deriving the summary from its parameters
+    pub fn new(
+        tool_name: impl Into<String>,
+        kind: ToolKind,
+        parameters: serde_json::Value,
+    ) -> Self {
+        let summary = Self::summarize(&parameters);
+        Self {
+            tool_name: tool_name.into(),
+            kind,
+            summary,
+            parameters,
+        }
+    }
+
+    /// Prefer the `command` and `path` parameters most tools take; anything
+    /// else is listed as compact `key=value` pairs
+    pub fn summarize(parameters: &serde_json::Value) -> String {
+        let field = |key: &str| parameters.get(key).and_then(|value| value.as_str());
+        match (field("command"), field("path")) {
+            (Some(command), Some(path)) => format!("{} {}", command, path),
+            (Some(command), None) => command.to_string(),
+            (None, Some(path)) => path.to_string(),
+            (None, None) => match parameters.as_object() {
+                Some(object) => object
+                    .iter()
+                    .map(|(key, value)| match value {
+                        serde_json::Value::String(text) => format!("{}={}", key, text),
+                        other => format!("{}={}", key, other),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                None => parameters.to_string(),
+            },
+        }
+    }
 }
 
 /// User/consumer decision for a confirmation request
@@ -68,6 +137,29 @@ pub struct ConfirmationDecision {
     pub approved: bool,
     /// Optional note/reason
     pub note: Option<String>,
+    /// For batched requests, the execution ids of the calls approved
+    /// individually; when `None`, `approved` applies to every call in the batch
+    #[serde(default)]
+    pub approved_ids: Option<Vec<String>>,
+}
+
+impl ConfirmationDecision {
+    /// Whether the tool call with `execution_id` may run under this decision
+    pub fn approves(&self, execution_id: &str) -> bool {
+        match &self.approved_ids {
+            Some(ids) => ids.iter().any(|approved| approved == execution_id),
+            None => self.approved,
+        }
+    }
+}
+
+/// A question the agent asks the user through the `ask_user` tool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserInputRequest {
+    /// Request identifier (the id of the tool call asking)
+    pub id: String,
+    /// The question to show the user
+    pub question: String,
 }
 
 /// Rich tool execution information
@@ -89,6 +181,29 @@ pub struct ToolExecutionInfo {
     pub metadata: HashMap<String, serde_json::Value>,
 }
 
+/// Metadata key carrying the provider's tool call id on a [`ToolExecutionInfo`]
+pub const TOOL_CALL_ID_METADATA_KEY: &str = "tool_call_id";
+
+impl ToolExecutionInfo {
+    /// Internal execution id for the `index`-th tool call of `step` in the agent's `task`-th task
+    ///
+    /// Unlike provider tool call ids, these are always present and never
+    /// repeat for the lifetime of an agent.
+    pub fn execution_id_for(task: usize, step: usize, index: usize) -> String {
+        format!("task-{}-step-{}-{}", task, step, index)
+    }
+
+    /// Replace the execution id, keeping the provider's tool call id in metadata
+    pub fn with_execution_id(mut self, execution_id: impl Into<String>) -> Self {
+        let tool_call_id = std::mem::replace(&mut self.execution_id, execution_id.into());
+        self.metadata.insert(
+            TOOL_CALL_ID_METADATA_KEY.to_string(),
+            serde_json::Value::String(tool_call_id),
+        );
+        self
+    }
+}
+
 /// Agent execution step information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentStepInfo {
@@ -113,6 +228,10 @@ pub struct TokenUsage {
     pub output_tokens: u32,
     /// Total tokens (input + output)
     pub total_tokens: u32,
+    /// Whether any of the counts are local estimates because the provider
+    /// did not report usage
+    #[serde(default)]
+    pub estimated: bool,
 }
 
 /// Agent execution context information
@@ -134,6 +253,36 @@ pub struct AgentExecutionContext {
     pub execution_time: std::time::Duration,
     /// Token usage statistics
     pub token_usage: TokenUsage,
+    /// Estimated fraction of the current task completed (0.0 to 1.0)
+    #[serde(default)]
+    pub progress: f64,
+    /// How often each compression level was applied during the current task
+    #[serde(default)]
+    pub compression_stats: HashMap<crate::agent::CompressionLevel, usize>,
+}
+
+impl AgentExecutionContext {
+    /// Fraction of progress assumed once the agent looks close to calling `task_done`
+    pub const NEAR_COMPLETION_PROGRESS: f64 = 0.9;
+
+    /// Estimate how far the current task has progressed
+    ///
+    /// Based on `current_step / max_steps`, raised to
+    /// [`Self::NEAR_COMPLETION_PROGRESS`] when `near_completion` is set. The
+    /// estimate never drops below the previously reported progress and stays
+    /// below 1.0 until the task is actually completed.
+    pub fn estimate_progress(&self, near_completion: bool) -> f64 {
+        if self.max_steps == 0 {
+            return self.progress;
+        }
+
+        let mut estimate = self.current_step as f64 / self.max_steps as f64;
+        if near_completion {
+            estimate = estimate.max(Self::NEAR_COMPLETION_PROGRESS);
+        }
+
+        estimate.min(0.99).max(self.progress)
+    }
 }
 
 /// Events that can be emitted during agent execution
@@ -156,6 +305,15 @@ pub enum AgentEvent {
     StepStarted { step_info: AgentStepInfo },
     /// Step completed
     StepCompleted { step_info: AgentStepInfo },
+    /// Arguments of a tool call streamed in so far, before it runs
+    ///
+    /// `partial_input` is the raw argument text received until now and may not
+    /// be valid JSON yet; `ToolExecutionStarted` for the same execution id
+    /// carries the complete input.
+    ToolCallDelta {
+        execution_id: String,
+        partial_input: String,
+    },
     /// Tool execution started
     ToolExecutionStarted { tool_info: ToolExecutionInfo },
     /// Tool execution status updated
@@ -167,6 +325,12 @@ pub enum AgentEvent {
         step_number: usize,
         thinking: String,
     },
+    /// The model called a tool that is not available
+    UnknownToolRequested { name: String },
+    /// The agent asked the user a question and is waiting for the answer
+    UserInputRequested { question: String },
+    /// Estimated task progress updated (emitted after each step)
+    ProgressUpdate { fraction: f64 },
     /// Token usage updated (emitted after each LLM call)
     TokenUsageUpdated { token_usage: TokenUsage },
     /// Agent status update (for interactive mode status reporting)
@@ -194,11 +358,39 @@ pub enum AgentEvent {
         messages_before: u32,
         messages_after: u32,
     },
+    /// Compression was evaluated but the conversation is under every threshold
+    CompressionSkipped {
+        estimated_tokens: u32,
+        threshold: u32,
+    },
     /// Conversation compression failed
     CompressionFailed {
         error: String,
         fallback_action: String,
     },
+    /// The prompt exceeded the context window budget and was compressed or
+    /// had tool results truncated before sending
+    PromptTrimmed {
+        tokens_before: u32,
+        tokens_after: u32,
+        max_tokens: u32,
+        truncated_results: usize,
+    },
+    /// The model refused to respond or its response was blocked by a content
+    /// filter; `message` is any explanation it gave
+    ModelRefused {
+        reason: FinishReason,
+        message: Option<String>,
+    },
+    /// Files changed during the task were committed to git
+    CommitCreated { hash: String },
+    /// The conversation reached its task or token limit and was replaced by
+    /// a summary before the next task
+    ConversationReset {
+        tasks: usize,
+        tokens_used: u32,
+        summary: String,
+    },
 }
 
 /// Message severity levels
@@ -289,9 +481,20 @@ pub trait AgentOutput: Send + Sync {
         Ok(ConfirmationDecision {
             approved: false,
             note: Some("No confirmation handler available; default deny".to_string()),
+            approved_ids: None,
         })
     }
 
+    /// Ask the user a question on behalf of the agent
+    /// Default: `None`, meaning no user can answer (e.g. headless runs).
+    /// Interactive outputs should override to prompt.
+    async fn request_user_input(
+        &self,
+        _request: &UserInputRequest,
+    ) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(None)
+    }
+
     /// Check if this output handler supports real-time updates
     fn supports_realtime_updates(&self) -> bool {
         false
@@ -303,6 +506,34 @@ pub trait AgentOutput: Send + Sync {
     }
 }
 
+/// Rewrites assistant text before it is shown to the user
+///
+/// Only the surfaced text is affected; the conversation history sent back to
+/// the model keeps the original response.
+pub trait AssistantTextTransformer: Send + Sync {
+    /// Return the text to display in place of `text`
+    fn transform(&self, text: &str) -> String;
+}
+
+/// Transformer that leaves text unchanged
+#[derive(Debug, Default, Clone, Copy)]
+pub struct IdentityTransformer;
+
+impl AssistantTextTransformer for IdentityTransformer {
+    fn transform(&self, text: &str) -> String {
+        text.to_string()
+    }
+}
+
+impl<F> AssistantTextTransformer for F
+where
+    F: Fn(&str) -> String + Send + Sync,
+{
+    fn transform(&self, text: &str) -> String {
+        self(text)
+    }
+}
+
 /// Helper trait for creating tool execution info
 pub trait ToolExecutionInfoBuilder {
     fn create_tool_execution_info(
@@ -0,0 +1,91 @@
+//! Sending output to several handlers at once
+
+use super::{AgentEvent, AgentOutput, ConfirmationDecision, ConfirmationRequest, UserInputRequest};
+use async_trait::async_trait;
+
+/// Output handler that forwards every event to each of its handlers in order
+///
+/// Confirmation and user input requests are answered by the first handler
+/// only, which is normally the interactive one. An event that a handler fails
+/// to take is still passed on to the others; the first failure is returned.
+pub struct TeeOutput {
+    handlers: Vec<Box<dyn AgentOutput>>,
+}
+
+impl TeeOutput {
+    /// Forward to `handlers`; the first one answers requests
+    pub fn new(handlers: Vec<Box<dyn AgentOutput>>) -> Self {
+        Self { handlers }
+    }
+
+    /// Add a handler after the existing ones
+    pub fn push(&mut self, handler: Box<dyn AgentOutput>) {
+        self.handlers.push(handler);
+    }
+
+    /// Number of handlers events are forwarded to
+    pub fn len(&self) -> usize {
+        self.handlers.len()
+    }
+
+    /// Whether there are no handlers
+    pub fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+}
+
+#[async_trait]
+impl AgentOutput for TeeOutput {
+    async fn emit_event(
+        &self,
+        event: AgentEvent,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut first_error = None;
+        for handler in &self.handlers {
+            if let Err(e) = handler.emit_event(event.clone()).await {
+                first_error.get_or_insert(e);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    async fn request_confirmation(
+        &self,
+        request: &ConfirmationRequest,
+    ) -> Result<ConfirmationDecision, Box<dyn std::error::Error + Send + Sync>> {
+        match self.handlers.first() {
+            Some(handler) => handler.request_confirmation(request).await,
+            None => Ok(ConfirmationDecision {
+                approved: false,
+                note: Some("No confirmation handler available; default deny".to_string()),
+                approved_ids: None,
+            }),
+        }
+    }
+
+    async fn request_user_input(
+        &self,
+        request: &UserInputRequest,
+    ) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+        match self.handlers.first() {
+            Some(handler) => handler.request_user_input(request).await,
+            None => Ok(None),
+        }
+    }
+
+    fn supports_realtime_updates(&self) -> bool {
+        self.handlers
+            .iter()
+            .any(|handler| handler.supports_realtime_updates())
+    }
+
+    async fn flush(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut first_error = None;
+        for handler in &self.handlers {
+            if let Err(e) = handler.flush().await {
+                first_error.get_or_insert(e);
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+}
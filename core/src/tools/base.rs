@@ -26,12 +26,63 @@ pub trait Tool: Send + Sync {
         false
     }
 
+    /// What the tool needs access to
+    fn kind(&self) -> ToolKind {
+        ToolKind::Local
+    }
+
+    /// Expected size in bytes of the output of `call`, if known before running it
+    ///
+    /// Lets the agent compress older history before a large result arrives.
+    fn estimated_output_size(&self, _call: &ToolCall) -> Option<usize> {
+        None
+    }
+
+    /// Whether `call` only reads state, so repeating it returns the same
+    /// result unless something else changed in between
+    fn is_read_only(&self, _call: &ToolCall) -> bool {
+        false
+    }
+
+    /// Whether `call` changes the file named by its `path` parameter
+    ///
+    /// Lets the agent track the files a task edited or created.
+    fn edits_file(&self, _call: &ToolCall) -> bool {
+        false
+    }
+
     /// Get examples of how to use this tool
     fn examples(&self) -> Vec<ToolExample> {
         Vec::new()
     }
 }
 
+/// Broad classification of the resources a tool uses
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolKind {
+    /// Works on the local machine only
+    #[default]
+    Local,
+    /// Reaches out over the network
+    Network,
+}
+
+/// How a tool result's content should be rendered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolContentType {
+    /// Unformatted text
+    #[default]
+    PlainText,
+    /// Markdown
+    Markdown,
+    /// A JSON document
+    Json,
+    /// A unified diff
+    Diff,
+}
+
 /// A call to a tool
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolCall {
@@ -48,6 +99,13 @@ pub struct ToolCall {
     pub metadata: Option<HashMap<String, serde_json::Value>>,
 }
 
+/// Metadata key on a [`ToolResult`] reused for a repeated call, naming the
+/// call that actually ran
+pub const DUPLICATE_OF_METADATA_KEY: &str = "duplicate_of";
+
+/// Key in [`ToolResult::data`] holding the URL the result's content came from
+pub const SOURCE_URL_DATA_KEY: &str = "source_url";
+
 /// Result of a tool execution
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolResult {
@@ -68,6 +126,10 @@ pub struct ToolResult {
 
     /// Optional metadata
     pub metadata: Option<HashMap<String, serde_json::Value>>,
+
+    /// How the content should be rendered
+    #[serde(default)]
+    pub content_type: ToolContentType,
 }
 
 /// Example usage of a tool
@@ -86,6 +148,7 @@ pub struct ToolExample {
 /// Tool executor that manages tool execution
 pub struct ToolExecutor {
     tools: HashMap<String, Box<dyn Tool>>,
+    allow_network: bool,
 }
 
 impl ToolCall {
@@ -138,6 +201,7 @@ impl ToolResult {
             data: None,
             duration_ms: None,
             metadata: None,
+            content_type: ToolContentType::PlainText,
         }
     }
 
@@ -150,6 +214,7 @@ impl ToolResult {
             data: None,
             duration_ms: None,
             metadata: None,
+            content_type: ToolContentType::PlainText,
         }
     }
 
@@ -159,6 +224,11 @@ impl ToolResult {
         self
     }
 
+    /// URL the content came from, for tools that fetch from the web
+    pub fn source_url(&self) -> Option<&str> {
+        self.data.as_ref()?.get(SOURCE_URL_DATA_KEY)?.as_str()
+    }
+
     /// Set execution duration
     pub fn with_duration(mut self, duration_ms: u64) -> Self {
         self.duration_ms = Some(duration_ms);
@@ -170,6 +240,12 @@ impl ToolResult {
         self.metadata = Some(metadata);
         self
     }
+
+    /// Set how the content should be rendered
+    pub fn with_content_type(mut self, content_type: ToolContentType) -> Self {
+        self.content_type = content_type;
+        self
+    }
 }
 
 impl ToolExecutor {
@@ -177,6 +253,7 @@ impl ToolExecutor {
     pub fn new() -> Self {
         Self {
             tools: HashMap::new(),
+            allow_network: true,
         }
     }
 
@@ -185,6 +262,23 @@ impl ToolExecutor {
         self.tools.insert(tool.name().to_string(), tool);
     }
 
+    /// Remove a tool by name, returning it if it was registered
+    pub fn remove_tool(&mut self, name: &str) -> Option<Box<dyn Tool>> {
+        self.tools.remove(name)
+    }
+
+    /// Allow or refuse tools classified as [`ToolKind::Network`]
+    ///
+    /// Refused tools are also left out of the tool definitions.
+    pub fn set_allow_network(&mut self, allow_network: bool) {
+        self.allow_network = allow_network;
+    }
+
+    /// Whether a tool may run under the current network policy
+    fn is_allowed(&self, tool: &dyn Tool) -> bool {
+        self.allow_network || tool.kind() != ToolKind::Network
+    }
+
     /// Get a tool by name
     pub fn get_tool(&self, name: &str) -> Option<&dyn Tool> {
         self.tools.get(name).map(|t| t.as_ref())
@@ -202,6 +296,12 @@ impl ToolExecutor {
             .ok_or_else(|| ToolError::NotFound {
                 name: call.name.clone(),
             })?;
+        if !self.is_allowed(tool) {
+            return Err(ToolError::NetworkDisabled {
+                name: call.name.clone(),
+            }
+            .into());
+        }
 
         let start_time = std::time::Instant::now();
         let call_id = call.id.clone();
@@ -221,6 +321,7 @@ impl ToolExecutor {
     pub fn get_tool_definitions(&self) -> Vec<crate::llm::ToolDefinition> {
         self.tools
             .values()
+            .filter(|tool| self.is_allowed(tool.as_ref()))
             .map(|tool| crate::llm::ToolDefinition {
                 tool_type: "function".to_string(),
                 function: crate::llm::FunctionDefinition {
@@ -238,3 +339,26 @@ impl Default for ToolExecutor {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_type_defaults_to_plain_text() {
+        let result: ToolResult = serde_json::from_value(serde_json::json!({
+            "tool_call_id": "call_1",
+            "success": true,
+            "content": "{\"ok\": true}",
+            "data": null,
+            "duration_ms": null,
+            "metadata": null
+        }))
+        .unwrap();
+        assert_eq!(result.content_type, ToolContentType::PlainText);
+
+        let result = result.with_content_type(ToolContentType::Json);
+        let value = serde_json::to_value(&result).unwrap();
+        assert_eq!(value["content_type"], "json");
+    }
+}
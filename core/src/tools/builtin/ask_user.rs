@@ -0,0 +1,85 @@
+//! Tool for asking the user a clarifying question
+
+use crate::error::Result;
+use crate::impl_tool_factory;
+use crate::tools::{Tool, ToolCall, ToolExample, ToolResult};
+use async_trait::async_trait;
+use serde_json::json;
+
+/// Name of the ask-user tool
+pub const ASK_USER_TOOL_NAME: &str = "ask_user";
+
+/// Tool result when nobody can answer, e.g. in headless runs
+pub const NO_USER_AVAILABLE: &str =
+    "No user is available to answer questions; continue with your best judgement";
+
+/// Tool for asking the user a question mid-task
+///
+/// The agent answers calls to this tool itself, through
+/// `AgentOutput::request_user_input`; executing it directly means no user is
+/// reachable.
+pub struct AskUserTool;
+
+impl AskUserTool {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl Tool for AskUserTool {
+    fn name(&self) -> &str {
+        ASK_USER_TOOL_NAME
+    }
+
+    fn description(&self) -> &str {
+        "Ask the user a question and wait for their answer. Use this only when \
+         you cannot proceed without clarification; the answer is returned as the \
+         tool result."
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "question": {
+                    "type": "string",
+                    "description": "The question to ask, phrased so it can be answered directly"
+                }
+            },
+            "required": ["question"]
+        })
+    }
+
+    async fn execute(&self, call: ToolCall) -> Result<ToolResult> {
+        let _question: String = call.get_parameter("question")?;
+
+        Ok(ToolResult::error(
+            call.id.clone(),
+            NO_USER_AVAILABLE.to_string(),
+        ))
+    }
+
+    fn examples(&self) -> Vec<ToolExample> {
+        vec![ToolExample {
+            description: "Clarify an ambiguous requirement".to_string(),
+            parameters: json!({
+                "question": "Should the new endpoint require authentication?"
+            }),
+            expected_result: "The user's answer".to_string(),
+        }]
+    }
+}
+
+impl Default for AskUserTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl_tool_factory!(
+    AskUserToolFactory,
+    AskUserTool,
+    ASK_USER_TOOL_NAME,
+    "Ask the user a clarifying question"
+);
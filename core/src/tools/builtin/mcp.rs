@@ -2,7 +2,7 @@
 
 use crate::error::Result;
 use crate::impl_tool_factory;
-use crate::tools::{Tool, ToolCall, ToolExample, ToolResult};
+use crate::tools::{Tool, ToolCall, ToolExample, ToolKind, ToolResult};
 use async_trait::async_trait;
 use serde_json::{json, Value};
 use std::collections::HashMap;
@@ -293,6 +293,11 @@ impl Tool for McpTool {
         })
     }
 
+    fn kind(&self) -> ToolKind {
+        // MCP servers are external processes that typically call remote services
+        ToolKind::Network
+    }
+
     async fn execute(&self, call: ToolCall) -> Result<ToolResult> {
         let operation: String = call.get_parameter("operation")?;
 
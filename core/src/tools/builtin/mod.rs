@@ -1,9 +1,15 @@
 //! Built-in tools
 
+pub mod ask_user;
 pub mod mcp;
+pub mod scratchpad;
+pub mod sub_agent;
 pub mod task_done;
 pub mod thinking;
 
+pub use ask_user::{AskUserTool, AskUserToolFactory};
 pub use mcp::{McpTool, McpToolFactory};
+pub use scratchpad::{Scratchpad, ScratchpadTool, ScratchpadToolFactory};
+pub use sub_agent::{SubAgentTool, SubAgentToolFactory};
 pub use task_done::{TaskDoneTool, TaskDoneToolFactory};
 pub use thinking::{ThinkingTool, ThinkingToolFactory};
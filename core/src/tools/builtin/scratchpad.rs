@@ -0,0 +1,166 @@
+//! Key-value scratchpad the model can use to keep notes during a task
+
+use crate::error::{Result, ToolError};
+use crate::impl_tool_factory;
+use crate::tools::{Tool, ToolCall, ToolExample, ToolResult};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+/// Name of the scratchpad tool
+pub const SCRATCHPAD_TOOL_NAME: &str = "scratchpad";
+
+/// Notes kept by the agent for the model, e.g. decisions already taken
+///
+/// It lives on the agent rather than in the conversation, and is part of
+/// exported context snapshots.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Scratchpad {
+    entries: BTreeMap<String, String>,
+}
+
+impl Scratchpad {
+    /// Value stored under `key`
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.get(key).map(String::as_str)
+    }
+
+    /// Store `value` under `key`, replacing any previous value
+    pub fn set(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.entries.insert(key.into(), value.into());
+    }
+
+    /// All entries, ordered by key
+    pub fn entries(&self) -> &BTreeMap<String, String> {
+        &self.entries
+    }
+
+    /// Whether no entries are stored
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Carry out a `scratchpad` tool call against this scratchpad
+    pub fn apply(&mut self, call: &ToolCall) -> Result<ToolResult> {
+        let action: String = call.get_parameter("action")?;
+        match action.as_str() {
+            "set" => {
+                let key: String = call.get_parameter("key")?;
+                let value: String = call.get_parameter("value")?;
+                let message = format!("Saved '{}'", key);
+                self.set(key, value);
+                Ok(ToolResult::success(call.id.clone(), message))
+            }
+            "get" => {
+                let key: String = call.get_parameter("key")?;
+                Ok(match self.get(&key) {
+                    Some(value) => ToolResult::success(call.id.clone(), value.to_string()),
+                    None => ToolResult::error(
+                        call.id.clone(),
+                        format!("No scratchpad entry named '{}'", key),
+                    ),
+                })
+            }
+            "list" => {
+                let listing = if self.is_empty() {
+                    "The scratchpad is empty".to_string()
+                } else {
+                    self.entries
+                        .iter()
+                        .map(|(key, value)| format!("{}: {}", key, value))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                };
+                Ok(ToolResult::success(call.id.clone(), listing))
+            }
+            other => Err(ToolError::InvalidParameters {
+                message: format!(
+                    "Unknown scratchpad action '{}'; expected set, get or list",
+                    other
+                ),
+            }
+            .into()),
+        }
+    }
+}
+
+/// Tool giving the model a small key-value store for the task
+///
+/// The agent answers calls to this tool itself from its [`Scratchpad`];
+/// executing it directly means no agent is keeping the notes.
+pub struct ScratchpadTool;
+
+impl ScratchpadTool {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl Tool for ScratchpadTool {
+    fn name(&self) -> &str {
+        SCRATCHPAD_TOOL_NAME
+    }
+
+    fn description(&self) -> &str {
+        "Keep short notes for the rest of the task, such as decisions you have \
+         made or facts you found. Use `set` to save a value under a key, `get` \
+         to read one back and `list` to see all notes."
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "action": {
+                    "type": "string",
+                    "enum": ["set", "get", "list"],
+                    "description": "What to do with the scratchpad"
+                },
+                "key": {
+                    "type": "string",
+                    "description": "Name of the note (for set and get)"
+                },
+                "value": {
+                    "type": "string",
+                    "description": "Content of the note (for set)"
+                }
+            },
+            "required": ["action"]
+        })
+    }
+
+    async fn execute(&self, call: ToolCall) -> Result<ToolResult> {
+        Ok(ToolResult::error(
+            call.id.clone(),
+            "The scratchpad is only available while running inside an agent".to_string(),
+        ))
+    }
+
+    fn examples(&self) -> Vec<ToolExample> {
+        vec![ToolExample {
+            description: "Record a design decision".to_string(),
+            parameters: json!({
+                "action": "set",
+                "key": "approach",
+                "value": "B: extend the existing parser instead of writing a new one"
+            }),
+            expected_result: "Saved 'approach'".to_string(),
+        }]
+    }
+}
+
+impl Default for ScratchpadTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl_tool_factory!(
+    ScratchpadToolFactory,
+    ScratchpadTool,
+    SCRATCHPAD_TOOL_NAME,
+    "Key-value notes kept for the rest of the task"
+);
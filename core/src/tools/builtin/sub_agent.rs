@@ -0,0 +1,323 @@
+//! Sub-agent delegation tool
+
+use crate::agent::{AgentConfig, AgentCore};
+use crate::error::Result;
+use crate::llm::LlmClient;
+use crate::output::events::NullOutput;
+use crate::tools::utils::ignore::PROJECT_ROOT_METADATA_KEY;
+use crate::tools::{Tool, ToolCall, ToolExample, ToolFactory, ToolRegistry, ToolResult};
+use async_trait::async_trait;
+use serde_json::json;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Name of the sub-agent tool
+pub const SUB_AGENT_TOOL_NAME: &str = "sub_agent";
+
+/// Tool that hands a focused subtask to a child agent
+///
+/// The child shares the parent's LLM client, runs with its own step budget
+/// and a subset of the parent's tools, and reports back its final message.
+pub struct SubAgentTool {
+    llm_client: Arc<dyn LlmClient>,
+    parent_config: AgentConfig,
+    tool_registry: Arc<ToolRegistry>,
+    depth: usize,
+}
+
+impl SubAgentTool {
+    /// Create the tool for an agent at nesting `depth` (0 for the top-level agent)
+    pub fn new(
+        llm_client: Arc<dyn LlmClient>,
+        parent_config: AgentConfig,
+        tool_registry: Arc<ToolRegistry>,
+        depth: usize,
+    ) -> Self {
+        Self {
+            llm_client,
+            parent_config,
+            tool_registry,
+            depth,
+        }
+    }
+
+    /// Configuration for the child agent, limited to the parent's tools and step budget
+    fn child_config(&self, call: &ToolCall) -> AgentConfig {
+        let mut config = self.parent_config.clone();
+
+        if let Ok(max_steps) = call.get_parameter::<usize>("max_steps") {
+            config.max_steps = max_steps.clamp(1, self.parent_config.max_steps);
+        }
+        if let Ok(tools) = call.get_parameter::<Vec<String>>("tools") {
+            config.tools = tools
+                .into_iter()
+                .filter(|tool| self.parent_config.tools.contains(tool))
+                .collect();
+        }
+        config.tools.retain(|tool| tool != SUB_AGENT_TOOL_NAME);
+        if !config.tools.iter().any(|tool| tool == "task_done") {
+            config.tools.push("task_done".to_string());
+        }
+
+        config
+    }
+}
+
+#[async_trait]
+impl Tool for SubAgentTool {
+    fn name(&self) -> &str {
+        SUB_AGENT_TOOL_NAME
+    }
+
+    fn description(&self) -> &str {
+        "Delegate a focused subtask to a sub-agent. The sub-agent works \
+         independently with its own step budget and returns its final message. \
+         Give it a self-contained task description, since it cannot see this conversation."
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "task": {
+                    "type": "string",
+                    "description": "Self-contained description of the subtask"
+                },
+                "tools": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Optional subset of your tools the sub-agent may use"
+                },
+                "max_steps": {
+                    "type": "integer",
+                    "description": "Optional step budget for the sub-agent"
+                }
+            },
+            "required": ["task"]
+        })
+    }
+
+    async fn execute(&self, call: ToolCall) -> Result<ToolResult> {
+        let task: String = call.get_parameter("task")?;
+
+        let max_depth = self.parent_config.max_delegation_depth;
+        if self.depth >= max_depth {
+            return Ok(ToolResult::error(
+                call.id.clone(),
+                format!(
+                    "Maximum delegation depth ({}) reached; complete the subtask directly",
+                    max_depth
+                ),
+            ));
+        }
+
+        let project_path = call
+            .metadata
+            .as_ref()
+            .and_then(|m| m.get(PROJECT_ROOT_METADATA_KEY))
+            .and_then(|v| v.as_str())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")));
+
+        let mut child = AgentCore::new_with_client(
+            self.child_config(&call),
+            self.llm_client.clone(),
+            Box::new(NullOutput),
+            &self.tool_registry,
+        );
+        if self.depth + 1 < max_depth {
+            child.add_tool(Box::new(
+                SubAgentToolFactory::new(
+                    self.llm_client.clone(),
+                    self.parent_config.clone(),
+                    self.tool_registry.clone(),
+                )
+                .at_depth(self.depth + 1),
+            ));
+        }
+
+        let execution = match child.execute_task_with_context(&task, &project_path).await {
+            Ok(execution) => execution,
+            Err(e) => {
+                return Ok(ToolResult::error(
+                    call.id.clone(),
+                    format!("Sub-agent failed: {}", e),
+                ))
+            }
+        };
+
+        let message = execution
+            .final_message
+            .clone()
+            .unwrap_or_else(|| execution.final_result.clone());
+        let data = json!({
+            "success": execution.success,
+            "steps_executed": execution.steps_executed,
+            "depth": self.depth + 1,
+        });
+
+        let result = if execution.success {
+            ToolResult::success(call.id.clone(), message)
+        } else {
+            ToolResult::error(
+                call.id.clone(),
+                format!("Sub-agent did not complete the task: {}", message),
+            )
+        };
+        Ok(result.with_data(data))
+    }
+
+    fn examples(&self) -> Vec<ToolExample> {
+        vec![ToolExample {
+            description: "Delegate a self-contained investigation".to_string(),
+            parameters: json!({
+                "task": "Find where the config file is parsed and summarize the supported keys",
+                "max_steps": 20
+            }),
+            expected_result: "The sub-agent's final message".to_string(),
+        }]
+    }
+}
+
+/// Factory for [`SubAgentTool`]
+///
+/// Not part of the default registry because it needs an LLM client; use
+/// [`AgentCore::enable_sub_agents`] to add it to an agent.
+pub struct SubAgentToolFactory {
+    llm_client: Arc<dyn LlmClient>,
+    parent_config: AgentConfig,
+    tool_registry: Arc<ToolRegistry>,
+    depth: usize,
+}
+
+impl SubAgentToolFactory {
+    /// Create a factory for a top-level agent
+    pub fn new(
+        llm_client: Arc<dyn LlmClient>,
+        parent_config: AgentConfig,
+        tool_registry: Arc<ToolRegistry>,
+    ) -> Self {
+        Self {
+            llm_client,
+            parent_config,
+            tool_registry,
+            depth: 0,
+        }
+    }
+
+    /// Set the nesting depth of the agent the tool belongs to
+    pub fn at_depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+}
+
+impl ToolFactory for SubAgentToolFactory {
+    fn create(&self) -> Box<dyn Tool> {
+        Box::new(SubAgentTool::new(
+            self.llm_client.clone(),
+            self.parent_config.clone(),
+            self.tool_registry.clone(),
+            self.depth,
+        ))
+    }
+
+    fn tool_name(&self) -> &str {
+        SUB_AGENT_TOOL_NAME
+    }
+
+    fn tool_description(&self) -> &str {
+        "Delegate a subtask to a sub-agent"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::{ContentBlock, MessageContent, ScriptedLlmClient};
+    use std::path::Path;
+
+    fn tool_results(agent: &AgentCore) -> Vec<(bool, String)> {
+        agent
+            .export_context_snapshot()
+            .unwrap()
+            .conversation_history
+            .iter()
+            .filter_map(|message| match &message.content {
+                MessageContent::MultiModal(blocks) => Some(blocks.clone()),
+                _ => None,
+            })
+            .flatten()
+            .filter_map(|block| match block {
+                ContentBlock::ToolResult {
+                    is_error, content, ..
+                } => Some((!is_error.unwrap_or(false), content)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_sub_agent_result_flows_back_to_parent() {
+        // Parent delegates, child finishes, then parent finishes
+        let client = Arc::new(
+            ScriptedLlmClient::new()
+                .with_tool_call("sub_agent", json!({"task": "Say hello", "max_steps": 3}))
+                .with_tool_call("task_done", json!({"summary": "Hello from the sub-agent"}))
+                .with_tool_call("task_done", json!({"summary": "Delegated and done"})),
+        );
+        let config = AgentConfig {
+            max_steps: 5,
+            tools: vec!["task_done".to_string()],
+            ..Default::default()
+        };
+        let mut parent = AgentCore::new_with_client(
+            config,
+            client.clone(),
+            Box::new(NullOutput),
+            &ToolRegistry::default(),
+        );
+        parent.enable_sub_agents(Arc::new(ToolRegistry::default()));
+
+        let execution = parent
+            .execute_task_with_context("Delegate a greeting", Path::new("."))
+            .await
+            .unwrap();
+
+        assert!(execution.success);
+        assert_eq!(
+            execution.final_message.as_deref(),
+            Some("Delegated and done")
+        );
+        assert_eq!(
+            tool_results(&parent)[0],
+            (true, "Hello from the sub-agent".to_string())
+        );
+        client.assert_exhausted();
+    }
+
+    #[tokio::test]
+    async fn test_delegation_depth_is_bounded() {
+        let client = Arc::new(ScriptedLlmClient::new());
+        let config = AgentConfig {
+            max_delegation_depth: 1,
+            ..Default::default()
+        };
+        let tool = SubAgentToolFactory::new(client.clone(), config, Arc::default())
+            .at_depth(1)
+            .create();
+
+        let result = tool
+            .execute(ToolCall {
+                id: "call_1".to_string(),
+                name: SUB_AGENT_TOOL_NAME.to_string(),
+                parameters: json!({"task": "Recurse"}),
+                metadata: None,
+            })
+            .await
+            .unwrap();
+
+        assert!(!result.success);
+        assert!(result.content.contains("Maximum delegation depth"));
+    }
+}
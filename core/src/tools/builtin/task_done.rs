@@ -37,6 +37,12 @@ impl Tool for TaskDoneTool {
                 "details": {
                     "type": "string",
                     "description": "Optional detailed description of the work done"
+                },
+                "confidence": {
+                    "type": "number",
+                    "minimum": 0,
+                    "maximum": 1,
+                    "description": "Optional confidence from 0 to 1 that the task is fully and correctly done"
                 }
             },
             "required": ["summary"]
@@ -46,17 +52,22 @@ impl Tool for TaskDoneTool {
     async fn execute(&self, call: ToolCall) -> Result<ToolResult> {
         let summary: String = call.get_parameter("summary")?;
         let details: Option<String> = call.get_parameter("details").ok();
+        let confidence: Option<f32> = call.get_parameter("confidence").ok();
 
         let mut result = format!("Summary: {}", summary);
 
         if let Some(ref details) = details {
             result.push_str(&format!("\n\nDetails:\n{}", details));
         }
+        if let Some(confidence) = confidence {
+            result.push_str(&format!("\n\nConfidence: {:.2}", confidence));
+        }
 
         Ok(ToolResult::success(&call.id, &result).with_data(json!({
             "task_completed": true,
             "summary": summary,
-            "details": details
+            "details": details,
+            "confidence": confidence
         })))
     }
 
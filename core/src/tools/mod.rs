@@ -6,5 +6,8 @@ pub mod output_formatter;
 pub mod registry;
 pub mod utils;
 
-pub use base::{Tool, ToolCall, ToolExample, ToolExecutor, ToolResult};
+pub use base::{
+    Tool, ToolCall, ToolContentType, ToolExample, ToolExecutor, ToolKind, ToolResult,
+    DUPLICATE_OF_METADATA_KEY, SOURCE_URL_DATA_KEY,
+};
 pub use registry::{ToolFactory, ToolRegistry};
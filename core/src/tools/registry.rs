@@ -84,6 +84,8 @@ impl Default for ToolRegistry {
         registry.register_factory(Box::new(crate::tools::builtin::ThinkingToolFactory));
         registry.register_factory(Box::new(crate::tools::builtin::TaskDoneToolFactory));
         registry.register_factory(Box::new(crate::tools::builtin::McpToolFactory));
+        registry.register_factory(Box::new(crate::tools::builtin::AskUserToolFactory));
+        registry.register_factory(Box::new(crate::tools::builtin::ScratchpadToolFactory));
 
         registry
     }
@@ -121,7 +123,13 @@ mod tests {
         let tools = registry.list_tools();
 
         // Expected tools based on Python version
-        let expected_tools = vec!["sequentialthinking", "task_done", "mcp_tool"];
+        let expected_tools = vec![
+            "sequentialthinking",
+            "task_done",
+            "mcp_tool",
+            "ask_user",
+            "scratchpad",
+        ];
 
         println!("Available tools: {:?}", tools);
 
@@ -150,7 +158,13 @@ mod tests {
         let registry = ToolRegistry::default();
 
         // Test creating each tool
-        let tools_to_test = vec!["sequentialthinking", "task_done", "mcp_tool"];
+        let tools_to_test = vec![
+            "sequentialthinking",
+            "task_done",
+            "mcp_tool",
+            "ask_user",
+            "scratchpad",
+        ];
 
         for tool_name in tools_to_test {
             let tool = registry.create_tool(tool_name);
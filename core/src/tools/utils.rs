@@ -1,12 +1,15 @@
 //! Utility functions for tools
 
+pub mod ignore;
 pub mod run;
 
 use crate::error::Result;
-use std::path::Path;
+use crate::tools::ToolCall;
+use std::path::{Path, PathBuf};
 use tokio::process::Command;
 use tokio::time::{timeout, Duration};
 
+pub use ignore::{IgnoreMatcher, CORO_IGNORE_FILE, PROJECT_ROOT_METADATA_KEY};
 pub use run::{
     execute_command, stream_command, validate_command_safety, CommandOptions, CommandResult,
 };
@@ -17,13 +20,43 @@ pub const MAX_RESPONSE_LEN: usize = 16000;
 /// Truncation message
 pub const TRUNCATED_MESSAGE: &str = "<response clipped><NOTE>To save on context only part of this file has been shown to you. You should retry this tool after you have searched inside the file with `grep -n` in order to find the line numbers of what you are looking for.</NOTE>";
 
-/// Truncate content if it exceeds the specified length
+/// Longest prefix of `content` that fits in `max_bytes` without splitting a character
+pub fn truncate_to_char_boundary(content: &str, max_bytes: usize) -> &str {
+    if content.len() <= max_bytes {
+        return content;
+    }
+
+    let mut boundary = max_bytes;
+    while !content.is_char_boundary(boundary) {
+        boundary -= 1;
+    }
+    &content[..boundary]
+}
+
+/// Keep about `keep` bytes from each end of `content` and replace the middle
+/// with `marker`, which is given the number of bytes removed
+pub fn truncate_middle(content: &str, keep: usize, marker: impl FnOnce(usize) -> String) -> String {
+    let head = truncate_to_char_boundary(content, keep / 2);
+    let mut tail_start = content.len().saturating_sub(keep / 2).max(head.len());
+    while !content.is_char_boundary(tail_start) {
+        tail_start += 1;
+    }
+    let tail = &content[tail_start..];
+    let removed = content.len() - head.len() - tail.len();
+    format!("{}{}{}", head, marker(removed), tail)
+}
+
+/// Truncate content if it exceeds the specified length in bytes
 pub fn maybe_truncate(content: &str, truncate_after: Option<usize>) -> String {
     let limit = truncate_after.unwrap_or(MAX_RESPONSE_LEN);
     if content.len() <= limit {
         content.to_string()
     } else {
-        format!("{}{}", &content[..limit], TRUNCATED_MESSAGE)
+        format!(
+            "{}{}",
+            truncate_to_char_boundary(content, limit),
+            TRUNCATED_MESSAGE
+        )
     }
 }
 
@@ -72,6 +105,91 @@ pub fn format_with_line_numbers(content: &str, start_line: usize) -> String {
         .join("\n")
 }
 
+/// Remove ANSI escape sequences such as colors and cursor movement from `content`
+pub fn strip_ansi(content: &str) -> String {
+    let mut stripped = String::with_capacity(content.len());
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            stripped.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI: parameters and intermediates up to a final byte in @..=~
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC: ends with BEL or ST (ESC \)
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            // Any other escape is a single character
+            _ => {}
+        }
+    }
+    stripped
+}
+
+/// Tool call metadata key saying whether file tool paths are normalized
+pub const NORMALIZE_PATHS_METADATA_KEY: &str = "normalize_paths";
+
+/// Tool call metadata key holding the largest fraction of a file one edit may replace
+pub const MAX_EDIT_FRACTION_METADATA_KEY: &str = "max_edit_fraction";
+
+/// Largest fraction of a file (0.0 to 1.0) a single edit in `call` may
+/// replace, when the agent sets a limit
+pub fn max_edit_fraction(call: &ToolCall) -> Option<f64> {
+    call.metadata
+        .as_ref()?
+        .get(MAX_EDIT_FRACTION_METADATA_KEY)?
+        .as_f64()
+}
+
+/// Replace `/` and `\` in `path` with the host's path separator
+pub fn normalize_separators(path: &str) -> PathBuf {
+    PathBuf::from(path.replace(['/', '\\'], std::path::MAIN_SEPARATOR_STR))
+}
+
+/// Resolve a path given to a file tool
+///
+/// Separators are converted to the host's and relative paths are joined to
+/// the project root the agent attaches to the call. Calls whose metadata
+/// turns normalization off get the path unchanged.
+pub fn resolve_tool_path(call: &ToolCall, path: &str) -> PathBuf {
+    let metadata = call.metadata.as_ref();
+    let enabled = metadata
+        .and_then(|m| m.get(NORMALIZE_PATHS_METADATA_KEY))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true);
+    if !enabled {
+        return PathBuf::from(path);
+    }
+
+    let path = normalize_separators(path);
+    if path.is_absolute() {
+        return path;
+    }
+    match metadata
+        .and_then(|m| m.get(PROJECT_ROOT_METADATA_KEY))
+        .and_then(|v| v.as_str())
+    {
+        Some(root) => normalize_separators(root).join(path),
+        None => path,
+    }
+}
+
 /// Validate that a path is absolute
 pub fn validate_absolute_path(path: &Path) -> Result<()> {
     if !path.is_absolute() {
@@ -146,6 +264,15 @@ pub fn create_edit_snippet(content: &str, target_line: usize, snippet_lines: usi
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(
+            strip_ansi("\x1b[1;32mCompiling\x1b[0m coro v0.1\x1b[K\n\x1b]0;title\x07done"),
+            "Compiling coro v0.1\ndone"
+        );
+        assert_eq!(strip_ansi("plain → text"), "plain → text");
+    }
+
     #[test]
     fn test_maybe_truncate() {
         let short_content = "Hello, world!";
@@ -157,6 +284,100 @@ mod tests {
         assert!(truncated.contains(TRUNCATED_MESSAGE));
     }
 
+    #[test]
+    fn test_truncate_multibyte_content_on_char_boundary() {
+        // "你" is 3 bytes and "🦀" is 4, so these limits fall mid-character
+        let content = "你好世界🦀🦀";
+        assert_eq!(truncate_to_char_boundary(content, 4), "你");
+        assert_eq!(truncate_to_char_boundary(content, 14), "你好世界");
+        assert_eq!(truncate_to_char_boundary(content, 100), content);
+
+        let truncated = maybe_truncate(content, Some(7));
+        assert!(std::str::from_utf8(truncated.as_bytes()).is_ok());
+        assert!(truncated.starts_with("你好"));
+        assert!(!truncated.starts_with("你好世"));
+        assert!(truncated.ends_with(TRUNCATED_MESSAGE));
+    }
+
+    #[test]
+    fn test_truncate_middle_keeps_both_ends_on_char_boundaries() {
+        let content = "你好世界🦀🦀";
+        let truncated = truncate_middle(content, 10, |removed| format!("[-{}]", removed));
+        // Half of 10 falls inside "好" at the start and inside a "🦀" at the end
+        assert_eq!(truncated, "你[-13]🦀");
+
+        let truncated = truncate_middle("abcdefgh", 4, |removed| format!("[-{}]", removed));
+        assert_eq!(truncated, "ab[-4]gh");
+    }
+
+    fn call_in_project(root: &str) -> ToolCall {
+        let mut call = ToolCall::new("str_replace_based_edit_tool", serde_json::json!({}));
+        call.metadata = Some(IgnoreMatcher::tool_call_metadata(Path::new(root), &[]));
+        call
+    }
+
+    #[test]
+    fn test_max_edit_fraction_read_from_metadata() {
+        let mut call = call_in_project("/project");
+        assert_eq!(max_edit_fraction(&call), None);
+
+        call.metadata
+            .as_mut()
+            .unwrap()
+            .insert(MAX_EDIT_FRACTION_METADATA_KEY.to_string(), 0.5.into());
+        assert_eq!(max_edit_fraction(&call), Some(0.5));
+    }
+
+    #[test]
+    fn test_relative_tool_path_resolved_against_project_root() {
+        let root = std::env::temp_dir().join("project");
+        let call = call_in_project(root.to_str().unwrap());
+
+        assert_eq!(
+            resolve_tool_path(&call, "src/foo.rs"),
+            root.join("src").join("foo.rs")
+        );
+        let absolute = root.join("README.md");
+        assert_eq!(
+            resolve_tool_path(&call, absolute.to_str().unwrap()),
+            absolute
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_mixed_separators_normalized_on_unix() {
+        let call = call_in_project("/project");
+        assert_eq!(
+            resolve_tool_path(&call, "src\\nested/foo.rs"),
+            PathBuf::from("/project/src/nested/foo.rs")
+        );
+
+        let mut raw = call.clone();
+        raw.metadata
+            .as_mut()
+            .unwrap()
+            .insert(NORMALIZE_PATHS_METADATA_KEY.to_string(), false.into());
+        assert_eq!(
+            resolve_tool_path(&raw, "src\\nested/foo.rs"),
+            PathBuf::from("src\\nested/foo.rs")
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_mixed_separators_normalized_on_windows() {
+        let call = call_in_project("C:\\project");
+        assert_eq!(
+            resolve_tool_path(&call, "src/nested\\foo.rs"),
+            PathBuf::from("C:\\project\\src\\nested\\foo.rs")
+        );
+        assert_eq!(
+            resolve_tool_path(&call, "C:/project/src/foo.rs"),
+            PathBuf::from("C:\\project\\src\\foo.rs")
+        );
+    }
+
     #[test]
     fn test_format_with_line_numbers() {
         let content = "line1\nline2\nline3";
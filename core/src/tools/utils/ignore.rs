@@ -0,0 +1,229 @@
+//! Project ignore rules shared by context-gathering tools
+
+use crate::tools::ToolCall;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Project-level ignore file read in addition to `.gitignore`
+pub const CORO_IGNORE_FILE: &str = ".coroignore";
+
+/// Tool call metadata key carrying the project root
+pub const PROJECT_ROOT_METADATA_KEY: &str = "project_root";
+
+/// Tool call metadata key carrying the configured ignore globs
+pub const IGNORE_GLOBS_METADATA_KEY: &str = "ignore_globs";
+
+/// Decides which project paths are hidden from the agent
+///
+/// Rules are applied in order: `.gitignore` files (optional), the project's
+/// `.coroignore`, then the configured globs. All use gitignore syntax, so a
+/// later `!pattern` can re-include something an earlier rule excluded.
+#[derive(Debug, Clone)]
+pub struct IgnoreMatcher {
+    root: PathBuf,
+    rules: Option<Gitignore>,
+}
+
+impl IgnoreMatcher {
+    /// Build a matcher for `root` honouring `.gitignore`, `.coroignore` and `globs`
+    pub fn new(root: impl AsRef<Path>, globs: &[String]) -> Self {
+        Self::with_gitignore(root, globs, true)
+    }
+
+    /// Build a matcher, optionally skipping `.gitignore` files
+    pub fn with_gitignore(
+        root: impl AsRef<Path>,
+        globs: &[String],
+        respect_gitignore: bool,
+    ) -> Self {
+        let root = root.as_ref().to_path_buf();
+        let mut builder = GitignoreBuilder::new(&root);
+
+        if respect_gitignore {
+            // Outermost first so nested .gitignore files take precedence
+            let mut gitignores: Vec<PathBuf> = root
+                .ancestors()
+                .map(|dir| dir.join(".gitignore"))
+                .filter(|path| path.is_file())
+                .collect();
+            gitignores.reverse();
+            for path in gitignores {
+                Self::add_ignore_file(&mut builder, &path);
+            }
+        }
+
+        let coroignore = root.join(CORO_IGNORE_FILE);
+        if coroignore.is_file() {
+            Self::add_ignore_file(&mut builder, &coroignore);
+        }
+
+        for glob in globs {
+            if let Err(e) = builder.add_line(None, glob) {
+                tracing::warn!("Invalid ignore glob '{}': {}", glob, e);
+            }
+        }
+
+        let rules = match builder.build() {
+            Ok(rules) => Some(rules),
+            Err(e) => {
+                tracing::warn!("Failed to build ignore matcher: {}", e);
+                None
+            }
+        };
+
+        Self { root, rules }
+    }
+
+    /// Build a matcher from the project context the agent attaches to tool calls
+    ///
+    /// Falls back to `default_root` when the call carries no project root or
+    /// when `default_root` lies outside it.
+    pub fn for_tool_call(call: &ToolCall, default_root: &Path, respect_gitignore: bool) -> Self {
+        let metadata = call.metadata.as_ref();
+
+        let root = metadata
+            .and_then(|m| m.get(PROJECT_ROOT_METADATA_KEY))
+            .and_then(|v| v.as_str())
+            .map(PathBuf::from)
+            .filter(|root| default_root.starts_with(root))
+            .unwrap_or_else(|| default_root.to_path_buf());
+
+        let globs: Vec<String> = metadata
+            .and_then(|m| m.get(IGNORE_GLOBS_METADATA_KEY))
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default();
+
+        Self::with_gitignore(root, &globs, respect_gitignore)
+    }
+
+    /// Tool call metadata describing the project root and ignore globs
+    pub fn tool_call_metadata(
+        project_root: &Path,
+        globs: &[String],
+    ) -> HashMap<String, serde_json::Value> {
+        let mut metadata = HashMap::new();
+        metadata.insert(
+            PROJECT_ROOT_METADATA_KEY.to_string(),
+            serde_json::Value::String(project_root.to_string_lossy().to_string()),
+        );
+        metadata.insert(
+            IGNORE_GLOBS_METADATA_KEY.to_string(),
+            serde_json::json!(globs),
+        );
+        metadata
+    }
+
+    /// Root directory the rules are relative to
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Whether `path` (or one of its parent directories) is ignored
+    ///
+    /// Paths outside the root are never ignored.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let Some(rules) = &self.rules else {
+            return false;
+        };
+
+        let relative = if path.is_absolute() {
+            match path.strip_prefix(&self.root) {
+                Ok(relative) => relative,
+                Err(_) => return false,
+            }
+        } else {
+            path
+        };
+
+        if relative.as_os_str().is_empty() {
+            return false;
+        }
+
+        rules
+            .matched_path_or_any_parents(relative, is_dir)
+            .is_ignore()
+    }
+
+    /// Convenience wrapper that checks the file system for whether `path` is a directory
+    pub fn is_path_ignored(&self, path: &Path) -> bool {
+        self.is_ignored(path, path.is_dir())
+    }
+
+    fn add_ignore_file(builder: &mut GitignoreBuilder, path: &Path) {
+        if let Some(e) = builder.add(path) {
+            tracing::warn!("Failed to parse {}: {}", path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn project() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::create_dir_all(dir.path().join("target/debug")).unwrap();
+        fs::create_dir_all(dir.path().join("generated")).unwrap();
+        fs::write(dir.path().join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(dir.path().join("target/debug/app"), "").unwrap();
+        fs::write(dir.path().join("generated/schema.json"), "{}").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_coroignore_entry_excludes_paths() {
+        let dir = project();
+        fs::write(dir.path().join(CORO_IGNORE_FILE), "target/\n").unwrap();
+
+        let matcher = IgnoreMatcher::new(dir.path(), &[]);
+
+        assert!(matcher.is_ignored(&dir.path().join("target"), true));
+        assert!(matcher.is_ignored(&dir.path().join("target/debug/app"), false));
+        assert!(!matcher.is_ignored(&dir.path().join("src/main.rs"), false));
+    }
+
+    #[test]
+    fn test_config_glob_excludes_paths() {
+        let dir = project();
+
+        let matcher = IgnoreMatcher::new(dir.path(), &["*.json".to_string()]);
+
+        assert!(matcher.is_path_ignored(&dir.path().join("generated/schema.json")));
+        assert!(!matcher.is_path_ignored(&dir.path().join("src/main.rs")));
+    }
+
+    #[test]
+    fn test_gitignore_can_be_skipped() {
+        let dir = project();
+        fs::write(dir.path().join(".gitignore"), "generated/\n").unwrap();
+
+        let with_git = IgnoreMatcher::new(dir.path(), &[]);
+        let without_git = IgnoreMatcher::with_gitignore(dir.path(), &[], false);
+
+        let path = dir.path().join("generated/schema.json");
+        assert!(with_git.is_path_ignored(&path));
+        assert!(!without_git.is_path_ignored(&path));
+    }
+
+    #[test]
+    fn test_for_tool_call_uses_attached_globs() {
+        let dir = project();
+        let call = ToolCall {
+            id: "call_1".to_string(),
+            name: "glob".to_string(),
+            parameters: serde_json::json!({}),
+            metadata: Some(IgnoreMatcher::tool_call_metadata(
+                dir.path(),
+                &["generated/".to_string()],
+            )),
+        };
+
+        let matcher = IgnoreMatcher::for_tool_call(&call, &dir.path().join("generated"), true);
+
+        assert_eq!(matcher.root(), dir.path());
+        assert!(matcher.is_path_ignored(&dir.path().join("generated/schema.json")));
+    }
+}
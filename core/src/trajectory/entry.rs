@@ -1,6 +1,6 @@
 //! Trajectory entry structures
 
-use crate::llm::LlmMessage;
+use crate::llm::{FinishReason, LlmMessage};
 use crate::tools::{ToolCall, ToolResult};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
@@ -47,7 +47,8 @@ pub enum EntryType {
     LlmResponse {
         message: LlmMessage,
         usage: Option<crate::llm::Usage>,
-        finish_reason: Option<String>,
+        #[serde(default, deserialize_with = "deserialize_finish_reason")]
+        finish_reason: Option<FinishReason>,
     },
 
     /// Tool call initiated
@@ -135,7 +136,7 @@ impl TrajectoryEntry {
     pub fn llm_response(
         message: LlmMessage,
         usage: Option<crate::llm::Usage>,
-        finish_reason: Option<String>,
+        finish_reason: Option<FinishReason>,
         step: usize,
     ) -> Self {
         Self::new(
@@ -148,6 +149,14 @@ impl TrajectoryEntry {
         )
     }
 
+    /// Finish reason of an LLM response entry
+    pub fn finish_reason(&self) -> Option<&FinishReason> {
+        match &self.entry_type {
+            EntryType::LlmResponse { finish_reason, .. } => finish_reason.as_ref(),
+            _ => None,
+        }
+    }
+
     /// Create a tool call entry
     pub fn tool_call(call: ToolCall, step: usize) -> Self {
         Self::new(EntryType::ToolCall { call }, step)
@@ -221,3 +230,87 @@ impl TrajectoryEntry {
         )
     }
 }
+
+/// Accept both typed finish reasons and the debug strings older trajectories stored
+fn deserialize_finish_reason<'de, D>(deserializer: D) -> Result<Option<FinishReason>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = Option::<serde_json::Value>::deserialize(deserializer)?;
+    let Some(value) = value else {
+        return Ok(None);
+    };
+    if let Ok(reason) = serde_json::from_value::<FinishReason>(value.clone()) {
+        return Ok(Some(reason));
+    }
+    let Some(legacy) = value.as_str() else {
+        return Err(serde::de::Error::custom(format!(
+            "invalid finish reason: {}",
+            value
+        )));
+    };
+    Ok(Some(match legacy {
+        "Stop" => FinishReason::Stop,
+        "Length" => FinishReason::Length,
+        "ToolCalls" => FinishReason::ToolCalls,
+        "ContentFilter" => FinishReason::ContentFilter,
+        other => FinishReason::Other(
+            other
+                .strip_prefix("Other(\"")
+                .and_then(|rest| rest.strip_suffix("\")"))
+                .unwrap_or(other)
+                .to_string(),
+        ),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(finish_reason: Option<FinishReason>, step: usize) -> TrajectoryEntry {
+        TrajectoryEntry::llm_response(LlmMessage::assistant("done"), None, finish_reason, step)
+    }
+
+    #[test]
+    fn test_finish_reasons_stored_typed() {
+        let entries = vec![
+            response(Some(FinishReason::ToolCalls), 1),
+            response(Some(FinishReason::Length), 2),
+            response(Some(FinishReason::Length), 3),
+            response(Some(FinishReason::Stop), 4),
+            response(None, 5),
+        ];
+
+        let json = serde_json::to_string(&entries).unwrap();
+        assert!(json.contains("\"finish_reason\":\"length\""));
+
+        let loaded: Vec<TrajectoryEntry> = serde_json::from_str(&json).unwrap();
+        let truncated = loaded
+            .iter()
+            .filter(|e| e.finish_reason() == Some(&FinishReason::Length))
+            .count();
+        assert_eq!(truncated, 2);
+        assert_eq!(loaded[0].finish_reason(), Some(&FinishReason::ToolCalls));
+        assert_eq!(loaded[3].finish_reason(), Some(&FinishReason::Stop));
+        assert_eq!(loaded[4].finish_reason(), None);
+    }
+
+    #[test]
+    fn test_legacy_debug_finish_reasons_load() {
+        let mut value = serde_json::to_value(response(None, 1)).unwrap();
+        let reason = &mut value["entry_type"]["finish_reason"];
+
+        *reason = serde_json::json!("Length");
+        let entry: TrajectoryEntry = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(entry.finish_reason(), Some(&FinishReason::Length));
+
+        let reason = &mut value["entry_type"]["finish_reason"];
+        *reason = serde_json::json!("Other(\"end_turn\")");
+        let entry: TrajectoryEntry = serde_json::from_value(value).unwrap();
+        assert_eq!(
+            entry.finish_reason(),
+            Some(&FinishReason::Other("end_turn".to_string()))
+        );
+    }
+}
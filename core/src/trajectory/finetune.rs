@@ -0,0 +1,308 @@
+//! Exporting trajectories as chat fine-tuning data
+
+use super::entry::EntryType;
+use super::recorder::Trajectory;
+use crate::llm::{ContentBlock, LlmMessage, MessageContent, MessageRole};
+use serde_json::{json, Value};
+
+/// Options for [`Trajectory::to_finetuning_jsonl_with`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FinetuningOptions {
+    /// Export nothing for runs that did not succeed
+    pub successful_only: bool,
+    /// Keep tool calls and tool results; otherwise only text turns are kept
+    pub include_tool_messages: bool,
+}
+
+impl Default for FinetuningOptions {
+    fn default() -> Self {
+        Self {
+            successful_only: true,
+            include_tool_messages: true,
+        }
+    }
+}
+
+impl Trajectory {
+    /// Whether the recorded task succeeded
+    ///
+    /// Uses the metadata when set, otherwise the last task completion entry.
+    pub fn succeeded(&self) -> bool {
+        self.metadata
+            .success
+            .or_else(|| {
+                self.entries
+                    .iter()
+                    .rev()
+                    .find_map(|entry| match &entry.entry_type {
+                        EntryType::TaskComplete { success, .. } => Some(*success),
+                        _ => None,
+                    })
+            })
+            .unwrap_or(false)
+    }
+
+    /// Conversation as last seen by the model, followed by its final responses
+    ///
+    /// Starts from the messages of the last LLM request and appends the
+    /// responses and tool results recorded after it.
+    pub fn conversation(&self) -> Vec<LlmMessage> {
+        let Some(last_request) = self
+            .entries
+            .iter()
+            .rposition(|entry| matches!(entry.entry_type, EntryType::LlmRequest { .. }))
+        else {
+            return Vec::new();
+        };
+
+        let mut messages = Vec::new();
+        for entry in &self.entries[last_request..] {
+            match &entry.entry_type {
+                EntryType::LlmRequest { messages: sent, .. } => messages.extend(sent.clone()),
+                EntryType::LlmResponse { message, .. } => messages.push(message.clone()),
+                EntryType::ToolResult { result } => messages.push(LlmMessage {
+                    role: MessageRole::Tool,
+                    content: MessageContent::MultiModal(vec![ContentBlock::ToolResult {
+                        tool_use_id: result.tool_call_id.clone(),
+                        is_error: Some(!result.success),
+                        content: result.content.clone(),
+                    }]),
+                    metadata: None,
+                }),
+                _ => {}
+            }
+        }
+        messages
+    }
+
+    /// Export the conversation as one line of OpenAI chat fine-tuning JSONL
+    ///
+    /// Failed runs are skipped and tool messages kept; see
+    /// [`Trajectory::to_finetuning_jsonl_with`].
+    pub fn to_finetuning_jsonl(&self) -> String {
+        self.to_finetuning_jsonl_with(FinetuningOptions::default())
+    }
+
+    /// Export the conversation as one line of OpenAI chat fine-tuning JSONL
+    ///
+    /// The line is `{"messages": [...]}` followed by a newline, so exports of
+    /// several trajectories can be concatenated. Returns an empty string when
+    /// the run is filtered out or has no assistant message.
+    pub fn to_finetuning_jsonl_with(&self, options: FinetuningOptions) -> String {
+        if options.successful_only && !self.succeeded() {
+            return String::new();
+        }
+
+        let messages: Vec<Value> = self
+            .conversation()
+            .iter()
+            .flat_map(|message| finetuning_messages(message, options.include_tool_messages))
+            .collect();
+        if !messages
+            .iter()
+            .any(|message| message["role"] == "assistant")
+        {
+            return String::new();
+        }
+
+        format!("{}\n", json!({ "messages": messages }))
+    }
+}
+
+/// Text blocks of `content`, joined by newlines
+fn text_of(content: &MessageContent) -> String {
+    match content {
+        MessageContent::Text(text) => text.clone(),
+        MessageContent::MultiModal(blocks) => blocks
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+/// Fine-tuning messages for one conversation message
+fn finetuning_messages(message: &LlmMessage, include_tools: bool) -> Vec<Value> {
+    let blocks = match &message.content {
+        MessageContent::MultiModal(blocks) => blocks.as_slice(),
+        MessageContent::Text(_) => &[],
+    };
+
+    match message.role {
+        MessageRole::System => {
+            vec![json!({ "role": "system", "content": text_of(&message.content) })]
+        }
+        MessageRole::User => vec![json!({ "role": "user", "content": text_of(&message.content) })],
+        MessageRole::Assistant => {
+            let text = text_of(&message.content);
+            let tool_calls: Vec<Value> = blocks
+                .iter()
+                .filter_map(|block| match block {
+                    ContentBlock::ToolUse { id, name, input } if include_tools => Some(json!({
+                        "id": id,
+                        "type": "function",
+                        "function": { "name": name, "arguments": input.to_string() },
+                    })),
+                    _ => None,
+                })
+                .collect();
+
+            if tool_calls.is_empty() {
+                if text.is_empty() {
+                    return Vec::new();
+                }
+                vec![json!({ "role": "assistant", "content": text })]
+            } else {
+                let content = if text.is_empty() {
+                    Value::Null
+                } else {
+                    Value::String(text)
+                };
+                vec![json!({ "role": "assistant", "content": content, "tool_calls": tool_calls })]
+            }
+        }
+        MessageRole::Tool if include_tools => blocks
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::ToolResult {
+                    tool_use_id,
+                    content,
+                    ..
+                } => Some(json!({
+                    "role": "tool",
+                    "tool_call_id": tool_use_id,
+                    "content": content,
+                })),
+                _ => None,
+            })
+            .collect(),
+        MessageRole::Tool => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::{ToolCall, ToolResult};
+    use crate::trajectory::recorder::TrajectoryMetadata;
+    use crate::trajectory::TrajectoryEntry;
+
+    fn listing_trajectory(success: bool) -> Trajectory {
+        let system = LlmMessage::system("You are a coding agent.");
+        let user = LlmMessage::user("List the files");
+        let calls_ls = LlmMessage {
+            role: MessageRole::Assistant,
+            content: MessageContent::MultiModal(vec![
+                ContentBlock::Text {
+                    text: "Let me look.".to_string(),
+                },
+                ContentBlock::ToolUse {
+                    id: "call_1".to_string(),
+                    name: "bash".to_string(),
+                    input: json!({ "command": "ls" }),
+                },
+            ]),
+            metadata: None,
+        };
+        let ls_result = ToolResult::success("call_1", "Cargo.toml\nsrc");
+        let tool_message = LlmMessage {
+            role: MessageRole::Tool,
+            content: MessageContent::MultiModal(vec![ContentBlock::ToolResult {
+                tool_use_id: "call_1".to_string(),
+                is_error: Some(false),
+                content: ls_result.content.clone(),
+            }]),
+            metadata: None,
+        };
+
+        Trajectory {
+            metadata: TrajectoryMetadata {
+                id: "t1".to_string(),
+                started_at: chrono::Utc::now(),
+                completed_at: None,
+                version: "1.0".to_string(),
+                agent_type: "coro_agent".to_string(),
+                task: Some("List the files".to_string()),
+                success: None,
+                total_steps: 2,
+                duration_ms: None,
+                segments: Vec::new(),
+            },
+            entries: vec![
+                TrajectoryEntry::task_start("List the files".to_string(), json!({})),
+                TrajectoryEntry::llm_request(
+                    vec![system.clone(), user.clone()],
+                    "gpt-4o".to_string(),
+                    "openai".to_string(),
+                    1,
+                ),
+                TrajectoryEntry::llm_response(calls_ls.clone(), None, None, 1),
+                TrajectoryEntry::tool_call(
+                    ToolCall {
+                        id: "call_1".to_string(),
+                        name: "bash".to_string(),
+                        parameters: json!({ "command": "ls" }),
+                        metadata: None,
+                    },
+                    1,
+                ),
+                TrajectoryEntry::tool_result(ls_result, 1),
+                TrajectoryEntry::llm_request(
+                    vec![system, user, calls_ls, tool_message],
+                    "gpt-4o".to_string(),
+                    "openai".to_string(),
+                    2,
+                ),
+                TrajectoryEntry::llm_response(
+                    LlmMessage::assistant("The project has Cargo.toml and src."),
+                    None,
+                    None,
+                    2,
+                ),
+                TrajectoryEntry::task_complete(success, "Listed files".to_string(), 2, 10),
+            ],
+        }
+    }
+
+    fn roles(line: &str) -> Vec<String> {
+        let value: Value = serde_json::from_str(line.trim_end()).unwrap();
+        value["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|message| message["role"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_successful_trajectory_exports_finetuning_line() {
+        let trajectory = listing_trajectory(true);
+
+        let jsonl = trajectory.to_finetuning_jsonl();
+        assert!(jsonl.ends_with('\n'));
+        assert_eq!(jsonl.lines().count(), 1);
+        assert_eq!(
+            roles(&jsonl),
+            ["system", "user", "assistant", "tool", "assistant"]
+        );
+        let value: Value = serde_json::from_str(jsonl.trim_end()).unwrap();
+        let call = &value["messages"][2]["tool_calls"][0];
+        assert_eq!(call["function"]["name"], "bash");
+        assert_eq!(call["function"]["arguments"], r#"{"command":"ls"}"#);
+        assert_eq!(value["messages"][3]["tool_call_id"], "call_1");
+
+        let text_only = trajectory.to_finetuning_jsonl_with(FinetuningOptions {
+            include_tool_messages: false,
+            ..Default::default()
+        });
+        assert_eq!(
+            roles(&text_only),
+            ["system", "user", "assistant", "assistant"]
+        );
+
+        assert_eq!(listing_trajectory(false).to_finetuning_jsonl(), "");
+    }
+}
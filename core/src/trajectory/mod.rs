@@ -1,7 +1,13 @@
 //! Execution trajectory recording and replay
 
 pub mod entry;
+pub mod finetune;
 pub mod recorder;
+pub mod replay;
+pub mod writer;
 
 pub use entry::{EntryType, TrajectoryEntry};
-pub use recorder::TrajectoryRecorder;
+pub use finetune::FinetuningOptions;
+pub use recorder::{FlushPolicy, SystemClock, TrajectoryClock, TrajectoryRecorder};
+pub use replay::ReplayToolExecutor;
+pub use writer::TrajectoryWriter;
@@ -5,14 +5,102 @@ use crate::trajectory::TrajectoryEntry;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::fs;
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock};
 
 /// Records execution trajectories for debugging and analysis
 pub struct TrajectoryRecorder {
     entries: RwLock<Vec<TrajectoryEntry>>,
     file_path: Option<PathBuf>,
     auto_save: bool,
+    flush_policy: Option<FlushPolicy>,
+    flush_state: Mutex<FlushState>,
+    clock: Arc<dyn TrajectoryClock>,
+    /// Trajectory id written to the metadata, stable across saves
+    id: String,
+    /// Start time carried over from a resumed trajectory
+    started_at: Option<DateTime<Utc>>,
+    /// Added to the step of every new entry so a resumed run continues the numbering
+    step_offset: usize,
+    /// Set once saving from `record` fails; later entries are kept in memory only
+    saving_disabled: AtomicBool,
+    /// Size above which the current file is closed and a new segment started
+    max_file_bytes: Option<u64>,
+    rotation: Mutex<RotationState>,
+}
+
+/// Where the saved entries are split across segment files
+#[derive(Default)]
+struct RotationState {
+    /// Index of the first entry in each segment after the first
+    starts: Vec<usize>,
+    /// File names of the segments after the first, beside the first file
+    segments: Vec<String>,
+}
+
+/// Policy for incrementally flushing the trajectory to disk
+///
+/// The trajectory is saved once either limit is reached, whichever comes first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FlushPolicy {
+    /// Flush after this many unsaved entries
+    pub max_entries: Option<usize>,
+
+    /// Flush when this much time has passed since the last flush
+    pub max_interval: Option<Duration>,
+}
+
+impl FlushPolicy {
+    /// Create a policy that flushes every `max_entries` entries or every `max_interval`
+    pub fn new(max_entries: usize, max_interval: Duration) -> Self {
+        Self {
+            max_entries: Some(max_entries),
+            max_interval: Some(max_interval),
+        }
+    }
+
+    /// Flush only on the entry-count boundary
+    pub fn every_entries(max_entries: usize) -> Self {
+        Self {
+            max_entries: Some(max_entries),
+            max_interval: None,
+        }
+    }
+
+    /// Flush only on the time interval
+    pub fn every_interval(max_interval: Duration) -> Self {
+        Self {
+            max_entries: None,
+            max_interval: Some(max_interval),
+        }
+    }
+}
+
+/// Source of the current time for flush scheduling
+pub trait TrajectoryClock: Send + Sync {
+    /// Get the current time
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// Clock backed by the system time
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl TrajectoryClock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Bookkeeping for incremental flushing
+struct FlushState {
+    /// Entries recorded since the last flush
+    pending_entries: usize,
+    /// When the trajectory was last flushed
+    last_flush: DateTime<Utc>,
 }
 
 /// Complete trajectory data
@@ -54,25 +142,130 @@ pub struct TrajectoryMetadata {
 
     /// Total duration in milliseconds
     pub duration_ms: Option<u64>,
+
+    /// Files holding the rest of a rotated trajectory, in order, relative to
+    /// this one; only set in the first file
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub segments: Vec<String>,
 }
 
 impl TrajectoryRecorder {
     /// Create a new trajectory recorder
     pub fn new() -> Self {
-        Self {
-            entries: RwLock::new(Vec::new()),
-            file_path: None,
-            auto_save: false,
-        }
+        Self::build(None, false, None, Arc::new(SystemClock))
     }
 
     /// Create a trajectory recorder that saves to a file
     pub fn with_file<P: AsRef<Path>>(path: P) -> Self {
+        Self::build(
+            Some(path.as_ref().to_path_buf()),
+            true,
+            None,
+            Arc::new(SystemClock),
+        )
+    }
+
+    /// Create a trajectory recorder that flushes to a file according to a policy
+    pub fn with_flush_policy<P: AsRef<Path>>(path: P, policy: FlushPolicy) -> Self {
+        Self::with_flush_policy_and_clock(path, policy, Arc::new(SystemClock))
+    }
+
+    /// Create a policy-flushed trajectory recorder with a custom clock
+    pub fn with_flush_policy_and_clock<P: AsRef<Path>>(
+        path: P,
+        policy: FlushPolicy,
+        clock: Arc<dyn TrajectoryClock>,
+    ) -> Self {
+        Self::build(
+            Some(path.as_ref().to_path_buf()),
+            false,
+            Some(policy),
+            clock,
+        )
+    }
+
+    fn build(
+        file_path: Option<PathBuf>,
+        auto_save: bool,
+        flush_policy: Option<FlushPolicy>,
+        clock: Arc<dyn TrajectoryClock>,
+    ) -> Self {
+        let last_flush = clock.now();
         Self {
             entries: RwLock::new(Vec::new()),
-            file_path: Some(path.as_ref().to_path_buf()),
-            auto_save: true,
+            file_path,
+            auto_save,
+            flush_policy,
+            flush_state: Mutex::new(FlushState {
+                pending_entries: 0,
+                last_flush,
+            }),
+            clock,
+            id: uuid::Uuid::new_v4().to_string(),
+            started_at: None,
+            step_offset: 0,
+            saving_disabled: AtomicBool::new(false),
+            max_file_bytes: None,
+            rotation: Mutex::new(RotationState::default()),
+        }
+    }
+
+    /// Rotate to a new segment file once the current one exceeds `max_bytes`
+    ///
+    /// The first file keeps the recorder's path and lists the later segments,
+    /// named like `trajectory.1.json`, so [`load`](Self::load) returns them
+    /// as one trajectory. Entries stay in memory regardless.
+    pub fn with_max_file_size(mut self, max_bytes: u64) -> Self {
+        self.max_file_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Resume recording into an existing trajectory file
+    ///
+    /// Loaded entries are kept and new ones are appended, saving to the same
+    /// file. The trajectory keeps its id and start time, and step numbers
+    /// continue after the highest loaded step. Use [`resume`](Self::resume)
+    /// to keep a flush policy or size limit.
+    pub async fn resume_from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::with_file(path).resume().await
+    }
+
+    /// Load the trajectory already in this recorder's file and continue it
+    ///
+    /// Like [`resume_from_file`](Self::resume_from_file), but the recorder
+    /// keeps the flush policy and size limit it was built with.
+    pub async fn resume(mut self) -> Result<Self> {
+        let Some(path) = self.file_path.clone() else {
+            return Err(TrajectoryError::RecordingFailed {
+                message: "No trajectory file to resume".to_string(),
+            }
+            .into());
+        };
+        let parts = Self::load_segments(&path).await?;
+        let trajectory = Self::stitch(&parts);
+
+        // Keep appending to the last segment of a rotated trajectory
+        let mut start = 0;
+        let mut starts = Vec::new();
+        for part in &parts[..parts.len() - 1] {
+            start += part.entries.len();
+            starts.push(start);
         }
+        self.rotation = Mutex::new(RotationState {
+            starts,
+            segments: trajectory.metadata.segments.clone(),
+        });
+        self.step_offset = trajectory
+            .entries
+            .iter()
+            .map(|entry| entry.step)
+            .max()
+            .unwrap_or(0);
+        self.id = trajectory.metadata.id;
+        self.started_at = Some(trajectory.metadata.started_at);
+        self.entries = RwLock::new(trajectory.entries);
+
+        Ok(self)
     }
 
     /// Create a trajectory recorder with auto-generated filename
@@ -83,7 +276,9 @@ impl TrajectoryRecorder {
         // Create trajectories directory if it doesn't exist
         let trajectories_dir = Path::new("trajectories");
         if !trajectories_dir.exists() {
-            std::fs::create_dir_all(trajectories_dir).ok();
+            if let Err(e) = std::fs::create_dir_all(trajectories_dir) {
+                tracing::warn!("Failed to create trajectories directory: {}", e);
+            }
         }
 
         let path = trajectories_dir.join(filename);
@@ -91,19 +286,56 @@ impl TrajectoryRecorder {
     }
 
     /// Record a trajectory entry
-    pub async fn record(&self, entry: TrajectoryEntry) -> Result<()> {
+    ///
+    /// The entry is always kept in memory. If saving it fails, the error is
+    /// returned once and later entries are no longer saved automatically;
+    /// [`save`](Self::save) can still be called explicitly.
+    pub async fn record(&self, mut entry: TrajectoryEntry) -> Result<()> {
+        entry.step += self.step_offset;
         {
             let mut entries = self.entries.write().await;
             entries.push(entry);
         }
 
-        if self.auto_save {
-            self.save().await?;
+        if self.saving_disabled() {
+            return Ok(());
+        }
+        if self.auto_save || self.flush_due().await {
+            if let Err(e) = self.save().await {
+                self.saving_disabled.store(true, Ordering::Relaxed);
+                return Err(e);
+            }
         }
 
         Ok(())
     }
 
+    /// Whether automatic saving stopped after a failed save
+    pub fn saving_disabled(&self) -> bool {
+        self.saving_disabled.load(Ordering::Relaxed)
+    }
+
+    /// Count a new entry against the flush policy and report whether a flush is due
+    async fn flush_due(&self) -> bool {
+        let Some(policy) = &self.flush_policy else {
+            return false;
+        };
+
+        let mut state = self.flush_state.lock().await;
+        state.pending_entries += 1;
+
+        let entries_due = policy
+            .max_entries
+            .is_some_and(|max| state.pending_entries >= max);
+        let interval_due = policy.max_interval.is_some_and(|interval| {
+            (self.clock.now() - state.last_flush)
+                .to_std()
+                .is_ok_and(|elapsed| elapsed >= interval)
+        });
+
+        entries_due || interval_due
+    }
+
     /// Get all recorded entries
     pub async fn get_entries(&self) -> Vec<TrajectoryEntry> {
         self.entries.read().await.clone()
@@ -115,30 +347,152 @@ impl TrajectoryRecorder {
     }
 
     /// Save the trajectory to file
+    ///
+    /// With a size limit, only the current segment is rewritten; when it ends
+    /// up over the limit, later entries go to a new segment.
     pub async fn save(&self) -> Result<()> {
         if let Some(path) = &self.file_path {
-            let trajectory = self.build_trajectory().await;
-            let json = serde_json::to_string_pretty(&trajectory).map_err(|e| {
-                TrajectoryError::RecordingFailed {
-                    message: format!("Failed to serialize trajectory: {}", e),
-                }
-            })?;
-
             // Ensure parent directory exists
             if let Some(parent) = path.parent() {
                 fs::create_dir_all(parent).await?;
             }
 
-            fs::write(path, json).await?;
+            let entries = self.entries.read().await.clone();
+            let mut rotation = self.rotation.lock().await;
+            let current = rotation.segments.len();
+            let size = self
+                .write_segment(path, &rotation, &entries, current)
+                .await?;
+
+            let start = rotation.starts.last().copied().unwrap_or(0);
+            let oversized = self.max_file_bytes.is_some_and(|max| size > max);
+            if oversized && entries.len() > start {
+                rotation.starts.push(entries.len());
+                rotation
+                    .segments
+                    .push(Self::segment_file_name(path, current + 1));
+                // Create the new segment before the first file lists it
+                self.write_segment(path, &rotation, &entries, current + 1)
+                    .await?;
+                self.write_segment(path, &rotation, &entries, 0).await?;
+            }
         }
 
+        let mut state = self.flush_state.lock().await;
+        state.pending_entries = 0;
+        state.last_flush = self.clock.now();
+
         Ok(())
     }
 
+    /// Write segment `index` of `entries` to its file, returning its size in bytes
+    async fn write_segment(
+        &self,
+        path: &Path,
+        rotation: &RotationState,
+        entries: &[TrajectoryEntry],
+        index: usize,
+    ) -> Result<u64> {
+        let start = if index == 0 {
+            0
+        } else {
+            rotation.starts[index - 1]
+        };
+        let end = rotation.starts.get(index).copied().unwrap_or(entries.len());
+        let started_at = self
+            .started_at
+            .or_else(|| entries.first().map(|e| e.timestamp));
+        let segments = if index == 0 {
+            rotation.segments.clone()
+        } else {
+            Vec::new()
+        };
+        let trajectory = self.build_trajectory(entries[start..end].to_vec(), started_at, segments);
+        let json = serde_json::to_string_pretty(&trajectory).map_err(|e| {
+            TrajectoryError::RecordingFailed {
+                message: format!("Failed to serialize trajectory: {}", e),
+            }
+        })?;
+
+        let file = if index == 0 {
+            path.to_path_buf()
+        } else {
+            Self::segment_path(path, &rotation.segments[index - 1])
+        };
+        fs::write(file, &json).await?;
+        Ok(json.len() as u64)
+    }
+
+    /// File name of segment `index`: `trajectory.json` becomes `trajectory.1.json`
+    fn segment_file_name(path: &Path, index: usize) -> String {
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy())
+            .unwrap_or_default();
+        match path.extension() {
+            Some(extension) => format!("{}.{}.{}", stem, index, extension.to_string_lossy()),
+            None => format!("{}.{}", stem, index),
+        }
+    }
+
+    /// Path of a segment listed in the first file at `path`
+    fn segment_path(path: &Path, segment: &str) -> PathBuf {
+        path.parent()
+            .map(|parent| parent.join(segment))
+            .unwrap_or_else(|| PathBuf::from(segment))
+    }
+
     /// Load a trajectory from file
+    ///
+    /// A rotated trajectory is read from all its segments and returned as one.
     pub async fn load<P: AsRef<Path>>(path: P) -> Result<Trajectory> {
-        let path = path.as_ref();
+        let parts = Self::load_segments(path.as_ref()).await?;
+        Ok(Self::stitch(&parts))
+    }
+
+    /// Read the file at `path` followed by any segments it lists
+    async fn load_segments(path: &Path) -> Result<Vec<Trajectory>> {
+        let first = Self::load_file(path).await?;
+        let mut parts = Vec::with_capacity(first.metadata.segments.len() + 1);
+        for segment in &first.metadata.segments {
+            parts.push(Self::load_file(&Self::segment_path(path, segment)).await?);
+        }
+        parts.insert(0, first);
+        Ok(parts)
+    }
+
+    /// Join segments into one trajectory, described by the first segment's
+    /// metadata updated to cover every entry
+    fn stitch(parts: &[Trajectory]) -> Trajectory {
+        let first = &parts[0];
+        if parts.len() == 1 {
+            return first.clone();
+        }
+        // A freshly rotated last segment has no entries yet
+        let completed_at = parts
+            .iter()
+            .rev()
+            .find_map(|part| part.metadata.completed_at);
+        let entries: Vec<TrajectoryEntry> = parts
+            .iter()
+            .flat_map(|part| part.entries.iter().cloned())
+            .collect();
+        let metadata = TrajectoryMetadata {
+            completed_at,
+            task: parts
+                .iter()
+                .rev()
+                .find_map(|part| part.metadata.task.clone()),
+            success: parts.iter().rev().find_map(|part| part.metadata.success),
+            total_steps: entries.len(),
+            duration_ms: completed_at
+                .map(|end| (end - first.metadata.started_at).num_milliseconds() as u64),
+            ..first.metadata.clone()
+        };
+        Trajectory { metadata, entries }
+    }
 
+    async fn load_file(path: &Path) -> Result<Trajectory> {
         if !path.exists() {
             return Err(TrajectoryError::LoadFailed {
                 path: path.to_string_lossy().to_string(),
@@ -153,14 +507,14 @@ impl TrajectoryRecorder {
         Ok(trajectory)
     }
 
-    /// Build a complete trajectory from recorded entries
-    async fn build_trajectory(&self) -> Trajectory {
-        let entries = self.entries.read().await.clone();
-
-        let started_at = entries
-            .first()
-            .map(|e| e.timestamp)
-            .unwrap_or_else(Utc::now);
+    /// Build a trajectory file's contents from its entries
+    fn build_trajectory(
+        &self,
+        entries: Vec<TrajectoryEntry>,
+        started_at: Option<DateTime<Utc>>,
+        segments: Vec<String>,
+    ) -> Trajectory {
+        let started_at = started_at.unwrap_or_else(Utc::now);
 
         let completed_at = entries.last().map(|e| e.timestamp);
 
@@ -183,7 +537,7 @@ impl TrajectoryRecorder {
         }
 
         let metadata = TrajectoryMetadata {
-            id: uuid::Uuid::new_v4().to_string(),
+            id: self.id.clone(),
             started_at,
             completed_at,
             version: "1.0".to_string(),
@@ -192,6 +546,7 @@ impl TrajectoryRecorder {
             success,
             total_steps: entries.len(),
             duration_ms,
+            segments,
         };
 
         Trajectory { metadata, entries }
@@ -201,12 +556,23 @@ impl TrajectoryRecorder {
     pub async fn clear(&self) {
         let mut entries = self.entries.write().await;
         entries.clear();
+        *self.rotation.lock().await = RotationState::default();
     }
 
     /// Get the file path if set
     pub fn file_path(&self) -> Option<&Path> {
         self.file_path.as_deref()
     }
+
+    /// Get the flush policy if set
+    pub fn flush_policy(&self) -> Option<&FlushPolicy> {
+        self.flush_policy.as_ref()
+    }
+
+    /// Offset added to the step of new entries (the last step of a resumed trajectory)
+    pub fn step_offset(&self) -> usize {
+        self.step_offset
+    }
 }
 
 impl Default for TrajectoryRecorder {
@@ -214,3 +580,207 @@ impl Default for TrajectoryRecorder {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trajectory::entry::LogLevel;
+
+    /// Clock that only moves when told to
+    struct ManualClock {
+        now: std::sync::Mutex<DateTime<Utc>>,
+    }
+
+    impl ManualClock {
+        fn new() -> Self {
+            Self {
+                now: std::sync::Mutex::new(Utc::now()),
+            }
+        }
+
+        fn advance(&self, duration: Duration) {
+            let mut now = self.now.lock().unwrap();
+            *now += chrono::Duration::from_std(duration).unwrap();
+        }
+    }
+
+    impl TrajectoryClock for ManualClock {
+        fn now(&self) -> DateTime<Utc> {
+            *self.now.lock().unwrap()
+        }
+    }
+
+    async fn saved_entry_count(path: &Path) -> Option<usize> {
+        if !path.exists() {
+            return None;
+        }
+        let trajectory = TrajectoryRecorder::load(path).await.unwrap();
+        Some(trajectory.entries.len())
+    }
+
+    fn log_entry(step: usize) -> TrajectoryEntry {
+        TrajectoryEntry::log(LogLevel::Info, format!("entry {}", step), step)
+    }
+
+    #[tokio::test]
+    async fn test_flush_on_entry_count_boundary() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trajectory.json");
+        let clock = Arc::new(ManualClock::new());
+        let recorder = TrajectoryRecorder::with_flush_policy_and_clock(
+            &path,
+            FlushPolicy::new(3, Duration::from_secs(60)),
+            clock,
+        );
+
+        recorder.record(log_entry(1)).await.unwrap();
+        recorder.record(log_entry(2)).await.unwrap();
+        assert_eq!(saved_entry_count(&path).await, None);
+
+        recorder.record(log_entry(3)).await.unwrap();
+        assert_eq!(saved_entry_count(&path).await, Some(3));
+
+        recorder.record(log_entry(4)).await.unwrap();
+        recorder.record(log_entry(5)).await.unwrap();
+        assert_eq!(saved_entry_count(&path).await, Some(3));
+
+        recorder.record(log_entry(6)).await.unwrap();
+        assert_eq!(saved_entry_count(&path).await, Some(6));
+    }
+
+    #[tokio::test]
+    async fn test_resume_continues_trajectory() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trajectory.json");
+
+        let recorder = TrajectoryRecorder::with_file(&path);
+        recorder.record(log_entry(1)).await.unwrap();
+        recorder.record(log_entry(2)).await.unwrap();
+        let before = TrajectoryRecorder::load(&path).await.unwrap();
+        drop(recorder);
+
+        // Simulated restart: the new run numbers its steps from 1 again
+        let resumed = TrajectoryRecorder::resume_from_file(&path).await.unwrap();
+        assert_eq!(resumed.step_offset(), 2);
+        resumed.record(log_entry(1)).await.unwrap();
+        resumed.record(log_entry(2)).await.unwrap();
+
+        let after = TrajectoryRecorder::load(&path).await.unwrap();
+        assert_eq!(after.metadata.id, before.metadata.id);
+        assert_eq!(after.metadata.started_at, before.metadata.started_at);
+        assert_eq!(
+            after.entries.iter().map(|e| e.step).collect::<Vec<_>>(),
+            vec![1, 2, 3, 4]
+        );
+        assert_eq!(
+            after.entries[..2]
+                .iter()
+                .map(|e| e.id.as_str())
+                .collect::<Vec<_>>(),
+            before
+                .entries
+                .iter()
+                .map(|e| e.id.as_str())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rotated_segments_load_as_one_trajectory() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trajectory.json");
+        let recorder = TrajectoryRecorder::with_file(&path).with_max_file_size(1024);
+
+        for step in 1..=12 {
+            recorder.record(log_entry(step)).await.unwrap();
+        }
+
+        let first: Trajectory =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert!(first.metadata.segments.len() >= 2);
+        assert!(first.entries.len() < 12);
+        for segment in &first.metadata.segments {
+            assert!(segment.starts_with("trajectory.") && segment.ends_with(".json"));
+            assert!(dir.path().join(segment).exists());
+        }
+        assert_eq!(first.metadata.segments[0], "trajectory.1.json");
+
+        let loaded = TrajectoryRecorder::load(&path).await.unwrap();
+        assert_eq!(loaded.metadata.total_steps, 12);
+        assert_eq!(
+            loaded.entries.iter().map(|e| e.step).collect::<Vec<_>>(),
+            (1..=12).collect::<Vec<_>>()
+        );
+
+        // Resuming appends to the last segment
+        drop(recorder);
+        let resumed = TrajectoryRecorder::resume_from_file(&path).await.unwrap();
+        resumed.record(log_entry(1)).await.unwrap();
+        let reloaded = TrajectoryRecorder::load(&path).await.unwrap();
+        assert_eq!(reloaded.entries.len(), 13);
+        assert_eq!(reloaded.entries[12].step, 13);
+        assert_eq!(reloaded.metadata.segments, first.metadata.segments);
+    }
+
+    #[tokio::test]
+    async fn test_resumed_recorder_keeps_flush_policy_and_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trajectory.json");
+        let recorder = TrajectoryRecorder::with_file(&path);
+        recorder.record(log_entry(1)).await.unwrap();
+        recorder.record(log_entry(2)).await.unwrap();
+        drop(recorder);
+
+        let resumed = TrajectoryRecorder::with_flush_policy(&path, FlushPolicy::every_entries(2))
+            .with_max_file_size(1024)
+            .resume()
+            .await
+            .unwrap();
+        assert_eq!(resumed.flush_policy(), Some(&FlushPolicy::every_entries(2)));
+
+        resumed.record(log_entry(1)).await.unwrap();
+        assert_eq!(saved_entry_count(&path).await, Some(2));
+        resumed.record(log_entry(2)).await.unwrap();
+        assert_eq!(saved_entry_count(&path).await, Some(4));
+
+        for step in 3..=12 {
+            resumed.record(log_entry(step)).await.unwrap();
+        }
+        let first: Trajectory =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert!(!first.metadata.segments.is_empty());
+        assert!(dir.path().join(&first.metadata.segments[0]).exists());
+
+        let loaded = TrajectoryRecorder::load(&path).await.unwrap();
+        assert_eq!(
+            loaded.entries.iter().map(|e| e.step).collect::<Vec<_>>(),
+            (1..=14).collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_flush_after_interval_elapses() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trajectory.json");
+        let clock = Arc::new(ManualClock::new());
+        let recorder = TrajectoryRecorder::with_flush_policy_and_clock(
+            &path,
+            FlushPolicy::new(100, Duration::from_secs(10)),
+            clock.clone(),
+        );
+
+        recorder.record(log_entry(1)).await.unwrap();
+        clock.advance(Duration::from_secs(5));
+        recorder.record(log_entry(2)).await.unwrap();
+        assert_eq!(saved_entry_count(&path).await, None);
+
+        clock.advance(Duration::from_secs(5));
+        recorder.record(log_entry(3)).await.unwrap();
+        assert_eq!(saved_entry_count(&path).await, Some(3));
+
+        // The interval restarts from the last flush
+        clock.advance(Duration::from_secs(9));
+        recorder.record(log_entry(4)).await.unwrap();
+        assert_eq!(saved_entry_count(&path).await, Some(3));
+    }
+}
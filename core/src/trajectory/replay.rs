@@ -0,0 +1,108 @@
+//! Serving recorded tool results instead of running tools
+
+use super::entry::{EntryType, TrajectoryEntry};
+use super::recorder::Trajectory;
+use crate::error::{Result, TrajectoryError};
+use crate::tools::{ToolCall, ToolResult};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// Answers tool calls with the results recorded in a trajectory
+///
+/// Calls are matched in recording order; each one must have the same tool
+/// name and arguments as the recorded call, otherwise replay fails.
+pub struct ReplayToolExecutor {
+    recorded: Mutex<VecDeque<(ToolCall, ToolResult)>>,
+}
+
+impl ReplayToolExecutor {
+    /// Collect the tool calls of `trajectory` that have a recorded result
+    pub fn from_trajectory(trajectory: &Trajectory) -> Self {
+        Self::from_entries(&trajectory.entries)
+    }
+
+    /// Collect the tool calls among `entries` that have a recorded result
+    pub fn from_entries(entries: &[TrajectoryEntry]) -> Self {
+        let mut results: HashMap<&str, &ToolResult> = HashMap::new();
+        for entry in entries {
+            if let EntryType::ToolResult { result } = &entry.entry_type {
+                results.insert(result.tool_call_id.as_str(), result);
+            }
+        }
+
+        let recorded = entries
+            .iter()
+            .filter_map(|entry| match &entry.entry_type {
+                EntryType::ToolCall { call } => results
+                    .get(call.id.as_str())
+                    .map(|result| (call.clone(), (*result).clone())),
+                _ => None,
+            })
+            .collect();
+
+        Self {
+            recorded: Mutex::new(recorded),
+        }
+    }
+
+    /// Number of recorded results not served yet
+    pub fn remaining(&self) -> usize {
+        self.recorded.lock().unwrap().len()
+    }
+
+    /// Return the recorded result for the next call, re-keyed to `call.id`
+    pub fn execute(&self, call: &ToolCall) -> Result<ToolResult> {
+        let mut recorded = self.recorded.lock().unwrap();
+        let actual = format!("{}({})", call.name, call.parameters);
+        let Some((expected_call, _)) = recorded.front() else {
+            return Err(TrajectoryError::ReplayMismatch {
+                expected: "no further tool calls".to_string(),
+                actual,
+            }
+            .into());
+        };
+        if expected_call.name != call.name || expected_call.parameters != call.parameters {
+            return Err(TrajectoryError::ReplayMismatch {
+                expected: format!("{}({})", expected_call.name, expected_call.parameters),
+                actual,
+            }
+            .into());
+        }
+
+        let (_, mut result) = recorded.pop_front().unwrap();
+        result.tool_call_id = call.id.clone();
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(id: &str, command: &str) -> ToolCall {
+        ToolCall {
+            id: id.to_string(),
+            name: "bash".to_string(),
+            parameters: serde_json::json!({ "command": command }),
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_replay_serves_results_in_order_and_rejects_mismatch() {
+        let replay = ReplayToolExecutor::from_entries(&[
+            TrajectoryEntry::tool_call(call("a", "ls"), 1),
+            TrajectoryEntry::tool_result(ToolResult::success("a", "Cargo.toml"), 1),
+            TrajectoryEntry::tool_call(call("b", "pwd"), 2),
+            TrajectoryEntry::tool_result(ToolResult::success("b", "/repo"), 2),
+        ]);
+        assert_eq!(replay.remaining(), 2);
+
+        let result = replay.execute(&call("new_1", "ls")).unwrap();
+        assert_eq!(result.content, "Cargo.toml");
+        assert_eq!(result.tool_call_id, "new_1");
+
+        assert!(replay.execute(&call("new_2", "rm -rf /")).is_err());
+        assert_eq!(replay.remaining(), 1);
+    }
+}
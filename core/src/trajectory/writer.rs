@@ -0,0 +1,73 @@
+//! Recording trajectory entries off the agent's critical path
+//!
+//! Only trajectory I/O is moved; agent events are still emitted inline,
+//! since outputs may wait on the user and must see events in step order.
+
+use super::{TrajectoryEntry, TrajectoryRecorder};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, oneshot};
+
+enum WriterMessage {
+    Record(Box<TrajectoryEntry>),
+    Flush(oneshot::Sender<()>),
+}
+
+/// Feeds entries to a [`TrajectoryRecorder`] from a background task
+///
+/// Entries are recorded in the order they were queued. At most `capacity`
+/// entries wait in the queue; queuing more waits for the writer to catch up.
+pub struct TrajectoryWriter {
+    tx: mpsc::Sender<WriterMessage>,
+    failure: Arc<Mutex<Option<String>>>,
+}
+
+impl TrajectoryWriter {
+    /// Start the background task; must be called within a Tokio runtime
+    pub fn spawn(recorder: Arc<TrajectoryRecorder>, capacity: usize) -> Self {
+        let (tx, mut rx) = mpsc::channel(capacity.max(1));
+        let failure = Arc::new(Mutex::new(None));
+        let task_failure = failure.clone();
+
+        tokio::spawn(async move {
+            while let Some(message) = rx.recv().await {
+                match message {
+                    WriterMessage::Record(entry) => {
+                        if let Err(e) = recorder.record(*entry).await {
+                            *task_failure.lock().unwrap() = Some(e.to_string());
+                        }
+                    }
+                    WriterMessage::Flush(done) => {
+                        let _ = done.send(());
+                    }
+                }
+            }
+        });
+
+        Self { tx, failure }
+    }
+
+    /// Queue an entry, waiting only while the queue is full
+    pub async fn record(&self, entry: TrajectoryEntry) {
+        if self
+            .tx
+            .send(WriterMessage::Record(Box::new(entry)))
+            .await
+            .is_err()
+        {
+            tracing::warn!("Trajectory writer stopped; entry dropped");
+        }
+    }
+
+    /// Wait until every queued entry has been recorded
+    pub async fn flush(&self) {
+        let (done, wait) = oneshot::channel();
+        if self.tx.send(WriterMessage::Flush(done)).await.is_ok() {
+            let _ = wait.await;
+        }
+    }
+
+    /// Error of a failed save not reported yet
+    pub fn take_failure(&self) -> Option<String> {
+        self.failure.lock().unwrap().take()
+    }
+}